@@ -8,24 +8,370 @@
 extern "C" {
 #endif
 
+/* Stable error codes reported in CResult.error_code. Values never change. */
+enum {
+    FFMPEG_ERROR_NONE = 0,
+    FFMPEG_ERROR_UNKNOWN = 1,
+    FFMPEG_ERROR_INVALID_ARGUMENT = 2,
+    FFMPEG_ERROR_INPUT_NOT_FOUND = 3,
+    FFMPEG_ERROR_UNSUPPORTED_FORMAT = 4,
+    FFMPEG_ERROR_UNSUPPORTED_CODEC = 5,
+    FFMPEG_ERROR_ENCODER_UNAVAILABLE = 6,
+    FFMPEG_ERROR_INVALID_BITRATE = 7,
+    FFMPEG_ERROR_CORRUPT_INPUT = 8,
+    FFMPEG_ERROR_PERMISSION_DENIED = 9,
+    FFMPEG_ERROR_DISK_FULL = 10,
+    FFMPEG_ERROR_CANCELLED = 11
+};
+
+/* Coarse grouping of error codes reported in CResult.error_category. */
+enum {
+    FFMPEG_ERROR_CATEGORY_NONE = 0,
+    FFMPEG_ERROR_CATEGORY_INTERNAL = 1,
+    FFMPEG_ERROR_CATEGORY_ARGUMENT = 2,
+    FFMPEG_ERROR_CATEGORY_INPUT = 3,
+    FFMPEG_ERROR_CATEGORY_CODEC = 4,
+    FFMPEG_ERROR_CATEGORY_OUTPUT = 5,
+    FFMPEG_ERROR_CATEGORY_CANCELLED = 6
+};
+
 typedef struct CResult {
     bool ok;
     char *error_message;
+    bool cancelled;
+    int32_t error_code;
+    int32_t error_category;
+    char *data;
 } CResult;
 
+/* Stage reported in CProgress.stage. */
+enum {
+    FFMPEG_PROGRESS_STAGE_PROBING = 0,
+    FFMPEG_PROGRESS_STAGE_ENCODING = 1,
+    FFMPEG_PROGRESS_STAGE_FLUSHING = 2,
+    FFMPEG_PROGRESS_STAGE_FINALIZING = 3
+};
+
+/* Progress payload, only valid for the duration of the callback. Unknown values are 0, except eta_seconds which is negative. */
+typedef struct CProgress {
+    double progress;
+    int32_t stage;
+    double processed_seconds;
+    double total_seconds;
+    uint64_t frames; /* video frames decoded or encoded; stays 0 when only copying or handling audio */
+    double fps;
+    double speed;
+    uint64_t bytes_written;
+    double eta_seconds;
+} CProgress;
+
+/* Audio codecs accepted in CAudioOptions.codec. */
+enum {
+    FFMPEG_AUDIO_CODEC_AUTO = 0,
+    FFMPEG_AUDIO_CODEC_AAC = 1,
+    FFMPEG_AUDIO_CODEC_OPUS = 2,
+    FFMPEG_AUDIO_CODEC_FLAC = 3,
+    FFMPEG_AUDIO_CODEC_ALAC = 4,
+    FFMPEG_AUDIO_CODEC_PCM = 5
+};
+
+/* Audio encoding options. Zero fields keep the source value (or the codec default bitrate). */
+typedef struct CAudioOptions {
+    int32_t codec;
+    int32_t bitrate;
+    int32_t sample_rate;
+    int32_t channels;
+} CAudioOptions;
+
 void *init_ffmpeg_plugin(void);
+void *init_ffmpeg_plugin_with_concurrency(int32_t max_concurrent_jobs);
 void deinit_ffmpeg_plugin(void *plugin);
 void free_c_result(CResult *result);
 
+uint64_t create_job(void *plugin);
+bool cancel_job(void *plugin, uint64_t job_handle);
+
+/* What reencode_video does with a stream the output container cannot hold as is. */
+enum {
+    FFMPEG_INCOMPATIBLE_STREAMS_TRANSCODE = 0,
+    FFMPEG_INCOMPATIBLE_STREAMS_DROP = 1,
+    FFMPEG_INCOMPATIBLE_STREAMS_FAIL = 2
+};
+
+/* How reencode_video fits the picture into the target size. */
+enum {
+    FFMPEG_RESIZE_MODE_EXACT = 0,
+    FFMPEG_RESIZE_MODE_FIT = 1,
+    FFMPEG_RESIZE_MODE_FILL = 2,
+    FFMPEG_RESIZE_MODE_LONG_EDGE = 3
+};
+
+/* Rate control modes accepted in CVideoOptions.rate_control. */
+enum {
+    FFMPEG_RATE_CONTROL_ABR = 0,
+    FFMPEG_RATE_CONTROL_CRF = 1,
+    FFMPEG_RATE_CONTROL_CBR = 2,
+    FFMPEG_RATE_CONTROL_TARGET_SIZE = 3
+};
+
+/* Video codecs accepted in CVideoOptions.codec. HEVC needs an FFmpeg build with libx265
+   (the `hevc` cargo feature), VP9 one with libvpx (the `vp9` feature) and AV1 one with
+   SVT-AV1 or libaom (the `av1` feature); without them, encoding fails with
+   FFMPEG_ERROR_ENCODER_UNAVAILABLE. ProRes (prores_ks, in .mov), DNxHR (.mov or .mxf) and
+   lossless FFV1 (.mkv) are FFmpeg's own encoders and always available. The output extension
+   picks the container, which must be able to hold the codec (VP9 or AV1 for .webm). */
+enum {
+    FFMPEG_VIDEO_CODEC_H264 = 0,
+    FFMPEG_VIDEO_CODEC_HEVC = 1,
+    FFMPEG_VIDEO_CODEC_VP9 = 2,
+    FFMPEG_VIDEO_CODEC_AV1 = 3,
+    FFMPEG_VIDEO_CODEC_PRORES = 4,
+    FFMPEG_VIDEO_CODEC_DNXHR = 5,
+    FFMPEG_VIDEO_CODEC_FFV1 = 6
+};
+
+/* Encoder presets accepted in CVideoOptions.preset, named after x264's; 0 keeps medium.
+   VP9 maps them onto libvpx's deadline and cpu-used, and AV1 onto the SVT-AV1 preset or
+   libaom's cpu-used; 0 keeps veryfast for AV1, which is slow on phone CPUs. */
+enum {
+    FFMPEG_PRESET_DEFAULT = 0,
+    FFMPEG_PRESET_ULTRAFAST = 1,
+    FFMPEG_PRESET_SUPERFAST = 2,
+    FFMPEG_PRESET_VERYFAST = 3,
+    FFMPEG_PRESET_FASTER = 4,
+    FFMPEG_PRESET_FAST = 5,
+    FFMPEG_PRESET_MEDIUM = 6,
+    FFMPEG_PRESET_SLOW = 7,
+    FFMPEG_PRESET_SLOWER = 8,
+    FFMPEG_PRESET_VERYSLOW = 9
+};
+
+/* Tunings accepted in CVideoOptions.tune, named after x264's; HEVC only takes ANIMATION
+   and ZEROLATENCY, VP9 only FILM and ZEROLATENCY, and AV1 none. */
+enum {
+    FFMPEG_TUNE_NONE = 0,
+    FFMPEG_TUNE_FILM = 1,
+    FFMPEG_TUNE_ANIMATION = 2,
+    FFMPEG_TUNE_STILLIMAGE = 3,
+    FFMPEG_TUNE_ZEROLATENCY = 4
+};
+
+/* H.264 profiles accepted in CVideoOptions.profile; all of them are 8-bit 4:2:0. */
+enum {
+    FFMPEG_H264_PROFILE_AUTO = 0,
+    FFMPEG_H264_PROFILE_BASELINE = 1,
+    FFMPEG_H264_PROFILE_MAIN = 2,
+    FFMPEG_H264_PROFILE_HIGH = 3
+};
+
+/* ProRes profiles accepted in CVideoOptions.prores_profile; 0 keeps HQ. The 422 profiles are
+   10-bit 4:2:2; 4444 and 4444 XQ are 10-bit 4:4:4 and keep the source's alpha channel. */
+enum {
+    FFMPEG_PRORES_PROFILE_DEFAULT = 0,
+    FFMPEG_PRORES_PROFILE_PROXY = 1,
+    FFMPEG_PRORES_PROFILE_LT = 2,
+    FFMPEG_PRORES_PROFILE_STANDARD = 3,
+    FFMPEG_PRORES_PROFILE_HQ = 4,
+    FFMPEG_PRORES_PROFILE_4444 = 5,
+    FFMPEG_PRORES_PROFILE_4444XQ = 6
+};
+
+/* DNxHR profiles accepted in CVideoOptions.dnxhr_profile; 0 keeps HQ. LB, SQ and HQ are 8-bit
+   4:2:2, HQX 10-bit 4:2:2 and 444 10-bit 4:4:4 (RGB for RGB sources). */
+enum {
+    FFMPEG_DNXHR_PROFILE_DEFAULT = 0,
+    FFMPEG_DNXHR_PROFILE_LB = 1,
+    FFMPEG_DNXHR_PROFILE_SQ = 2,
+    FFMPEG_DNXHR_PROFILE_HQ = 3,
+    FFMPEG_DNXHR_PROFILE_HQX = 4,
+    FFMPEG_DNXHR_PROFILE_444 = 5
+};
+
+/* Video encoding options. pad_color is 0xRRGGBB and only used by FFMPEG_RESIZE_MODE_FIT.
+   Zero numeric fields keep the default: CRF 23 (28 for HEVC, 31 for VP9 and 35 for AV1, whose
+   scales go to 63), no max_bitrate cap, and a buffer_size of 2 seconds at max_bitrate (1 second at the
+   bitrate for CBR). target_size is in bytes and
+   required by FFMPEG_RATE_CONTROL_TARGET_SIZE, which ignores the bitrate argument. passes = 2
+   runs a two-pass encode (ABR and target size only); progress covers both passes. passes = 0
+   runs two passes for a target size with H.264 and HEVC, and one otherwise. level is
+   ten times the level number, such as 31 for level 3.1, or 0 to let the encoder pick. HEVC
+   takes no profile (10-bit inputs are encoded as Main 10 when libx265 supports it), only the
+   animation and zerolatency tunes, and is tagged hvc1 in MP4 and MOV. VP9 takes no profile,
+   level, B-frames or second pass; its CRF with a max_bitrate is constrained quality.
+   tile_columns (up to 64) and tile_rows (up to 4) are VP9 tile counts, powers of two, and
+   VP9 uses row-based multithreading unless disable_row_mt is set. AV1 takes no profile,
+   level, tune, B-frames or second pass; like VP9, its CRF with a max_bitrate is constrained
+   quality.
+   ProRes, DNxHR and FFV1 are intra-only: their bitrate follows from prores_profile or
+   dnxhr_profile (FFV1 is lossless and keeps the source's pixel format), so they take no rate
+   control, preset, tune, level, GOP, B-frame or keyframe options, and the bitrate argument is
+   ignored. ffv1_slices is 4, 6, 9, 12, 16, 24 or 30; FFV1 is written as version 3 with slice
+   CRCs. MXF output carries 48 kHz PCM audio.
+   gop_frames and gop_seconds cap the keyframe interval (set at most one); with disable_scene_cut
+   keyframes land exactly on that interval. b_frames is 1-16, -1 for none, or 0 for the preset
+   default. keyframe_times lists keyframe_count output times in seconds where an IDR frame is
+   forced; it is only read during the call. With skip_if_compliant, an input already in the
+   output codec and an 8-bit 4:2:0 pixel format (or 10-bit for HEVC, VP9 and AV1) that fits the target size, bitrate, profile and level is copied or remuxed instead of
+   re-encoded, along with audio that needs no transcoding. */
+typedef struct CVideoOptions {
+    int32_t resize_mode;
+    uint32_t pad_color;
+    int32_t rate_control;
+    double crf;
+    int32_t max_bitrate;
+    int32_t buffer_size;
+    int64_t target_size;
+    int32_t passes;
+    int32_t preset;
+    int32_t tune;
+    int32_t profile;
+    int32_t level;
+    int32_t gop_frames;
+    double gop_seconds;
+    bool closed_gop;
+    bool disable_scene_cut;
+    int32_t b_frames;
+    const double *keyframe_times;
+    int32_t keyframe_count;
+    bool skip_if_compliant;
+    int32_t codec;
+    int32_t tile_columns;
+    int32_t tile_rows;
+    bool disable_row_mt;
+    int32_t prores_profile;
+    int32_t dnxhr_profile;
+    int32_t ffv1_slices;
+} CVideoOptions;
+
+/* A 0 target dimension is derived from the other one. A null video_options scales to exactly
+   the target size. A null audio_options copies audio streams instead of transcoding them.
+   On success, result->data holds {"path": "reencoded" | "remuxed" | "copied", "droppedStreams": [...]},
+   the indices of the input streams the output container could not hold. */
 CResult *reencode_video(
+    void *plugin,
+    uint64_t job_handle,
+    const char *input_path,
+    const char *output_path,
+    int32_t target_width,
+    int32_t target_height,
+    int32_t bitrate,
+    const CVideoOptions *video_options,
+    const CAudioOptions *audio_options,
+    int32_t incompatible_stream_policy,
+    void *swift_internal_data_structure_pointer,
+    int32_t (*inform_about_progress)(const CProgress *progress, void *swift_internal_data_structure_pointer)
+);
+
+uint64_t submit_reencode_video(
     void *plugin,
     const char *input_path,
     const char *output_path,
     int32_t target_width,
     int32_t target_height,
     int32_t bitrate,
+    const CVideoOptions *video_options,
+    const CAudioOptions *audio_options,
+    int32_t incompatible_stream_policy,
+    void *swift_internal_data_structure_pointer,
+    int32_t (*inform_about_progress)(const CProgress *progress, void *swift_internal_data_structure_pointer),
+    void (*on_complete)(uint64_t job_handle, CResult *result, void *swift_internal_data_structure_pointer)
+);
+
+/* Copies every stream without re-encoding; fails with FFMPEG_ERROR_UNSUPPORTED_CODEC if one cannot live in the output container. */
+CResult *remux(
+    void *plugin,
+    uint64_t job_handle,
+    const char *input_path,
+    const char *output_path,
+    void *swift_internal_data_structure_pointer,
+    int32_t (*inform_about_progress)(const CProgress *progress, void *swift_internal_data_structure_pointer)
+);
+
+uint64_t submit_remux(
+    void *plugin,
+    const char *input_path,
+    const char *output_path,
+    void *swift_internal_data_structure_pointer,
+    int32_t (*inform_about_progress)(const CProgress *progress, void *swift_internal_data_structure_pointer),
+    void (*on_complete)(uint64_t job_handle, CResult *result, void *swift_internal_data_structure_pointer)
+);
+
+/* stream_index < 0 picks the best audio stream. The output extension picks the container.
+   A null audio_options copies the stream when the container can hold it. */
+CResult *extract_audio(
+    void *plugin,
+    uint64_t job_handle,
+    const char *input_path,
+    const char *output_path,
+    int32_t stream_index,
+    const CAudioOptions *audio_options,
+    void *swift_internal_data_structure_pointer,
+    int32_t (*inform_about_progress)(const CProgress *progress, void *swift_internal_data_structure_pointer)
+);
+
+uint64_t submit_extract_audio(
+    void *plugin,
+    const char *input_path,
+    const char *output_path,
+    int32_t stream_index,
+    const CAudioOptions *audio_options,
+    void *swift_internal_data_structure_pointer,
+    int32_t (*inform_about_progress)(const CProgress *progress, void *swift_internal_data_structure_pointer),
+    void (*on_complete)(uint64_t job_handle, CResult *result, void *swift_internal_data_structure_pointer)
+);
+
+/* Modes accepted by trim. */
+enum {
+    FFMPEG_TRIM_MODE_COPY = 0,
+    FFMPEG_TRIM_MODE_ACCURATE = 1
+};
+
+/* end_seconds <= 0 keeps everything after start_seconds. The output starts at zero. */
+CResult *trim(
+    void *plugin,
+    uint64_t job_handle,
+    const char *input_path,
+    const char *output_path,
+    double start_seconds,
+    double end_seconds,
+    int32_t mode,
+    void *swift_internal_data_structure_pointer,
+    int32_t (*inform_about_progress)(const CProgress *progress, void *swift_internal_data_structure_pointer)
+);
+
+uint64_t submit_trim(
+    void *plugin,
+    const char *input_path,
+    const char *output_path,
+    double start_seconds,
+    double end_seconds,
+    int32_t mode,
     void *swift_internal_data_structure_pointer,
-    int32_t (*inform_about_progress)(double progress, void *swift_internal_data_structure_pointer)
+    int32_t (*inform_about_progress)(const CProgress *progress, void *swift_internal_data_structure_pointer),
+    void (*on_complete)(uint64_t job_handle, CResult *result, void *swift_internal_data_structure_pointer)
+);
+
+/* On success, result->data holds JSON describing the container, chapters and streams. */
+CResult *probe_media(void *plugin, const char *input_path);
+
+/* Image formats accepted by generate_thumbnail. WebP needs the webp build feature (libwebp);
+   without it, FFMPEG_IMAGE_FORMAT_WEBP fails with FFMPEG_ERROR_ENCODER_UNAVAILABLE. */
+enum {
+    FFMPEG_IMAGE_FORMAT_JPEG = 0,
+    FFMPEG_IMAGE_FORMAT_PNG = 1,
+    FFMPEG_IMAGE_FORMAT_WEBP = 2
+};
+
+/* A 0 width or height keeps the source aspect ratio. quality is 0.0-1.0 and ignored for PNG. */
+CResult *generate_thumbnail(
+    void *plugin,
+    const char *input_path,
+    const char *output_path,
+    double time_seconds,
+    int32_t target_width,
+    int32_t target_height,
+    int32_t image_format,
+    double quality
 );
 
 #ifdef __cplusplus
@@ -23,14 +23,17 @@ extension CResult {
     }
 }
 
-typealias FFmpegProgressCallback = @convention(c) (Double, UnsafeMutableRawPointer?) -> Int32
+typealias FFmpegProgressCallback = @convention(c) (UnsafePointer<CProgress>?, UnsafeMutableRawPointer?) -> Int32
 
 protocol FFmpegNativeBinding {
     func initPlugin() -> UnsafeMutableRawPointer?
     func deinitPlugin(_ plugin: UnsafeMutableRawPointer)
     func freeResult(_ rawResult: UnsafeMutablePointer<CResult>)
+    func createJob(plugin: UnsafeMutableRawPointer) -> UInt64
+    func cancelJob(plugin: UnsafeMutableRawPointer, jobHandle: UInt64) -> Bool
     func reencodeVideo(
         plugin: UnsafeMutableRawPointer,
+        jobHandle: UInt64,
         inputPath: UnsafePointer<CChar>,
         outputPath: UnsafePointer<CChar>,
         targetWidth: Int32,
@@ -67,8 +70,17 @@ private struct LinkedFFmpegNativeBindings: FFmpegNativeBinding {
         free_c_result(rawResult)
     }
 
+    func createJob(plugin: UnsafeMutableRawPointer) -> UInt64 {
+        create_job(plugin)
+    }
+
+    func cancelJob(plugin: UnsafeMutableRawPointer, jobHandle: UInt64) -> Bool {
+        cancel_job(plugin, jobHandle)
+    }
+
     func reencodeVideo(
         plugin: UnsafeMutableRawPointer,
+        jobHandle: UInt64,
         inputPath: UnsafePointer<CChar>,
         outputPath: UnsafePointer<CChar>,
         targetWidth: Int32,
@@ -79,11 +91,15 @@ private struct LinkedFFmpegNativeBindings: FFmpegNativeBinding {
     ) -> UnsafeMutablePointer<CResult>? {
         reencode_video(
             plugin,
+            jobHandle,
             inputPath,
             outputPath,
             targetWidth,
             targetHeight,
             bitrate,
+            nil,
+            nil,
+            0,
             statePointer,
             progressCallback
         )
@@ -266,6 +282,8 @@ private final class SelfForReencodeVideo {
     private let nativeBindings: any FFmpegNativeBinding
     private let audioExportSessionFactory: AudioExportSessionFactory
     private let nativeCoreReason: String?
+    private let jobHandlesLock = NSLock()
+    private var jobHandles: [String: UInt64] = [:]
 
     /// Progress callback closure that can be set from outside
     var onProgress: ((FFmpegProgressPayload) -> Void)?
@@ -385,6 +403,8 @@ private final class SelfForReencodeVideo {
         let resolvedInputPath = inputURL.path
         let resolvedOutputPath = outputURL.path
         let acceptedJob = FFmpegAcceptedJob(jobId: UUID().uuidString)
+        let jobHandle = nativeBindings.createJob(plugin: plugin)
+        setJobHandle(jobHandle, for: acceptedJob.jobId)
         let encodingState = SelfForReencodeVideo(
             jobId: acceptedJob.jobId,
             outputPath: outputPath,
@@ -394,11 +414,13 @@ private final class SelfForReencodeVideo {
         DispatchQueue.global(qos: .userInitiated).async { [self] in
             let statePointer = Unmanaged.passRetained(encodingState).toOpaque()
 
-            let progressCallback: FFmpegProgressCallback = { progress, selfPointer in
-                guard let selfPointer else {
+            let progressCallback: FFmpegProgressCallback = { progressPointer, selfPointer in
+                guard let selfPointer, let progressPointer else {
                     return -1
                 }
 
+                let progress = progressPointer.pointee.progress
+
                 let state = Unmanaged<SelfForReencodeVideo>.fromOpaque(selfPointer).takeUnretainedValue()
                 state.emit(
                     progress: min(max(progress, 0.0), 0.99),
@@ -413,6 +435,7 @@ private final class SelfForReencodeVideo {
                 resolvedOutputPath.withCString { outputCStr in
                     self.nativeBindings.reencodeVideo(
                         plugin: plugin,
+                        jobHandle: jobHandle,
                         inputPath: inputCStr,
                         outputPath: outputCStr,
                         targetWidth: width,
@@ -423,6 +446,7 @@ private final class SelfForReencodeVideo {
                     )
                 }
             }
+            self.removeJobHandle(for: acceptedJob.jobId)
 
             guard let resultPtr else {
                 let state = Unmanaged<SelfForReencodeVideo>.fromOpaque(statePointer).takeRetainedValue()
@@ -444,6 +468,12 @@ private final class SelfForReencodeVideo {
                     message: "Re-encoding completed.",
                     outputPath: state.outputPath
                 )
+            } else if result.cancelled {
+                state.emit(
+                    progress: 0.0,
+                    state: "failed",
+                    message: "Re-encoding was cancelled."
+                )
             } else {
                 state.emit(
                     progress: 0.0,
@@ -458,6 +488,37 @@ private final class SelfForReencodeVideo {
         return acceptedJob.jobId
     }
 
+    /// Ask the native core to stop a running re-encode job.
+    ///
+    /// Returns false when the job is unknown or has already finished. Not bridged to JavaScript yet.
+    func cancelJob(jobId: String) -> Bool {
+        guard let plugin = self.pointerToRustPlugin else {
+            return false
+        }
+
+        jobHandlesLock.lock()
+        let jobHandle = jobHandles[jobId]
+        jobHandlesLock.unlock()
+
+        guard let jobHandle else {
+            return false
+        }
+
+        return nativeBindings.cancelJob(plugin: plugin, jobHandle: jobHandle)
+    }
+
+    private func setJobHandle(_ jobHandle: UInt64, for jobId: String) {
+        jobHandlesLock.lock()
+        jobHandles[jobId] = jobHandle
+        jobHandlesLock.unlock()
+    }
+
+    private func removeJobHandle(for jobId: String) {
+        jobHandlesLock.lock()
+        jobHandles.removeValue(forKey: jobId)
+        jobHandlesLock.unlock()
+    }
+
     func convertImage(
         inputPath: String,
         outputPath: String,
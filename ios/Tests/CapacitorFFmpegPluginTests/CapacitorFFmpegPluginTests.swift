@@ -18,8 +18,20 @@ private struct FailingFFmpegNativeBindings: FFmpegNativeBinding {
         _ = rawResult
     }
 
+    func createJob(plugin: UnsafeMutableRawPointer) -> UInt64 {
+        _ = plugin
+        return 0
+    }
+
+    func cancelJob(plugin: UnsafeMutableRawPointer, jobHandle: UInt64) -> Bool {
+        _ = plugin
+        _ = jobHandle
+        return false
+    }
+
     func reencodeVideo(
         plugin: UnsafeMutableRawPointer,
+        jobHandle: UInt64,
         inputPath: UnsafePointer<CChar>,
         outputPath: UnsafePointer<CChar>,
         targetWidth: Int32,
@@ -29,6 +41,7 @@ private struct FailingFFmpegNativeBindings: FFmpegNativeBinding {
         progressCallback: @escaping FFmpegProgressCallback
     ) -> UnsafeMutablePointer<CResult>? {
         _ = plugin
+        _ = jobHandle
         _ = inputPath
         _ = outputPath
         _ = targetWidth
@@ -171,7 +184,7 @@ final class CapacitorFFmpegPluginTests: XCTestCase {
     }
 
     func testSuccessResultConvertsToSwiftSuccess() {
-        let result = CResult(ok: true, error_message: nil)
+        let result = CResult(ok: true, error_message: nil, cancelled: false, error_code: 0, error_category: 0, data: nil)
 
         switch result.toSwiftResult() {
         case .success:
@@ -185,7 +198,7 @@ final class CapacitorFFmpegPluginTests: XCTestCase {
         let errorPointer = strdup("native failure")
         defer { free(errorPointer) }
 
-        let result = CResult(ok: false, error_message: errorPointer)
+        let result = CResult(ok: false, error_message: errorPointer, cancelled: false, error_code: 1, error_category: 1, data: nil)
 
         switch result.toSwiftResult() {
         case .success:
@@ -0,0 +1,33 @@
+#!/bin/bash
+
+set -euo pipefail
+
+# ffmpeg-sys has no switch for libsvtav1; this patch adds the build-lib-svtav1 feature
+
+SCRIPT_DIR="$(cd "$(dirname "${BASH_SOURCE[0]}")" && pwd)"
+SUBMODULE_DIR="$SCRIPT_DIR/ffmpeg-sys"
+PATCH_PATH="$SCRIPT_DIR/patches/ffmpeg-sys-av1.patch"
+
+if [ ! -d "$SUBMODULE_DIR" ] || [ ! -f "$SUBMODULE_DIR/build.rs" ]; then
+    echo "Error: ffmpeg-sys submodule is missing at $SUBMODULE_DIR"
+    exit 1
+fi
+
+if [ ! -f "$PATCH_PATH" ]; then
+    echo "Error: patch file is missing at $PATCH_PATH"
+    exit 1
+fi
+
+if git -C "$SUBMODULE_DIR" apply --reverse --check "$PATCH_PATH" >/dev/null 2>&1; then
+    echo "ffmpeg-sys AV1 patch already applied"
+    exit 0
+fi
+
+if git -C "$SUBMODULE_DIR" apply --check "$PATCH_PATH" >/dev/null 2>&1; then
+    git -C "$SUBMODULE_DIR" apply "$PATCH_PATH"
+    echo "Applied ffmpeg-sys AV1 patch"
+    exit 0
+fi
+
+echo "Error: ffmpeg-sys AV1 patch could not be applied cleanly"
+exit 1
@@ -55,6 +55,36 @@ export CFLAGS="-arch arm64 -isysroot $IOSSIM_SDK -mios-simulator-version-min=$IO
 export CXXFLAGS="$CFLAGS"
 export LDFLAGS="-arch arm64 -isysroot $IOSSIM_SDK -mios-simulator-version-min=$IOS_MIN_VERSION"
 
+# ENABLE_X265=1 adds the libx265 HEVC encoder, built by build_x265_ios_sim_arm64.sh
+EXTRA_CONFIGURE_FLAGS=()
+if [ "${ENABLE_X265:-0}" = "1" ]; then
+    X265_PKGCONFIG_DIR="$SCRIPT_DIR/x265-build-ios-sim-arm64/lib/pkgconfig"
+    export PKG_CONFIG_PATH="${X265_PKGCONFIG_DIR}${PKG_CONFIG_PATH:+:${PKG_CONFIG_PATH}}"
+    EXTRA_CONFIGURE_FLAGS+=(--enable-gpl --enable-libx265 --enable-encoder=libx265)
+fi
+
+# ENABLE_VP9=1 adds the libvpx VP9 and libopus encoders for WebM output
+if [ "${ENABLE_VP9:-0}" = "1" ]; then
+    for LIBRARY_DIR in libvpx-build-ios-sim-arm64 opus-build-ios-sim-arm64; do
+        export PKG_CONFIG_PATH="$SCRIPT_DIR/$LIBRARY_DIR/lib/pkgconfig${PKG_CONFIG_PATH:+:${PKG_CONFIG_PATH}}"
+    done
+    EXTRA_CONFIGURE_FLAGS+=(--enable-libvpx --enable-encoder=libvpx_vp9 --enable-libopus --enable-encoder=libopus)
+fi
+
+# ENABLE_AV1=1 adds the SVT-AV1 encoder, built by build_svtav1_ios_sim_arm64.sh
+if [ "${ENABLE_AV1:-0}" = "1" ]; then
+    SVTAV1_PKGCONFIG_DIR="$SCRIPT_DIR/svtav1-build-ios-sim-arm64/lib/pkgconfig"
+    export PKG_CONFIG_PATH="${SVTAV1_PKGCONFIG_DIR}${PKG_CONFIG_PATH:+:${PKG_CONFIG_PATH}}"
+    EXTRA_CONFIGURE_FLAGS+=(--enable-libsvtav1 --enable-encoder=libsvtav1)
+fi
+
+# ENABLE_WEBP=1 adds the libwebp encoder for WebP thumbnails, built by build_libwebp_ios_sim_arm64.sh
+if [ "${ENABLE_WEBP:-0}" = "1" ]; then
+    LIBWEBP_PKGCONFIG_DIR="$SCRIPT_DIR/libwebp-build-ios-sim-arm64/lib/pkgconfig"
+    export PKG_CONFIG_PATH="${LIBWEBP_PKGCONFIG_DIR}${PKG_CONFIG_PATH:+:${PKG_CONFIG_PATH}}"
+    EXTRA_CONFIGURE_FLAGS+=(--enable-libwebp --enable-encoder=libwebp)
+fi
+
 # Create and enter build directory
 BUILD_DIR="$FFMPEG_SRC_DIR/build-ios-sim-arm64"
 mkdir -p "$BUILD_DIR"
@@ -98,28 +128,60 @@ echo "Configuring FFmpeg..."
     --enable-filter=fps \
     --enable-filter=format \
     --enable-filter=transpose \
+    --enable-filter=hflip \
+    --enable-filter=vflip \
     --enable-filter=crop \
     --enable-filter=pad \
     --disable-decoders \
     --enable-decoder=h264 \
     --enable-decoder=hevc \
+    --enable-decoder=vp8 \
+    --enable-decoder=vp9 \
     --enable-decoder=aac \
     --enable-decoder=mp3 \
     --enable-decoder=pcm_s16le \
+    --enable-decoder=flac \
+    --enable-decoder=alac \
+    --enable-decoder=opus \
+    --enable-decoder=vorbis \
+    --enable-decoder=prores \
+    --enable-decoder=dnxhd \
+    --enable-decoder=ffv1 \
     --disable-encoders \
     --enable-encoder=libx264 \
     --enable-encoder=aac \
     --enable-encoder=pcm_s16le \
+    --enable-encoder=mjpeg \
+    --enable-encoder=png \
+    --enable-encoder=flac \
+    --enable-encoder=alac \
+    --enable-encoder=prores_ks \
+    --enable-encoder=dnxhd \
+    --enable-encoder=ffv1 \
+    --enable-encoder=pcm_s24le \
+    --enable-zlib \
     --disable-muxers \
     --enable-muxer=mp4 \
     --enable-muxer=mov \
+    --enable-muxer=ipod \
+    --enable-muxer=adts \
+    --enable-muxer=matroska \
+    --enable-muxer=webm \
+    --enable-muxer=ogg \
+    --enable-muxer=wav \
+    --enable-muxer=mxf \
     --disable-demuxers \
     --enable-demuxer=mov \
     --enable-demuxer=mp4 \
     --enable-demuxer=aac \
     --enable-demuxer=mp3 \
+    --enable-demuxer=matroska \
+    --enable-demuxer=mpegts \
+    --enable-demuxer=wav \
+    --enable-demuxer=mxf \
     --disable-protocols \
-    --enable-protocol=file
+    --enable-protocol=file \
+    "${EXTRA_CONFIGURE_FLAGS[@]}"
 
 echo "Building FFmpeg..."
 make -j$(sysctl -n hw.ncpu)
@@ -9,11 +9,39 @@ export PKG_CONFIG_ALLOW_CROSS=1
 
 SCRIPT_DIR="$(cd "$(dirname "${BASH_SOURCE[0]}")" && pwd)"
 X264_PKGCONFIG_DIR="$SCRIPT_DIR/x264-build-ios/lib/pkgconfig"
+X265_PKGCONFIG_DIR="$SCRIPT_DIR/x265-build-ios/lib/pkgconfig"
+LIBVPX_PKGCONFIG_DIR="$SCRIPT_DIR/libvpx-build-ios/lib/pkgconfig"
+OPUS_PKGCONFIG_DIR="$SCRIPT_DIR/opus-build-ios/lib/pkgconfig"
+SVTAV1_PKGCONFIG_DIR="$SCRIPT_DIR/svtav1-build-ios/lib/pkgconfig"
+LIBWEBP_PKGCONFIG_DIR="$SCRIPT_DIR/libwebp-build-ios/lib/pkgconfig"
 
 if [ -d "$X264_PKGCONFIG_DIR" ]; then
     export PKG_CONFIG_PATH="${X264_PKGCONFIG_DIR}${PKG_CONFIG_PATH:+:${PKG_CONFIG_PATH}}"
 fi
 
+# ENABLE_X265=1 builds FFmpeg with libx265 for HEVC output; run build_x265_ios.sh first
+CARGO_FEATURES=()
+if [ "${ENABLE_X265:-0}" = "1" ]; then
+    CARGO_FEATURES+=(--features hevc)
+    export PKG_CONFIG_PATH="${X265_PKGCONFIG_DIR}${PKG_CONFIG_PATH:+:${PKG_CONFIG_PATH}}"
+fi
+# ENABLE_VP9=1 adds libvpx and libopus for WebM output; run build_libvpx_ios.sh and build_opus_ios.sh first
+if [ "${ENABLE_VP9:-0}" = "1" ]; then
+    CARGO_FEATURES+=(--features vp9)
+    export PKG_CONFIG_PATH="${LIBVPX_PKGCONFIG_DIR}:${OPUS_PKGCONFIG_DIR}${PKG_CONFIG_PATH:+:${PKG_CONFIG_PATH}}"
+fi
+# ENABLE_AV1=1 builds FFmpeg with SVT-AV1 for AV1 output; run build_svtav1_ios.sh first
+if [ "${ENABLE_AV1:-0}" = "1" ]; then
+    "$SCRIPT_DIR/apply_ffmpeg_sys_av1_patch.sh"
+    CARGO_FEATURES+=(--features av1)
+    export PKG_CONFIG_PATH="${SVTAV1_PKGCONFIG_DIR}${PKG_CONFIG_PATH:+:${PKG_CONFIG_PATH}}"
+fi
+# ENABLE_WEBP=1 builds FFmpeg with libwebp for WebP thumbnails; run build_libwebp_ios.sh first
+if [ "${ENABLE_WEBP:-0}" = "1" ]; then
+    CARGO_FEATURES+=(--features webp)
+    export PKG_CONFIG_PATH="${LIBWEBP_PKGCONFIG_DIR}${PKG_CONFIG_PATH:+:${PKG_CONFIG_PATH}}"
+fi
+
 echo "Building Rust library for iOS device only..."
 
 # Install iOS device target if not already installed
@@ -29,7 +57,7 @@ echo "Building for iOS device (aarch64-apple-ios)..."
 SDKROOT="$IOS_SDK" \
 CC="$(xcrun --sdk iphoneos --find clang)" \
 CFLAGS="-arch arm64 -isysroot $IOS_SDK" \
-cargo build --release --target aarch64-apple-ios
+cargo build --release --target aarch64-apple-ios "${CARGO_FEATURES[@]}"
 
 echo "Creating output directory..."
 mkdir -p target/universal/release
@@ -6,6 +6,11 @@ export PKG_CONFIG_ALLOW_CROSS=1
 
 SCRIPT_DIR="$(cd "$(dirname "${BASH_SOURCE[0]}")" && pwd)"
 X264_PKGCONFIG_DIR="$SCRIPT_DIR/x264-build-ios-sim-arm64/lib/pkgconfig"
+X265_PKGCONFIG_DIR="$SCRIPT_DIR/x265-build-ios-sim-arm64/lib/pkgconfig"
+LIBVPX_PKGCONFIG_DIR="$SCRIPT_DIR/libvpx-build-ios-sim-arm64/lib/pkgconfig"
+OPUS_PKGCONFIG_DIR="$SCRIPT_DIR/opus-build-ios-sim-arm64/lib/pkgconfig"
+SVTAV1_PKGCONFIG_DIR="$SCRIPT_DIR/svtav1-build-ios-sim-arm64/lib/pkgconfig"
+LIBWEBP_PKGCONFIG_DIR="$SCRIPT_DIR/libwebp-build-ios-sim-arm64/lib/pkgconfig"
 
 "$SCRIPT_DIR/apply_ffmpeg_sys_ios_sim_patch.sh"
 
@@ -13,6 +18,25 @@ if [ -d "$X264_PKGCONFIG_DIR" ]; then
     export PKG_CONFIG_PATH="${X264_PKGCONFIG_DIR}${PKG_CONFIG_PATH:+:${PKG_CONFIG_PATH}}"
 fi
 
+CARGO_FEATURES=()
+if [ "${ENABLE_X265:-0}" = "1" ]; then
+    CARGO_FEATURES+=(--features hevc)
+    export PKG_CONFIG_PATH="${X265_PKGCONFIG_DIR}${PKG_CONFIG_PATH:+:${PKG_CONFIG_PATH}}"
+fi
+if [ "${ENABLE_VP9:-0}" = "1" ]; then
+    CARGO_FEATURES+=(--features vp9)
+    export PKG_CONFIG_PATH="${LIBVPX_PKGCONFIG_DIR}:${OPUS_PKGCONFIG_DIR}${PKG_CONFIG_PATH:+:${PKG_CONFIG_PATH}}"
+fi
+if [ "${ENABLE_AV1:-0}" = "1" ]; then
+    "$SCRIPT_DIR/apply_ffmpeg_sys_av1_patch.sh"
+    CARGO_FEATURES+=(--features av1)
+    export PKG_CONFIG_PATH="${SVTAV1_PKGCONFIG_DIR}${PKG_CONFIG_PATH:+:${PKG_CONFIG_PATH}}"
+fi
+if [ "${ENABLE_WEBP:-0}" = "1" ]; then
+    CARGO_FEATURES+=(--features webp)
+    export PKG_CONFIG_PATH="${LIBWEBP_PKGCONFIG_DIR}${PKG_CONFIG_PATH:+:${PKG_CONFIG_PATH}}"
+fi
+
 echo "Building Rust library for iOS simulator ARM64..."
 
 rustup target add aarch64-apple-ios-sim
@@ -23,7 +47,7 @@ export IPHONEOS_DEPLOYMENT_TARGET="15.0"
 SDKROOT="$IOSSIM_SDK" \
 CC="$(xcrun --sdk iphonesimulator --find clang)" \
 CFLAGS="-arch arm64 -isysroot $IOSSIM_SDK -mios-simulator-version-min=15.0" \
-cargo build --release --target aarch64-apple-ios-sim
+cargo build --release --target aarch64-apple-ios-sim "${CARGO_FEATURES[@]}"
 
 mkdir -p target/universal/release
 cp target/aarch64-apple-ios-sim/release/libcapacitor_ffmpeg_rust.a target/universal/release/libcapacitor_ffmpeg_rust_sim_arm64.a
@@ -0,0 +1,81 @@
+#!/bin/bash
+
+# Build script for libvpx iOS device only
+
+set -e
+
+SCRIPT_DIR="$(cd "$(dirname "${BASH_SOURCE[0]}")" && pwd)"
+LIBVPX_DIR="$SCRIPT_DIR/rust/libvpx"
+LIBVPX_VERSION="v1.15.0"
+OUTPUT_DIR="$SCRIPT_DIR/libvpx-build-ios"
+
+echo "Building libvpx for iOS device only..."
+
+# Fetch libvpx if it is not there yet
+if [ ! -d "$LIBVPX_DIR" ]; then
+    echo "Cloning libvpx $LIBVPX_VERSION into $LIBVPX_DIR..."
+    git clone --depth 1 --branch "$LIBVPX_VERSION" https://chromium.googlesource.com/webm/libvpx "$LIBVPX_DIR"
+fi
+
+# Clean previous builds
+rm -rf "$OUTPUT_DIR"
+mkdir -p "$OUTPUT_DIR"
+
+cd "$LIBVPX_DIR"
+
+# Clean any previous builds
+make clean || true
+
+# Set iOS SDK environment
+IOS_SDK=$(xcrun --sdk iphoneos --show-sdk-path)
+export IPHONEOS_DEPLOYMENT_TARGET="11.0"
+
+echo "Using iOS SDK: $IOS_SDK"
+
+# Get iOS toolchain tools
+CC=$(xcrun --sdk iphoneos --find clang)
+CXX=$(xcrun --sdk iphoneos --find clang++)
+AR=$(xcrun --sdk iphoneos --find ar)
+RANLIB=$(xcrun --sdk iphoneos --find ranlib)
+STRIP=$(xcrun --sdk iphoneos --find strip)
+
+export CC CXX AR RANLIB STRIP
+
+export CFLAGS="-arch arm64 -isysroot $IOS_SDK -miphoneos-version-min=11.0"
+export CXXFLAGS="$CFLAGS"
+export LDFLAGS="-arch arm64 -isysroot $IOS_SDK -miphoneos-version-min=11.0"
+
+# generic-gnu takes the toolchain from the environment; like x264, this is built without assembly
+echo "Configuring libvpx for iOS device (aarch64-apple-ios)..."
+./configure \
+    --target=generic-gnu \
+    --enable-static \
+    --disable-shared \
+    --enable-pic \
+    --disable-examples \
+    --disable-tools \
+    --disable-docs \
+    --disable-unit-tests \
+    --disable-vp8 \
+    --enable-vp9-highbitdepth \
+    --prefix="$OUTPUT_DIR"
+
+echo "Building libvpx..."
+NCPU=$(sysctl -n hw.ncpu 2>/dev/null || echo 4)
+make -j$NCPU
+
+echo "Installing libvpx..."
+make install
+
+echo "libvpx iOS build complete!"
+echo "Static library: $OUTPUT_DIR/lib/libvpx.a"
+echo "Headers: $OUTPUT_DIR/include/"
+
+# Verify the build
+if [ -f "$OUTPUT_DIR/lib/libvpx.a" ]; then
+    echo "✓ Static library built successfully"
+    lipo -info "$OUTPUT_DIR/lib/libvpx.a"
+else
+    echo "✗ Build failed - static library not found"
+    exit 1
+fi
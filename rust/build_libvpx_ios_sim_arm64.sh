@@ -0,0 +1,55 @@
+#!/bin/bash
+
+set -e
+
+SCRIPT_DIR="$(cd "$(dirname "${BASH_SOURCE[0]}")" && pwd)"
+LIBVPX_DIR="$SCRIPT_DIR/rust/libvpx"
+LIBVPX_VERSION="v1.15.0"
+OUTPUT_DIR="$SCRIPT_DIR/libvpx-build-ios-sim-arm64"
+
+echo "Building libvpx for iOS Simulator ARM64..."
+
+if [ ! -d "$LIBVPX_DIR" ]; then
+    git clone --depth 1 --branch "$LIBVPX_VERSION" https://chromium.googlesource.com/webm/libvpx "$LIBVPX_DIR"
+fi
+
+rm -rf "$OUTPUT_DIR"
+mkdir -p "$OUTPUT_DIR"
+
+cd "$LIBVPX_DIR"
+
+make clean || true
+
+IOSSIM_SDK=$(xcrun --sdk iphonesimulator --show-sdk-path)
+export IPHONEOS_DEPLOYMENT_TARGET="15.0"
+
+CC=$(xcrun --sdk iphonesimulator --find clang)
+CXX=$(xcrun --sdk iphonesimulator --find clang++)
+AR=$(xcrun --sdk iphonesimulator --find ar)
+RANLIB=$(xcrun --sdk iphonesimulator --find ranlib)
+STRIP=$(xcrun --sdk iphonesimulator --find strip)
+
+export CC CXX AR RANLIB STRIP
+export CFLAGS="-arch arm64 -isysroot $IOSSIM_SDK -mios-simulator-version-min=15.0"
+export CXXFLAGS="$CFLAGS"
+export LDFLAGS="-arch arm64 -isysroot $IOSSIM_SDK -mios-simulator-version-min=15.0"
+
+./configure \
+    --target=generic-gnu \
+    --enable-static \
+    --disable-shared \
+    --enable-pic \
+    --disable-examples \
+    --disable-tools \
+    --disable-docs \
+    --disable-unit-tests \
+    --disable-vp8 \
+    --enable-vp9-highbitdepth \
+    --prefix="$OUTPUT_DIR"
+
+NCPU=$(sysctl -n hw.ncpu 2>/dev/null || echo 4)
+make -j"$NCPU"
+make install
+
+echo "libvpx iOS simulator build complete"
+echo "Static library: $OUTPUT_DIR/lib/libvpx.a"
@@ -0,0 +1,74 @@
+#!/bin/bash
+
+# Build script for libwebp iOS device only
+#
+# FFmpeg has no WebP encoder of its own, so WebP thumbnails are encoded with libwebp.
+
+set -e
+
+SCRIPT_DIR="$(cd "$(dirname "${BASH_SOURCE[0]}")" && pwd)"
+LIBWEBP_DIR="$SCRIPT_DIR/rust/libwebp"
+LIBWEBP_VERSION="v1.5.0"
+OUTPUT_DIR="$SCRIPT_DIR/libwebp-build-ios"
+BUILD_DIR="$LIBWEBP_DIR/build-ios"
+
+echo "Building libwebp for iOS device only..."
+
+# Fetch libwebp if it is not there yet
+if [ ! -d "$LIBWEBP_DIR" ]; then
+    echo "Cloning libwebp $LIBWEBP_VERSION into $LIBWEBP_DIR..."
+    git clone --depth 1 --branch "$LIBWEBP_VERSION" https://chromium.googlesource.com/webm/libwebp "$LIBWEBP_DIR"
+fi
+
+# Clean previous builds
+rm -rf "$OUTPUT_DIR" "$BUILD_DIR"
+mkdir -p "$OUTPUT_DIR" "$BUILD_DIR"
+
+# Set iOS SDK environment
+IOS_SDK=$(xcrun --sdk iphoneos --show-sdk-path)
+export IPHONEOS_DEPLOYMENT_TARGET="11.0"
+
+echo "Using iOS SDK: $IOS_SDK"
+
+# Only the encoder library is needed; the command line tools would pull in image libraries
+echo "Configuring libwebp for iOS device (aarch64-apple-ios)..."
+cd "$BUILD_DIR"
+cmake "$LIBWEBP_DIR" \
+    -DCMAKE_SYSTEM_NAME=iOS \
+    -DCMAKE_OSX_ARCHITECTURES=arm64 \
+    -DCMAKE_OSX_SYSROOT="$IOS_SDK" \
+    -DCMAKE_OSX_DEPLOYMENT_TARGET=11.0 \
+    -DCMAKE_C_COMPILER="$(xcrun --sdk iphoneos --find clang)" \
+    -DCMAKE_BUILD_TYPE=Release \
+    -DCMAKE_POSITION_INDEPENDENT_CODE=ON \
+    -DBUILD_SHARED_LIBS=OFF \
+    -DWEBP_BUILD_ANIM_UTILS=OFF \
+    -DWEBP_BUILD_CWEBP=OFF \
+    -DWEBP_BUILD_DWEBP=OFF \
+    -DWEBP_BUILD_GIF2WEBP=OFF \
+    -DWEBP_BUILD_IMG2WEBP=OFF \
+    -DWEBP_BUILD_VWEBP=OFF \
+    -DWEBP_BUILD_WEBPINFO=OFF \
+    -DWEBP_BUILD_WEBPMUX=OFF \
+    -DWEBP_BUILD_EXTRAS=OFF \
+    -DCMAKE_INSTALL_PREFIX="$OUTPUT_DIR"
+
+echo "Building libwebp..."
+NCPU=$(sysctl -n hw.ncpu 2>/dev/null || echo 4)
+make -j$NCPU
+
+echo "Installing libwebp..."
+make install
+
+echo "libwebp iOS build complete!"
+echo "Static libraries: $OUTPUT_DIR/lib/libwebp.a $OUTPUT_DIR/lib/libsharpyuv.a"
+echo "Headers: $OUTPUT_DIR/include/"
+
+# Verify the build
+if [ -f "$OUTPUT_DIR/lib/libwebp.a" ]; then
+    echo "✓ Static library built successfully"
+    lipo -info "$OUTPUT_DIR/lib/libwebp.a"
+else
+    echo "✗ Build failed - static library not found"
+    exit 1
+fi
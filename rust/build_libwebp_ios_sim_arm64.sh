@@ -0,0 +1,49 @@
+#!/bin/bash
+
+set -e
+
+SCRIPT_DIR="$(cd "$(dirname "${BASH_SOURCE[0]}")" && pwd)"
+LIBWEBP_DIR="$SCRIPT_DIR/rust/libwebp"
+LIBWEBP_VERSION="v1.5.0"
+OUTPUT_DIR="$SCRIPT_DIR/libwebp-build-ios-sim-arm64"
+BUILD_DIR="$LIBWEBP_DIR/build-ios-sim-arm64"
+
+echo "Building libwebp for iOS Simulator ARM64..."
+
+if [ ! -d "$LIBWEBP_DIR" ]; then
+    git clone --depth 1 --branch "$LIBWEBP_VERSION" https://chromium.googlesource.com/webm/libwebp "$LIBWEBP_DIR"
+fi
+
+rm -rf "$OUTPUT_DIR" "$BUILD_DIR"
+mkdir -p "$OUTPUT_DIR" "$BUILD_DIR"
+
+IOSSIM_SDK=$(xcrun --sdk iphonesimulator --show-sdk-path)
+export IPHONEOS_DEPLOYMENT_TARGET="15.0"
+
+cd "$BUILD_DIR"
+cmake "$LIBWEBP_DIR" \
+    -DCMAKE_SYSTEM_NAME=iOS \
+    -DCMAKE_OSX_ARCHITECTURES=arm64 \
+    -DCMAKE_OSX_SYSROOT="$IOSSIM_SDK" \
+    -DCMAKE_OSX_DEPLOYMENT_TARGET=15.0 \
+    -DCMAKE_C_COMPILER="$(xcrun --sdk iphonesimulator --find clang)" \
+    -DCMAKE_BUILD_TYPE=Release \
+    -DCMAKE_POSITION_INDEPENDENT_CODE=ON \
+    -DBUILD_SHARED_LIBS=OFF \
+    -DWEBP_BUILD_ANIM_UTILS=OFF \
+    -DWEBP_BUILD_CWEBP=OFF \
+    -DWEBP_BUILD_DWEBP=OFF \
+    -DWEBP_BUILD_GIF2WEBP=OFF \
+    -DWEBP_BUILD_IMG2WEBP=OFF \
+    -DWEBP_BUILD_VWEBP=OFF \
+    -DWEBP_BUILD_WEBPINFO=OFF \
+    -DWEBP_BUILD_WEBPMUX=OFF \
+    -DWEBP_BUILD_EXTRAS=OFF \
+    -DCMAKE_INSTALL_PREFIX="$OUTPUT_DIR"
+
+NCPU=$(sysctl -n hw.ncpu 2>/dev/null || echo 4)
+make -j"$NCPU"
+make install
+
+echo "libwebp iOS simulator build complete"
+echo "Static libraries: $OUTPUT_DIR/lib/libwebp.a $OUTPUT_DIR/lib/libsharpyuv.a"
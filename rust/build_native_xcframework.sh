@@ -12,8 +12,34 @@ cd "$SCRIPT_DIR"
 "$SCRIPT_DIR/apply_ffmpeg_sys_ios_sim_patch.sh"
 
 ./build_x264_ios.sh
+if [[ "${ENABLE_X265:-0}" == "1" ]]; then
+  ./build_x265_ios.sh
+fi
+if [[ "${ENABLE_VP9:-0}" == "1" ]]; then
+  ./build_libvpx_ios.sh
+  ./build_opus_ios.sh
+fi
+if [[ "${ENABLE_AV1:-0}" == "1" ]]; then
+  ./build_svtav1_ios.sh
+fi
+if [[ "${ENABLE_WEBP:-0}" == "1" ]]; then
+  ./build_libwebp_ios.sh
+fi
 ./build_ios.sh
 ./build_x264_ios_sim_arm64.sh
+if [[ "${ENABLE_X265:-0}" == "1" ]]; then
+  ./build_x265_ios_sim_arm64.sh
+fi
+if [[ "${ENABLE_VP9:-0}" == "1" ]]; then
+  ./build_libvpx_ios_sim_arm64.sh
+  ./build_opus_ios_sim_arm64.sh
+fi
+if [[ "${ENABLE_AV1:-0}" == "1" ]]; then
+  ./build_svtav1_ios_sim_arm64.sh
+fi
+if [[ "${ENABLE_WEBP:-0}" == "1" ]]; then
+  ./build_libwebp_ios_sim_arm64.sh
+fi
 ./build_ios_sim_arm64.sh
 
 rm -rf "$TEMP_DIR" "$OUTPUT_DIR"
@@ -22,11 +48,35 @@ mkdir -p "$TEMP_DIR"
 device_libs=(target/universal/release/libcapacitor_ffmpeg_rust_device.a)
 device_libs+=("${(@f)$(find target/aarch64-apple-ios/release/build -path '*/out/dist/lib/*.a' | sort)}")
 device_libs+=(x264-build-ios/lib/libx264.a)
+if [[ "${ENABLE_X265:-0}" == "1" ]]; then
+  device_libs+=(x265-build-ios/lib/libx265.a)
+fi
+if [[ "${ENABLE_VP9:-0}" == "1" ]]; then
+  device_libs+=(libvpx-build-ios/lib/libvpx.a opus-build-ios/lib/libopus.a)
+fi
+if [[ "${ENABLE_AV1:-0}" == "1" ]]; then
+  device_libs+=(svtav1-build-ios/lib/libSvtAv1Enc.a)
+fi
+if [[ "${ENABLE_WEBP:-0}" == "1" ]]; then
+  device_libs+=(libwebp-build-ios/lib/libwebp.a libwebp-build-ios/lib/libsharpyuv.a)
+fi
 libtool -static -o "$TEMP_DIR/libcapacitor_ffmpeg_native_device.a" "${device_libs[@]}"
 
 simulator_libs=(target/universal/release/libcapacitor_ffmpeg_rust_sim_arm64.a)
 simulator_libs+=("${(@f)$(find target/aarch64-apple-ios-sim/release/build -path '*/out/dist/lib/*.a' | sort)}")
 simulator_libs+=(x264-build-ios-sim-arm64/lib/libx264.a)
+if [[ "${ENABLE_X265:-0}" == "1" ]]; then
+  simulator_libs+=(x265-build-ios-sim-arm64/lib/libx265.a)
+fi
+if [[ "${ENABLE_VP9:-0}" == "1" ]]; then
+  simulator_libs+=(libvpx-build-ios-sim-arm64/lib/libvpx.a opus-build-ios-sim-arm64/lib/libopus.a)
+fi
+if [[ "${ENABLE_AV1:-0}" == "1" ]]; then
+  simulator_libs+=(svtav1-build-ios-sim-arm64/lib/libSvtAv1Enc.a)
+fi
+if [[ "${ENABLE_WEBP:-0}" == "1" ]]; then
+  simulator_libs+=(libwebp-build-ios-sim-arm64/lib/libwebp.a libwebp-build-ios-sim-arm64/lib/libsharpyuv.a)
+fi
 libtool -static -o "$TEMP_DIR/libcapacitor_ffmpeg_native_sim_arm64.a" "${simulator_libs[@]}"
 
 xcodebuild -create-xcframework \
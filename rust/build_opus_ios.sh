@@ -0,0 +1,66 @@
+#!/bin/bash
+
+# Build script for libopus iOS device only
+#
+# FFmpeg's own Opus encoder is experimental, so WebM audio is encoded with libopus.
+
+set -e
+
+SCRIPT_DIR="$(cd "$(dirname "${BASH_SOURCE[0]}")" && pwd)"
+OPUS_DIR="$SCRIPT_DIR/rust/opus"
+OPUS_VERSION="v1.5.2"
+OUTPUT_DIR="$SCRIPT_DIR/opus-build-ios"
+BUILD_DIR="$OPUS_DIR/build-ios"
+
+echo "Building libopus for iOS device only..."
+
+# Fetch opus if it is not there yet
+if [ ! -d "$OPUS_DIR" ]; then
+    echo "Cloning opus $OPUS_VERSION into $OPUS_DIR..."
+    git clone --depth 1 --branch "$OPUS_VERSION" https://gitlab.xiph.org/xiph/opus.git "$OPUS_DIR"
+fi
+
+# Clean previous builds
+rm -rf "$OUTPUT_DIR" "$BUILD_DIR"
+mkdir -p "$OUTPUT_DIR" "$BUILD_DIR"
+
+# Set iOS SDK environment
+IOS_SDK=$(xcrun --sdk iphoneos --show-sdk-path)
+export IPHONEOS_DEPLOYMENT_TARGET="11.0"
+
+echo "Using iOS SDK: $IOS_SDK"
+
+echo "Configuring libopus for iOS device (aarch64-apple-ios)..."
+cd "$BUILD_DIR"
+cmake "$OPUS_DIR" \
+    -DCMAKE_SYSTEM_NAME=iOS \
+    -DCMAKE_OSX_ARCHITECTURES=arm64 \
+    -DCMAKE_OSX_SYSROOT="$IOS_SDK" \
+    -DCMAKE_OSX_DEPLOYMENT_TARGET=11.0 \
+    -DCMAKE_C_COMPILER="$(xcrun --sdk iphoneos --find clang)" \
+    -DCMAKE_BUILD_TYPE=Release \
+    -DCMAKE_POSITION_INDEPENDENT_CODE=ON \
+    -DBUILD_SHARED_LIBS=OFF \
+    -DOPUS_BUILD_PROGRAMS=OFF \
+    -DOPUS_BUILD_TESTING=OFF \
+    -DCMAKE_INSTALL_PREFIX="$OUTPUT_DIR"
+
+echo "Building libopus..."
+NCPU=$(sysctl -n hw.ncpu 2>/dev/null || echo 4)
+make -j$NCPU
+
+echo "Installing libopus..."
+make install
+
+echo "libopus iOS build complete!"
+echo "Static library: $OUTPUT_DIR/lib/libopus.a"
+echo "Headers: $OUTPUT_DIR/include/"
+
+# Verify the build
+if [ -f "$OUTPUT_DIR/lib/libopus.a" ]; then
+    echo "✓ Static library built successfully"
+    lipo -info "$OUTPUT_DIR/lib/libopus.a"
+else
+    echo "✗ Build failed - static library not found"
+    exit 1
+fi
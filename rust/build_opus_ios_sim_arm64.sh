@@ -0,0 +1,42 @@
+#!/bin/bash
+
+set -e
+
+SCRIPT_DIR="$(cd "$(dirname "${BASH_SOURCE[0]}")" && pwd)"
+OPUS_DIR="$SCRIPT_DIR/rust/opus"
+OPUS_VERSION="v1.5.2"
+OUTPUT_DIR="$SCRIPT_DIR/opus-build-ios-sim-arm64"
+BUILD_DIR="$OPUS_DIR/build-ios-sim-arm64"
+
+echo "Building libopus for iOS Simulator ARM64..."
+
+if [ ! -d "$OPUS_DIR" ]; then
+    git clone --depth 1 --branch "$OPUS_VERSION" https://gitlab.xiph.org/xiph/opus.git "$OPUS_DIR"
+fi
+
+rm -rf "$OUTPUT_DIR" "$BUILD_DIR"
+mkdir -p "$OUTPUT_DIR" "$BUILD_DIR"
+
+IOSSIM_SDK=$(xcrun --sdk iphonesimulator --show-sdk-path)
+export IPHONEOS_DEPLOYMENT_TARGET="15.0"
+
+cd "$BUILD_DIR"
+cmake "$OPUS_DIR" \
+    -DCMAKE_SYSTEM_NAME=iOS \
+    -DCMAKE_OSX_ARCHITECTURES=arm64 \
+    -DCMAKE_OSX_SYSROOT="$IOSSIM_SDK" \
+    -DCMAKE_OSX_DEPLOYMENT_TARGET=15.0 \
+    -DCMAKE_C_COMPILER="$(xcrun --sdk iphonesimulator --find clang)" \
+    -DCMAKE_BUILD_TYPE=Release \
+    -DCMAKE_POSITION_INDEPENDENT_CODE=ON \
+    -DBUILD_SHARED_LIBS=OFF \
+    -DOPUS_BUILD_PROGRAMS=OFF \
+    -DOPUS_BUILD_TESTING=OFF \
+    -DCMAKE_INSTALL_PREFIX="$OUTPUT_DIR"
+
+NCPU=$(sysctl -n hw.ncpu 2>/dev/null || echo 4)
+make -j"$NCPU"
+make install
+
+echo "libopus iOS simulator build complete"
+echo "Static library: $OUTPUT_DIR/lib/libopus.a"
@@ -0,0 +1,69 @@
+#!/bin/bash
+
+# Build script for SVT-AV1 iOS device only
+#
+# Only the encoder library is built; FFmpeg decodes AV1 with its own decoder.
+
+set -e
+
+SCRIPT_DIR="$(cd "$(dirname "${BASH_SOURCE[0]}")" && pwd)"
+SVTAV1_DIR="$SCRIPT_DIR/rust/SVT-AV1"
+SVTAV1_VERSION="v2.3.0"
+OUTPUT_DIR="$SCRIPT_DIR/svtav1-build-ios"
+BUILD_DIR="$SVTAV1_DIR/build-ios"
+
+echo "Building SVT-AV1 for iOS device only..."
+
+# Fetch SVT-AV1 if it is not there yet
+if [ ! -d "$SVTAV1_DIR" ]; then
+    echo "Cloning SVT-AV1 $SVTAV1_VERSION into $SVTAV1_DIR..."
+    git clone --depth 1 --branch "$SVTAV1_VERSION" https://gitlab.com/AOMediaCodec/SVT-AV1.git "$SVTAV1_DIR"
+fi
+
+# Clean previous builds
+rm -rf "$OUTPUT_DIR" "$BUILD_DIR"
+mkdir -p "$OUTPUT_DIR" "$BUILD_DIR"
+
+# Set iOS SDK environment
+IOS_SDK=$(xcrun --sdk iphoneos --show-sdk-path)
+export IPHONEOS_DEPLOYMENT_TARGET="11.0"
+
+echo "Using iOS SDK: $IOS_SDK"
+
+echo "Configuring SVT-AV1 for iOS device (aarch64-apple-ios)..."
+cd "$BUILD_DIR"
+cmake "$SVTAV1_DIR" \
+    -DCMAKE_SYSTEM_NAME=iOS \
+    -DCMAKE_SYSTEM_PROCESSOR=aarch64 \
+    -DCMAKE_OSX_ARCHITECTURES=arm64 \
+    -DCMAKE_OSX_SYSROOT="$IOS_SDK" \
+    -DCMAKE_OSX_DEPLOYMENT_TARGET=11.0 \
+    -DCMAKE_C_COMPILER="$(xcrun --sdk iphoneos --find clang)" \
+    -DCMAKE_CXX_COMPILER="$(xcrun --sdk iphoneos --find clang++)" \
+    -DCMAKE_BUILD_TYPE=Release \
+    -DCMAKE_POSITION_INDEPENDENT_CODE=ON \
+    -DBUILD_SHARED_LIBS=OFF \
+    -DBUILD_APPS=OFF \
+    -DBUILD_DEC=OFF \
+    -DBUILD_TESTING=OFF \
+    -DCMAKE_INSTALL_PREFIX="$OUTPUT_DIR"
+
+echo "Building SVT-AV1..."
+NCPU=$(sysctl -n hw.ncpu 2>/dev/null || echo 4)
+make -j$NCPU
+
+echo "Installing SVT-AV1..."
+make install
+
+echo "SVT-AV1 iOS build complete!"
+echo "Static library: $OUTPUT_DIR/lib/libSvtAv1Enc.a"
+echo "Headers: $OUTPUT_DIR/include/"
+
+# Verify the build
+if [ -f "$OUTPUT_DIR/lib/libSvtAv1Enc.a" ]; then
+    echo "✓ Static library built successfully"
+    lipo -info "$OUTPUT_DIR/lib/libSvtAv1Enc.a"
+else
+    echo "✗ Build failed - static library not found"
+    exit 1
+fi
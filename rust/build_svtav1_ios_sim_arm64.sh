@@ -0,0 +1,45 @@
+#!/bin/bash
+
+set -e
+
+SCRIPT_DIR="$(cd "$(dirname "${BASH_SOURCE[0]}")" && pwd)"
+SVTAV1_DIR="$SCRIPT_DIR/rust/SVT-AV1"
+SVTAV1_VERSION="v2.3.0"
+OUTPUT_DIR="$SCRIPT_DIR/svtav1-build-ios-sim-arm64"
+BUILD_DIR="$SVTAV1_DIR/build-ios-sim-arm64"
+
+echo "Building SVT-AV1 for iOS Simulator ARM64..."
+
+if [ ! -d "$SVTAV1_DIR" ]; then
+    git clone --depth 1 --branch "$SVTAV1_VERSION" https://gitlab.com/AOMediaCodec/SVT-AV1.git "$SVTAV1_DIR"
+fi
+
+rm -rf "$OUTPUT_DIR" "$BUILD_DIR"
+mkdir -p "$OUTPUT_DIR" "$BUILD_DIR"
+
+IOSSIM_SDK=$(xcrun --sdk iphonesimulator --show-sdk-path)
+export IPHONEOS_DEPLOYMENT_TARGET="15.0"
+
+cd "$BUILD_DIR"
+cmake "$SVTAV1_DIR" \
+    -DCMAKE_SYSTEM_NAME=iOS \
+    -DCMAKE_SYSTEM_PROCESSOR=aarch64 \
+    -DCMAKE_OSX_ARCHITECTURES=arm64 \
+    -DCMAKE_OSX_SYSROOT="$IOSSIM_SDK" \
+    -DCMAKE_OSX_DEPLOYMENT_TARGET=15.0 \
+    -DCMAKE_C_COMPILER="$(xcrun --sdk iphonesimulator --find clang)" \
+    -DCMAKE_CXX_COMPILER="$(xcrun --sdk iphonesimulator --find clang++)" \
+    -DCMAKE_BUILD_TYPE=Release \
+    -DCMAKE_POSITION_INDEPENDENT_CODE=ON \
+    -DBUILD_SHARED_LIBS=OFF \
+    -DBUILD_APPS=OFF \
+    -DBUILD_DEC=OFF \
+    -DBUILD_TESTING=OFF \
+    -DCMAKE_INSTALL_PREFIX="$OUTPUT_DIR"
+
+NCPU=$(sysctl -n hw.ncpu 2>/dev/null || echo 4)
+make -j"$NCPU"
+make install
+
+echo "SVT-AV1 iOS simulator build complete"
+echo "Static library: $OUTPUT_DIR/lib/libSvtAv1Enc.a"
@@ -0,0 +1,87 @@
+#!/bin/bash
+
+# Build script for x265 iOS device only
+#
+# Builds 8-bit x265 with the 10-bit encoder linked in, so that FFmpeg's libx265
+# accepts both 8-bit and 10-bit input.
+
+set -e
+
+SCRIPT_DIR="$(cd "$(dirname "${BASH_SOURCE[0]}")" && pwd)"
+X265_DIR="$SCRIPT_DIR/rust/x265"
+X265_VERSION="4.1"
+OUTPUT_DIR="$SCRIPT_DIR/x265-build-ios"
+BUILD_DIR="$X265_DIR/build-ios"
+
+echo "Building x265 for iOS device only..."
+
+# Fetch x265 if it is not there yet
+if [ ! -d "$X265_DIR" ]; then
+    echo "Cloning x265 $X265_VERSION into $X265_DIR..."
+    git clone --depth 1 --branch "$X265_VERSION" https://bitbucket.org/multicoreware/x265_git.git "$X265_DIR"
+fi
+
+# Clean previous builds
+rm -rf "$OUTPUT_DIR" "$BUILD_DIR"
+mkdir -p "$OUTPUT_DIR" "$BUILD_DIR/10bit" "$BUILD_DIR/8bit"
+
+# Set iOS SDK environment
+IOS_SDK=$(xcrun --sdk iphoneos --show-sdk-path)
+export IPHONEOS_DEPLOYMENT_TARGET="11.0"
+
+echo "Using iOS SDK: $IOS_SDK"
+
+CMAKE_FLAGS=(
+    -DCMAKE_SYSTEM_NAME=iOS
+    -DCMAKE_SYSTEM_PROCESSOR=aarch64
+    -DCMAKE_OSX_ARCHITECTURES=arm64
+    -DCMAKE_OSX_SYSROOT="$IOS_SDK"
+    -DCMAKE_OSX_DEPLOYMENT_TARGET=11.0
+    -DCMAKE_C_COMPILER="$(xcrun --sdk iphoneos --find clang)"
+    -DCMAKE_CXX_COMPILER="$(xcrun --sdk iphoneos --find clang++)"
+    -DCMAKE_BUILD_TYPE=Release
+    -DCMAKE_POSITION_INDEPENDENT_CODE=ON
+    -DENABLE_SHARED=OFF
+    -DENABLE_CLI=OFF
+    -DENABLE_ASSEMBLY=OFF
+)
+
+NCPU=$(sysctl -n hw.ncpu 2>/dev/null || echo 4)
+
+# 10-bit encoder, linked into the 8-bit library below
+echo "Configuring x265 10-bit for iOS device (aarch64-apple-ios)..."
+cd "$BUILD_DIR/10bit"
+cmake "$X265_DIR/source" "${CMAKE_FLAGS[@]}" \
+    -DHIGH_BIT_DEPTH=ON \
+    -DEXPORT_C_API=OFF
+make -j$NCPU
+cp libx265.a "$BUILD_DIR/8bit/libx265_main10.a"
+
+echo "Configuring x265 8-bit for iOS device (aarch64-apple-ios)..."
+cd "$BUILD_DIR/8bit"
+cmake "$X265_DIR/source" "${CMAKE_FLAGS[@]}" \
+    -DEXTRA_LIB="x265_main10.a" \
+    -DEXTRA_LINK_FLAGS=-L. \
+    -DLINKED_10BIT=ON \
+    -DCMAKE_INSTALL_PREFIX="$OUTPUT_DIR"
+make -j$NCPU
+
+echo "Installing x265..."
+make install
+
+# Merge both bit depths into the installed library
+mv libx265.a libx265_main.a
+libtool -static -o "$OUTPUT_DIR/lib/libx265.a" libx265_main.a libx265_main10.a
+
+echo "x265 iOS build complete!"
+echo "Static library: $OUTPUT_DIR/lib/libx265.a"
+echo "Headers: $OUTPUT_DIR/include/"
+
+# Verify the build
+if [ -f "$OUTPUT_DIR/lib/libx265.a" ]; then
+    echo "✓ Static library built successfully"
+    lipo -info "$OUTPUT_DIR/lib/libx265.a"
+else
+    echo "✗ Build failed - static library not found"
+    exit 1
+fi
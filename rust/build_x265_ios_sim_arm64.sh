@@ -0,0 +1,58 @@
+#!/bin/bash
+
+set -e
+
+SCRIPT_DIR="$(cd "$(dirname "${BASH_SOURCE[0]}")" && pwd)"
+X265_DIR="$SCRIPT_DIR/rust/x265"
+X265_VERSION="4.1"
+OUTPUT_DIR="$SCRIPT_DIR/x265-build-ios-sim-arm64"
+BUILD_DIR="$X265_DIR/build-ios-sim-arm64"
+
+echo "Building x265 for iOS Simulator ARM64..."
+
+if [ ! -d "$X265_DIR" ]; then
+    git clone --depth 1 --branch "$X265_VERSION" https://bitbucket.org/multicoreware/x265_git.git "$X265_DIR"
+fi
+
+rm -rf "$OUTPUT_DIR" "$BUILD_DIR"
+mkdir -p "$OUTPUT_DIR" "$BUILD_DIR/10bit" "$BUILD_DIR/8bit"
+
+IOSSIM_SDK=$(xcrun --sdk iphonesimulator --show-sdk-path)
+export IPHONEOS_DEPLOYMENT_TARGET="15.0"
+
+CMAKE_FLAGS=(
+    -DCMAKE_SYSTEM_NAME=iOS
+    -DCMAKE_SYSTEM_PROCESSOR=aarch64
+    -DCMAKE_OSX_ARCHITECTURES=arm64
+    -DCMAKE_OSX_SYSROOT="$IOSSIM_SDK"
+    -DCMAKE_OSX_DEPLOYMENT_TARGET=15.0
+    -DCMAKE_C_COMPILER="$(xcrun --sdk iphonesimulator --find clang)"
+    -DCMAKE_CXX_COMPILER="$(xcrun --sdk iphonesimulator --find clang++)"
+    -DCMAKE_BUILD_TYPE=Release
+    -DCMAKE_POSITION_INDEPENDENT_CODE=ON
+    -DENABLE_SHARED=OFF
+    -DENABLE_CLI=OFF
+    -DENABLE_ASSEMBLY=OFF
+)
+
+NCPU=$(sysctl -n hw.ncpu 2>/dev/null || echo 4)
+
+cd "$BUILD_DIR/10bit"
+cmake "$X265_DIR/source" "${CMAKE_FLAGS[@]}" -DHIGH_BIT_DEPTH=ON -DEXPORT_C_API=OFF
+make -j"$NCPU"
+cp libx265.a "$BUILD_DIR/8bit/libx265_main10.a"
+
+cd "$BUILD_DIR/8bit"
+cmake "$X265_DIR/source" "${CMAKE_FLAGS[@]}" \
+    -DEXTRA_LIB="x265_main10.a" \
+    -DEXTRA_LINK_FLAGS=-L. \
+    -DLINKED_10BIT=ON \
+    -DCMAKE_INSTALL_PREFIX="$OUTPUT_DIR"
+make -j"$NCPU"
+make install
+
+mv libx265.a libx265_main.a
+libtool -static -o "$OUTPUT_DIR/lib/libx265.a" libx265_main.a libx265_main10.a
+
+echo "x265 iOS simulator build complete"
+echo "Static library: $OUTPUT_DIR/lib/libx265.a"
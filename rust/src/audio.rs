@@ -0,0 +1,561 @@
+use anyhow::{Context, Result};
+use ffmpeg_next::ffi;
+use ffmpeg_next::format;
+use ffmpeg_next::software::resampling::context::Context as ResamplingContext;
+use ffmpeg_next::{codec, decoder, encoder, frame, ChannelLayout, Codec, Dictionary, Packet, Rational, Rescale};
+use std::os::raw::c_void;
+
+use crate::error::{ErrorCode, PluginError};
+use crate::progress::ProgressReporter;
+
+// Audio bitrate constants (in bits per second)
+const MIN_AUDIO_BITRATE: usize = 8_000;
+const MAX_AUDIO_BITRATE: usize = 512_000;
+const DEFAULT_AUDIO_BITRATE: usize = 128_000; // used for lossy codecs when none is requested
+
+const MIN_SAMPLE_RATE: u32 = 8_000;
+const MAX_SAMPLE_RATE: u32 = 192_000;
+const MAX_CHANNELS: u32 = 8;
+
+/// Audio codecs the transcoder can produce
+///
+/// The values are part of the C ABI; 0 is reserved for "pick one for the container".
+#[repr(i32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AudioCodec {
+    Aac = 1,
+    Opus = 2,
+    Flac = 3,
+    Alac = 4,
+    Pcm = 5,
+}
+
+impl AudioCodec {
+    /// Find the encoder for this codec
+    ///
+    /// Opus prefers libopus, as FFmpeg's native Opus encoder is experimental.
+    pub fn encoder(self) -> Result<Codec> {
+        let codec = match self {
+            AudioCodec::Aac => encoder::find(codec::Id::AAC),
+            AudioCodec::Opus => encoder::find_by_name("libopus").or_else(|| {
+                encoder::find(codec::Id::OPUS)
+                    .filter(|codec| !codec.capabilities().contains(codec::Capabilities::EXPERIMENTAL))
+            }),
+            AudioCodec::Flac => encoder::find(codec::Id::FLAC),
+            AudioCodec::Alac => encoder::find(codec::Id::ALAC),
+            AudioCodec::Pcm => encoder::find(codec::Id::PCM_S16LE),
+        };
+        codec.ok_or_else(|| {
+            PluginError::new(
+                ErrorCode::EncoderUnavailable,
+                format!("No {:?} encoder is available in this FFmpeg build", self),
+            )
+            .into()
+        })
+    }
+}
+
+/// C-compatible audio encoding options
+///
+/// Zero or negative fields keep the source value (or the codec default for the bitrate).
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct CAudioOptions {
+    pub codec: i32,       // AudioCodec, or 0 to pick one for the container
+    pub bitrate: i32,     // bits per second, ignored by lossless codecs
+    pub sample_rate: i32, // Hz
+    pub channels: i32,
+}
+
+/// How audio streams are transcoded
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AudioSettings {
+    pub codec: Option<AudioCodec>, // None picks the container's preferred codec
+    pub bit_rate: Option<usize>,
+    pub sample_rate: Option<u32>,
+    pub channels: Option<u32>,
+}
+
+impl AudioSettings {
+    pub fn from_c(options: &CAudioOptions) -> Result<Self, PluginError> {
+        let codec = match options.codec {
+            0 => None,
+            1 => Some(AudioCodec::Aac),
+            2 => Some(AudioCodec::Opus),
+            3 => Some(AudioCodec::Flac),
+            4 => Some(AudioCodec::Alac),
+            5 => Some(AudioCodec::Pcm),
+            other => {
+                return Err(PluginError::new(ErrorCode::InvalidArgument, format!("Unknown audio codec {}", other)));
+            }
+        };
+        let positive = |value: i32| if value > 0 { Some(value as u32) } else { None };
+        let settings = Self {
+            codec,
+            bit_rate: positive(options.bitrate).map(|bit_rate| bit_rate as usize),
+            sample_rate: positive(options.sample_rate),
+            channels: positive(options.channels),
+        };
+        settings.validate()?;
+        Ok(settings)
+    }
+
+    fn validate(&self) -> Result<(), PluginError> {
+        if let Some(bit_rate) = self.bit_rate {
+            if !(MIN_AUDIO_BITRATE..=MAX_AUDIO_BITRATE).contains(&bit_rate) {
+                return Err(PluginError::new(
+                    ErrorCode::InvalidBitrate,
+                    format!("Audio bitrate {} must be between {} and {} bps", bit_rate, MIN_AUDIO_BITRATE, MAX_AUDIO_BITRATE),
+                ));
+            }
+        }
+        if let Some(sample_rate) = self.sample_rate {
+            if !(MIN_SAMPLE_RATE..=MAX_SAMPLE_RATE).contains(&sample_rate) {
+                return Err(PluginError::new(
+                    ErrorCode::InvalidArgument,
+                    format!("Sample rate {} must be between {} and {} Hz", sample_rate, MIN_SAMPLE_RATE, MAX_SAMPLE_RATE),
+                ));
+            }
+        }
+        if let Some(channels) = self.channels {
+            if channels > MAX_CHANNELS {
+                return Err(PluginError::new(
+                    ErrorCode::InvalidArgument,
+                    format!("Channel count {} must be at most {}", channels, MAX_CHANNELS),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Encoder for these settings, checked against the output container
+    pub fn encoder_for(&self, octx: &format::context::Output) -> Result<Codec> {
+        let codec = match self.codec {
+            Some(audio_codec) => audio_codec.encoder()?,
+            None => return select_audio_encoder(octx),
+        };
+        let muxer = octx.format();
+        let supported = unsafe { ffi::avformat_query_codec(muxer.as_ptr(), codec.id().into(), ffi::FF_COMPLIANCE_NORMAL) };
+        if supported == 0 {
+            return Err(PluginError::new(
+                ErrorCode::UnsupportedCodec,
+                format!("{} audio cannot be stored in {}", codec.name(), muxer.name()),
+            )
+            .into());
+        }
+        Ok(codec)
+    }
+}
+
+/// Codecs an audio stream is encoded to for a given muxer, best first
+///
+/// Matched on the muxer name FFmpeg picks from the output extension.
+fn preferred_audio_codecs(muxer: &str) -> &'static [codec::Id] {
+    match muxer {
+        "wav" => &[codec::Id::PCM_S16LE],
+        "adts" => &[codec::Id::AAC],
+        "flac" => &[codec::Id::FLAC],
+        "ogg" | "opus" => &[codec::Id::OPUS, codec::Id::VORBIS, codec::Id::FLAC],
+        "webm" => &[codec::Id::OPUS, codec::Id::VORBIS],
+        "matroska" => &[codec::Id::OPUS, codec::Id::VORBIS, codec::Id::FLAC, codec::Id::AAC],
+        "mxf" => &[codec::Id::PCM_S24LE, codec::Id::PCM_S16LE],
+        _ => &[codec::Id::AAC, codec::Id::ALAC],
+    }
+}
+
+fn is_lossless(codec_id: codec::Id) -> bool {
+    matches!(codec_id, codec::Id::FLAC | codec::Id::ALAC) || codec_id.name().starts_with("pcm_")
+}
+
+/// Bits per second an audio stream is expected to take in the output
+///
+/// Used to budget target-size encodes. Copied streams and lossless codecs keep
+/// roughly the source bitrate; lossy transcodes use the requested or default bitrate.
+pub fn estimated_bit_rate(ist: &format::stream::Stream, settings: Option<&AudioSettings>) -> usize {
+    let (source_bit_rate, sample_rate, channels) = unsafe {
+        let parameters = &*ist.parameters().as_ptr();
+        (parameters.bit_rate, parameters.sample_rate, parameters.ch_layout.nb_channels)
+    };
+    let source_bit_rate = if source_bit_rate > 0 {
+        source_bit_rate as usize
+    } else {
+        DEFAULT_AUDIO_BITRATE
+    };
+    let settings = match settings {
+        Some(settings) => settings,
+        None => return source_bit_rate,
+    };
+    match settings.codec {
+        Some(AudioCodec::Pcm) => {
+            let sample_rate = settings.sample_rate.unwrap_or(sample_rate.max(0) as u32) as usize;
+            let channels = settings.channels.unwrap_or(channels.max(1) as u32) as usize;
+            sample_rate * channels * 16
+        }
+        Some(AudioCodec::Flac) | Some(AudioCodec::Alac) => source_bit_rate,
+        _ => settings.bit_rate.unwrap_or(DEFAULT_AUDIO_BITRATE),
+    }
+}
+
+/// Whether an audio stream can be copied into the output as is
+///
+/// WAV can technically wrap compressed audio, but nothing plays it back, so only PCM is copied there.
+pub fn can_copy_audio(muxer: &format::Output, codec_id: codec::Id) -> bool {
+    if muxer.name() == "wav" {
+        return codec_id.name().starts_with("pcm_");
+    }
+    let supported = unsafe { ffi::avformat_query_codec(muxer.as_ptr(), codec_id.into(), ffi::FF_COMPLIANCE_NORMAL) };
+    // Muxers that do not declare their codecs only take what we would encode to ourselves
+    supported == 1 || (supported < 0 && preferred_audio_codecs(muxer.name()).contains(&codec_id))
+}
+
+/// Find an encoder whose output the muxer accepts
+///
+/// Experimental encoders, such as FFmpeg's native Opus encoder, are skipped.
+pub fn select_audio_encoder(octx: &format::context::Output) -> Result<Codec> {
+    let muxer = octx.format();
+    for codec_id in preferred_audio_codecs(muxer.name()) {
+        let Some(codec) = encoder::find(*codec_id) else {
+            continue;
+        };
+        if codec.capabilities().contains(codec::Capabilities::EXPERIMENTAL) {
+            continue;
+        }
+        let supported = unsafe { ffi::avformat_query_codec(muxer.as_ptr(), (*codec_id).into(), ffi::FF_COMPLIANCE_NORMAL) };
+        if supported != 0 {
+            return Ok(codec);
+        }
+    }
+    Err(PluginError::new(
+        ErrorCode::EncoderUnavailable,
+        format!("No audio encoder available for the {} container", muxer.name()),
+    )
+    .into())
+}
+
+/// Sample FIFO between the resampler and the encoder
+///
+/// Most encoders want frames of exactly `frame_size` samples, which is rarely
+/// what the decoder produces.
+struct AudioFifo(*mut ffi::AVAudioFifo);
+
+impl AudioFifo {
+    fn new(format: format::Sample, channels: i32) -> Result<Self, ffmpeg_next::Error> {
+        let fifo = unsafe { ffi::av_audio_fifo_alloc(format.into(), channels, 1) };
+        if fifo.is_null() {
+            return Err(ffmpeg_next::Error::Other { errno: ffmpeg_next::util::error::ENOMEM });
+        }
+        Ok(Self(fifo))
+    }
+
+    fn size(&self) -> usize {
+        unsafe { ffi::av_audio_fifo_size(self.0).max(0) as usize }
+    }
+
+    fn write(&mut self, samples: &frame::Audio) -> Result<(), ffmpeg_next::Error> {
+        let written = unsafe {
+            ffi::av_audio_fifo_write(
+                self.0,
+                (*samples.as_ptr()).extended_data as *const *mut c_void,
+                samples.samples() as i32,
+            )
+        };
+        if written < 0 {
+            return Err(ffmpeg_next::Error::from(written));
+        }
+        Ok(())
+    }
+
+    fn read(&mut self, samples: &mut frame::Audio) -> Result<(), ffmpeg_next::Error> {
+        let read = unsafe {
+            ffi::av_audio_fifo_read(
+                self.0,
+                (*samples.as_ptr()).extended_data as *const *mut c_void,
+                samples.samples() as i32,
+            )
+        };
+        if read < 0 {
+            return Err(ffmpeg_next::Error::from(read));
+        }
+        samples.set_samples(read as usize);
+        Ok(())
+    }
+}
+
+impl Drop for AudioFifo {
+    fn drop(&mut self) {
+        unsafe { ffi::av_audio_fifo_free(self.0) };
+    }
+}
+
+/// Decodes an audio stream, converts it with swresample and encodes it again
+pub struct AudioTranscoder {
+    ost_index: usize,
+    decoder: decoder::Audio,
+    input_time_base: Rational,
+    input_start_time: i64,
+    input_channel_layout: ChannelLayout,
+    encoder: encoder::Audio,
+    encoder_time_base: Rational,
+    resampler: ResamplingContext,
+    fifo: AudioFifo,
+    next_pts: Option<i64>, // encoder time base, set from the first decoded frame
+    should_inform_about_progress: bool,
+}
+
+impl AudioTranscoder {
+    fn select_sample_rate(codec: &codec::Audio, source_rate: u32) -> u32 {
+        let Some(rates) = codec.rates() else {
+            return source_rate;
+        };
+        let rates: Vec<u32> = rates.map(|rate| rate as u32).collect();
+        if rates.is_empty() || rates.contains(&source_rate) {
+            return source_rate;
+        }
+        // The closest rate at or above the source, so nothing is lost; otherwise the highest
+        rates
+            .iter()
+            .copied()
+            .filter(|rate| *rate >= source_rate)
+            .min()
+            .or_else(|| rates.iter().copied().max())
+            .unwrap_or(source_rate)
+    }
+
+    fn select_sample_format(codec: &codec::Audio, source_format: format::Sample) -> format::Sample {
+        match codec.formats() {
+            Some(formats) => {
+                let formats: Vec<format::Sample> = formats.collect();
+                if formats.contains(&source_format) {
+                    source_format
+                } else {
+                    formats.first().copied().unwrap_or(source_format)
+                }
+            }
+            None => source_format,
+        }
+    }
+
+    fn select_channel_layout(codec: &codec::Audio, source_layout: ChannelLayout) -> ChannelLayout {
+        match codec.channel_layouts() {
+            Some(layouts) => {
+                let layouts: Vec<ChannelLayout> = layouts.collect();
+                if layouts.is_empty() || layouts.contains(&source_layout) {
+                    source_layout
+                } else {
+                    layouts
+                        .iter()
+                        .copied()
+                        .filter(|layout| layout.channels() <= source_layout.channels())
+                        .max_by_key(|layout| layout.channels())
+                        .unwrap_or(ChannelLayout::STEREO)
+                }
+            }
+            None => source_layout,
+        }
+    }
+
+    pub fn new(
+        ist: &format::stream::Stream,
+        octx: &mut format::context::Output,
+        ost_index: usize,
+        codec: Codec,
+        settings: &AudioSettings,
+        should_inform_about_progress: bool,
+    ) -> Result<Self, ffmpeg_next::Error> {
+        let global_header = octx.format().flags().contains(format::Flags::GLOBAL_HEADER);
+        let decoder = codec::context::Context::from_parameters(ist.parameters())?
+            .decoder()
+            .audio()?;
+        // WAV and some raw inputs do not say how their channels are laid out
+        let input_channel_layout = if decoder.channel_layout().is_empty() {
+            ChannelLayout::default(decoder.channels() as i32)
+        } else {
+            decoder.channel_layout()
+        };
+
+        let requested_channel_layout = match settings.channels {
+            Some(channels) => ChannelLayout::default(channels as i32),
+            None => input_channel_layout,
+        };
+
+        let audio_codec = codec.audio()?;
+        // The MXF muxer only writes 48 kHz audio
+        let source_rate = if octx.format().name() == "mxf" { 48_000 } else { decoder.rate() };
+        let sample_rate = Self::select_sample_rate(&audio_codec, settings.sample_rate.unwrap_or(source_rate));
+        let sample_format = Self::select_sample_format(&audio_codec, decoder.format());
+        let channel_layout = Self::select_channel_layout(&audio_codec, requested_channel_layout);
+        let encoder_time_base = Rational(1, sample_rate as i32);
+
+        let mut ost = octx.add_stream(codec)?;
+        let mut encoder = codec::context::Context::new_with_codec(codec)
+            .encoder()
+            .audio()?;
+        encoder.set_rate(sample_rate as i32);
+        encoder.set_format(sample_format);
+        encoder.set_channel_layout(channel_layout);
+        encoder.set_time_base(encoder_time_base);
+        if !is_lossless(codec.id()) {
+            encoder.set_bit_rate(settings.bit_rate.unwrap_or(DEFAULT_AUDIO_BITRATE));
+        }
+        if global_header {
+            encoder.set_flags(codec::Flags::GLOBAL_HEADER);
+        }
+
+        let opened_encoder = encoder.open_with(Dictionary::new())?;
+        ost.set_parameters(&opened_encoder);
+        ost.set_time_base(encoder_time_base);
+
+        let resampler = ResamplingContext::get(
+            decoder.format(),
+            input_channel_layout,
+            decoder.rate(),
+            sample_format,
+            channel_layout,
+            sample_rate,
+        )?;
+        let fifo = AudioFifo::new(sample_format, channel_layout.channels())?;
+
+        Ok(Self {
+            ost_index,
+            decoder,
+            input_time_base: ist.time_base(),
+            input_start_time: if ist.start_time() == ffi::AV_NOPTS_VALUE { 0 } else { ist.start_time() },
+            input_channel_layout,
+            encoder: opened_encoder,
+            encoder_time_base,
+            resampler,
+            fifo,
+            next_pts: None,
+            should_inform_about_progress,
+        })
+    }
+
+    pub fn ost_index(&self) -> usize {
+        self.ost_index
+    }
+
+    pub fn send_packet_to_decoder(&mut self, packet: &Packet) -> Result<()> {
+        self.decoder.send_packet(packet)
+            .context("Failed to send packet to audio decoder")?;
+        Ok(())
+    }
+
+    pub fn send_eof_to_decoder(&mut self) -> Result<()> {
+        self.decoder.send_eof()
+            .context("Failed to send EOF to audio decoder")?;
+        Ok(())
+    }
+
+    pub fn receive_and_process_decoded_frames(
+        &mut self,
+        octx: &mut format::context::Output,
+        ost_time_base: Rational,
+        progress: &mut ProgressReporter,
+    ) -> Result<()> {
+        let mut decoded = frame::Audio::empty();
+        while self.decoder.receive_frame(&mut decoded).is_ok() {
+            let timestamp = decoded.timestamp();
+            if self.next_pts.is_none() {
+                self.next_pts = Some(
+                    timestamp
+                        .map(|timestamp| timestamp.rescale(self.input_time_base, self.encoder_time_base))
+                        .unwrap_or(0),
+                );
+            }
+            if self.should_inform_about_progress {
+                if let Some(timestamp) = timestamp {
+                    progress.audio_processed((timestamp - self.input_start_time) as f64 * f64::from(self.input_time_base));
+                }
+            }
+
+            if decoded.channel_layout().is_empty() {
+                decoded.set_channel_layout(self.input_channel_layout);
+            }
+            let mut resampled = self.resampled_frame(decoded.samples());
+            self.resampler
+                .run(&decoded, &mut resampled)
+                .context("Failed to resample decoded audio")?;
+            self.fifo.write(&resampled)
+                .context("Failed to buffer resampled audio")?;
+            self.encode_buffered_samples(octx, ost_time_base, false)?;
+        }
+        Ok(())
+    }
+
+    /// Drain the resampler and the FIFO, then tell the encoder no more frames are coming
+    pub fn send_eof_to_encoder(&mut self, octx: &mut format::context::Output, ost_time_base: Rational) -> Result<()> {
+        loop {
+            let mut resampled = self.resampled_frame(0);
+            self.resampler
+                .flush(&mut resampled)
+                .context("Failed to flush the resampler")?;
+            if resampled.samples() == 0 {
+                break;
+            }
+            self.fifo.write(&resampled)
+                .context("Failed to buffer resampled audio")?;
+        }
+        self.encode_buffered_samples(octx, ost_time_base, true)?;
+
+        self.encoder.send_eof()
+            .context("Failed to send EOF to audio encoder")?;
+        Ok(())
+    }
+
+    pub fn receive_and_process_encoded_packets(
+        &mut self,
+        octx: &mut format::context::Output,
+        ost_time_base: Rational,
+    ) -> Result<()> {
+        let mut encoded = Packet::empty();
+        while self.encoder.receive_packet(&mut encoded).is_ok() {
+            encoded.set_stream(self.ost_index);
+            encoded.rescale_ts(self.encoder_time_base, ost_time_base);
+            encoded.write_interleaved(octx)
+                .context("Failed to write encoded audio packet")?;
+        }
+        Ok(())
+    }
+
+    /// Output frame big enough for everything the resampler can return for `input_samples`
+    fn resampled_frame(&mut self, input_samples: usize) -> frame::Audio {
+        let capacity = unsafe { ffi::swr_get_out_samples(self.resampler.as_mut_ptr(), input_samples as i32) };
+        let mut resampled = frame::Audio::new(
+            self.encoder.format(),
+            capacity.max(1) as usize,
+            self.encoder.channel_layout(),
+        );
+        resampled.set_rate(self.encoder.rate());
+        resampled
+    }
+
+    /// Send whole encoder frames from the FIFO, and the remainder too when `flush` is set
+    fn encode_buffered_samples(
+        &mut self,
+        octx: &mut format::context::Output,
+        ost_time_base: Rational,
+        flush: bool,
+    ) -> Result<()> {
+        // 0 means the encoder takes frames of any size
+        let frame_size = match self.encoder.frame_size() as usize {
+            0 => self.fifo.size(),
+            frame_size => frame_size,
+        };
+        while frame_size > 0 && (self.fifo.size() >= frame_size || (flush && self.fifo.size() > 0)) {
+            let samples = frame_size.min(self.fifo.size());
+            let mut audio = frame::Audio::new(self.encoder.format(), samples, self.encoder.channel_layout());
+            audio.set_rate(self.encoder.rate());
+            self.fifo.read(&mut audio)
+                .context("Failed to read buffered audio")?;
+
+            let pts = self.next_pts.unwrap_or(0);
+            audio.set_pts(Some(pts));
+            self.next_pts = Some(pts + audio.samples() as i64);
+
+            self.encoder.send_frame(&audio)
+                .context("Failed to send frame to audio encoder")?;
+            self.receive_and_process_encoded_packets(octx, ost_time_base)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,211 @@
+use ffmpeg_next::util::error::{EACCES, EINVAL, ENOENT, ENOSPC, EPERM, EROFS};
+use std::fmt;
+use std::io;
+
+use crate::job::Cancelled;
+
+/// Stable numeric error codes reported to Swift in `CResult::error_code`
+///
+/// The values are part of the C ABI: never renumber them, only append.
+#[repr(i32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorCode {
+    None = 0,
+    Unknown = 1,
+    InvalidArgument = 2,
+    InputNotFound = 3,
+    UnsupportedFormat = 4,
+    UnsupportedCodec = 5,
+    EncoderUnavailable = 6,
+    InvalidBitrate = 7,
+    CorruptInput = 8,
+    PermissionDenied = 9,
+    DiskFull = 10,
+    Cancelled = 11,
+}
+
+/// Coarse grouping of error codes, for callers that only need to know where a failure came from
+#[repr(i32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorCategory {
+    None = 0,
+    Internal = 1,
+    Argument = 2,
+    Input = 3,
+    Codec = 4,
+    Output = 5,
+    Cancelled = 6,
+}
+
+impl ErrorCode {
+    pub fn category(self) -> ErrorCategory {
+        match self {
+            ErrorCode::None => ErrorCategory::None,
+            ErrorCode::Unknown => ErrorCategory::Internal,
+            ErrorCode::InvalidArgument | ErrorCode::InvalidBitrate => ErrorCategory::Argument,
+            ErrorCode::InputNotFound | ErrorCode::UnsupportedFormat | ErrorCode::CorruptInput => ErrorCategory::Input,
+            ErrorCode::UnsupportedCodec | ErrorCode::EncoderUnavailable => ErrorCategory::Codec,
+            ErrorCode::PermissionDenied | ErrorCode::DiskFull => ErrorCategory::Output,
+            ErrorCode::Cancelled => ErrorCategory::Cancelled,
+        }
+    }
+
+    /// Classify an error by the first cause in its chain that we know how to map
+    ///
+    /// Causes are visited from the outermost context inwards, so an explicit
+    /// [`PluginError`] wins over the FFmpeg or IO error it wraps.
+    pub fn from_error(error: &anyhow::Error) -> Self {
+        for cause in error.chain() {
+            if let Some(plugin_error) = cause.downcast_ref::<PluginError>() {
+                return plugin_error.code;
+            }
+            if cause.is::<Cancelled>() {
+                return ErrorCode::Cancelled;
+            }
+            if let Some(ffmpeg_error) = cause.downcast_ref::<ffmpeg_next::Error>() {
+                return Self::from_ffmpeg(*ffmpeg_error);
+            }
+            if let Some(io_error) = cause.downcast_ref::<io::Error>() {
+                return Self::from_io(io_error);
+            }
+        }
+        ErrorCode::Unknown
+    }
+
+    fn from_ffmpeg(error: ffmpeg_next::Error) -> Self {
+        match error {
+            ffmpeg_next::Error::DemuxerNotFound | ffmpeg_next::Error::MuxerNotFound => ErrorCode::UnsupportedFormat,
+            ffmpeg_next::Error::DecoderNotFound => ErrorCode::UnsupportedCodec,
+            ffmpeg_next::Error::EncoderNotFound => ErrorCode::EncoderUnavailable,
+            ffmpeg_next::Error::InvalidData => ErrorCode::CorruptInput,
+            ffmpeg_next::Error::Exit => ErrorCode::Cancelled,
+            ffmpeg_next::Error::Other { errno } => match errno {
+                ENOENT => ErrorCode::InputNotFound,
+                EACCES | EPERM | EROFS => ErrorCode::PermissionDenied,
+                ENOSPC => ErrorCode::DiskFull,
+                EINVAL => ErrorCode::InvalidArgument,
+                _ => ErrorCode::Unknown,
+            },
+            _ => ErrorCode::Unknown,
+        }
+    }
+
+    fn from_io(error: &io::Error) -> Self {
+        match error.kind() {
+            io::ErrorKind::NotFound => ErrorCode::InputNotFound,
+            io::ErrorKind::PermissionDenied => ErrorCode::PermissionDenied,
+            io::ErrorKind::InvalidInput => ErrorCode::InvalidArgument,
+            _ if error.raw_os_error() == Some(ENOSPC) => ErrorCode::DiskFull,
+            _ => ErrorCode::Unknown,
+        }
+    }
+}
+
+/// Error raised by the plugin itself with an explicit code
+///
+/// Use it at `bail!` sites whose failure the host may want to branch on.
+#[derive(Debug)]
+pub struct PluginError {
+    code: ErrorCode,
+    message: String,
+}
+
+impl PluginError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+
+    pub fn code(&self) -> ErrorCode {
+        self.code
+    }
+}
+
+impl fmt::Display for PluginError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for PluginError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    fn code_of(error: impl Into<anyhow::Error>) -> ErrorCode {
+        ErrorCode::from_error(&error.into())
+    }
+
+    #[test]
+    fn plugin_error_keeps_its_code() {
+        assert_eq!(code_of(PluginError::new(ErrorCode::InvalidBitrate, "too low")), ErrorCode::InvalidBitrate);
+        assert_eq!(code_of(PluginError::new(ErrorCode::UnsupportedCodec, "no")), ErrorCode::UnsupportedCodec);
+    }
+
+    #[test]
+    fn cancelled_maps_to_cancelled() {
+        assert_eq!(code_of(Cancelled), ErrorCode::Cancelled);
+        assert_eq!(code_of(ffmpeg_next::Error::Exit), ErrorCode::Cancelled);
+    }
+
+    #[test]
+    fn ffmpeg_errors() {
+        assert_eq!(code_of(ffmpeg_next::Error::Other { errno: EINVAL }), ErrorCode::InvalidArgument);
+        assert_eq!(code_of(ffmpeg_next::Error::Other { errno: ENOENT }), ErrorCode::InputNotFound);
+        assert_eq!(code_of(ffmpeg_next::Error::Other { errno: EACCES }), ErrorCode::PermissionDenied);
+        assert_eq!(code_of(ffmpeg_next::Error::Other { errno: ENOSPC }), ErrorCode::DiskFull);
+        assert_eq!(code_of(ffmpeg_next::Error::DemuxerNotFound), ErrorCode::UnsupportedFormat);
+        assert_eq!(code_of(ffmpeg_next::Error::DecoderNotFound), ErrorCode::UnsupportedCodec);
+        assert_eq!(code_of(ffmpeg_next::Error::EncoderNotFound), ErrorCode::EncoderUnavailable);
+        assert_eq!(code_of(ffmpeg_next::Error::InvalidData), ErrorCode::CorruptInput);
+        assert_eq!(code_of(ffmpeg_next::Error::Bug), ErrorCode::Unknown);
+    }
+
+    #[test]
+    fn io_errors() {
+        assert_eq!(code_of(io::Error::from(io::ErrorKind::NotFound)), ErrorCode::InputNotFound);
+        assert_eq!(code_of(io::Error::from(io::ErrorKind::PermissionDenied)), ErrorCode::PermissionDenied);
+        assert_eq!(code_of(io::Error::from(io::ErrorKind::InvalidInput)), ErrorCode::InvalidArgument);
+        assert_eq!(code_of(io::Error::from_raw_os_error(ENOSPC)), ErrorCode::DiskFull);
+        assert_eq!(code_of(io::Error::from(io::ErrorKind::Other)), ErrorCode::Unknown);
+    }
+
+    #[test]
+    fn unknown_errors() {
+        assert_eq!(code_of(anyhow::anyhow!("something else")), ErrorCode::Unknown);
+    }
+
+    #[test]
+    fn context_keeps_the_code() {
+        let error = Err::<(), _>(PluginError::new(ErrorCode::InvalidArgument, "bad"))
+            .context("Invalid bitrate specified")
+            .context("Video re-encoding failed")
+            .unwrap_err();
+        assert_eq!(ErrorCode::from_error(&error), ErrorCode::InvalidArgument);
+
+        let error = Err::<(), _>(io::Error::from(io::ErrorKind::NotFound))
+            .context("Failed to open input file")
+            .unwrap_err();
+        assert_eq!(ErrorCode::from_error(&error), ErrorCode::InputNotFound);
+
+        let error = Err::<(), _>(Cancelled).context("Failed to acquire a job slot").unwrap_err();
+        assert_eq!(ErrorCode::from_error(&error), ErrorCode::Cancelled);
+    }
+
+    #[test]
+    fn categories() {
+        assert_eq!(ErrorCode::None.category(), ErrorCategory::None);
+        assert_eq!(ErrorCode::Unknown.category(), ErrorCategory::Internal);
+        assert_eq!(ErrorCode::InvalidArgument.category(), ErrorCategory::Argument);
+        assert_eq!(ErrorCode::InvalidBitrate.category(), ErrorCategory::Argument);
+        assert_eq!(ErrorCode::InputNotFound.category(), ErrorCategory::Input);
+        assert_eq!(ErrorCode::CorruptInput.category(), ErrorCategory::Input);
+        assert_eq!(ErrorCode::EncoderUnavailable.category(), ErrorCategory::Codec);
+        assert_eq!(ErrorCode::DiskFull.category(), ErrorCategory::Output);
+        assert_eq!(ErrorCode::Cancelled.category(), ErrorCategory::Cancelled);
+    }
+}
@@ -0,0 +1,172 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+
+/// Cooperative cancellation flag shared between a running job and `cancel_job`
+#[derive(Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// Error returned by an operation that stopped because its job was cancelled
+///
+/// Callers can detect it with `anyhow::Error::downcast_ref::<Cancelled>()`.
+#[derive(Debug)]
+pub struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Job was cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+/// Table of jobs that can currently be cancelled, keyed by the handle given to Swift
+///
+/// Handles start at 1 so that 0 can be used across the C ABI to mean "no job".
+pub struct JobRegistry {
+    next_handle: AtomicU64,
+    jobs: Mutex<HashMap<u64, CancellationToken>>,
+}
+
+impl JobRegistry {
+    pub fn new() -> Self {
+        Self {
+            next_handle: AtomicU64::new(1),
+            jobs: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Register a new job and return its handle
+    pub fn create(&self) -> u64 {
+        let handle = self.next_handle.fetch_add(1, Ordering::SeqCst);
+        match self.jobs.lock() {
+            Ok(mut jobs) => {
+                jobs.insert(handle, CancellationToken::new());
+            }
+            Err(err) => eprintln!("Cannot lock job registry: {}", err),
+        }
+        handle
+    }
+
+    /// Get the cancellation token of a registered job
+    pub fn token(&self, handle: u64) -> Option<CancellationToken> {
+        match self.jobs.lock() {
+            Ok(jobs) => jobs.get(&handle).cloned(),
+            Err(err) => {
+                eprintln!("Cannot lock job registry: {}", err);
+                None
+            }
+        }
+    }
+
+    /// Request cancellation of a job
+    ///
+    /// Returns false if the handle is unknown or the job already finished.
+    pub fn cancel(&self, handle: u64) -> bool {
+        match self.token(handle) {
+            Some(token) => {
+                token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Request cancellation of every registered job
+    pub fn cancel_all(&self) {
+        match self.jobs.lock() {
+            Ok(jobs) => {
+                for token in jobs.values() {
+                    token.cancel();
+                }
+            }
+            Err(err) => eprintln!("Cannot lock job registry: {}", err),
+        }
+    }
+
+    /// Forget a job once it has finished
+    pub fn remove(&self, handle: u64) {
+        match self.jobs.lock() {
+            Ok(mut jobs) => {
+                jobs.remove(&handle);
+            }
+            Err(err) => eprintln!("Cannot lock job registry: {}", err),
+        }
+    }
+}
+
+impl Default for JobRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Counting semaphore limiting how many heavy jobs run at the same time
+///
+/// Blocking rather than async because jobs run either on the caller's thread
+/// or on the runtime's blocking pool.
+pub struct JobSlots {
+    available: Mutex<usize>,
+    released: Condvar,
+}
+
+/// A taken slot, given back when dropped
+pub struct JobSlot<'a> {
+    slots: &'a JobSlots,
+}
+
+// How often a job waiting for a slot checks whether it has been cancelled
+const SLOT_WAIT_INTERVAL: Duration = Duration::from_millis(100);
+
+impl JobSlots {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            available: Mutex::new(limit.max(1)),
+            released: Condvar::new(),
+        }
+    }
+
+    /// Wait for a free slot
+    ///
+    /// Gives up with [`Cancelled`] if the job is cancelled while it is waiting.
+    pub fn acquire(&self, cancellation: &CancellationToken) -> Result<JobSlot<'_>, Cancelled> {
+        // The guarded value is a plain counter, so a poisoned lock is still usable
+        let mut available = self.available.lock().unwrap_or_else(|err| err.into_inner());
+        while *available == 0 {
+            if cancellation.is_cancelled() {
+                return Err(Cancelled);
+            }
+            available = self
+                .released
+                .wait_timeout(available, SLOT_WAIT_INTERVAL)
+                .unwrap_or_else(|err| err.into_inner())
+                .0;
+        }
+        *available -= 1;
+        Ok(JobSlot { slots: self })
+    }
+}
+
+impl Drop for JobSlot<'_> {
+    fn drop(&mut self) {
+        let mut available = self.slots.available.lock().unwrap_or_else(|err| err.into_inner());
+        *available += 1;
+        self.slots.released.notify_one();
+    }
+}
@@ -2,16 +2,34 @@ use anyhow::anyhow;
 use std::ffi::{CStr, CString};
 use std::os::raw::{c_void, c_char, c_int};
 use std::sync::Arc;
-use std::sync::Mutex;
 
+mod audio;
+mod error;
+mod job;
 mod plugin;
-use plugin::CapacitorFFmpegPlugin;
+mod probe;
+mod progress;
+mod thumbnail;
+mod trim;
+mod video;
+use audio::{AudioSettings, CAudioOptions};
+use error::{ErrorCategory, ErrorCode, PluginError};
+use job::{Cancelled, CancellationToken};
+use plugin::{CapacitorFFmpegPlugin, IncompatibleStreamPolicy};
+use progress::{CProgress, ProgressCallback};
+use thumbnail::ImageFormat;
+use trim::TrimMode;
+use video::{CVideoOptions, VideoSettings};
 
 /// C-compatible result structure for communicating with Swift
 #[repr(C)]
 pub struct CResult {
     pub ok: bool,
     pub error_message: *mut c_char, // NULL if ok = true, otherwise points to error string
+    pub cancelled: bool, // true if the job stopped because of cancel_job
+    pub error_code: i32, // ErrorCode, 0 if ok = true
+    pub error_category: i32, // ErrorCategory of error_code, 0 if ok = true
+    pub data: *mut c_char, // JSON payload of operations that return data, otherwise NULL
 }
 
 impl CResult {
@@ -20,12 +38,39 @@ impl CResult {
         let result = CResult {
             ok: true,
             error_message: std::ptr::null_mut(),
+            cancelled: false,
+            error_code: ErrorCode::None as i32,
+            error_category: ErrorCategory::None as i32,
+            data: std::ptr::null_mut(),
         };
         Box::into_raw(Box::new(result))
     }
+
+    /// Create a success result carrying a JSON payload
+    fn success_with_data(data: String) -> *mut CResult {
+        let c_string = match CString::new(data) {
+            Ok(s) => s,
+            Err(_) => return CResult::error(ErrorCode::Unknown, "Result data contains a NUL byte".to_string()),
+        };
+
+        let result = CResult {
+            ok: true,
+            error_message: std::ptr::null_mut(),
+            cancelled: false,
+            error_code: ErrorCode::None as i32,
+            error_category: ErrorCategory::None as i32,
+            data: c_string.into_raw(),
+        };
+        Box::into_raw(Box::new(result))
+    }
+
+    /// Create a result for a job that was cancelled before completing
+    fn cancelled() -> *mut CResult {
+        CResult::error(ErrorCode::Cancelled, "Job was cancelled".to_string())
+    }
     
-    /// Create an error result with detailed message
-    fn error(message: String) -> *mut CResult {
+    /// Create an error result with a code and a human-readable message
+    fn error(code: ErrorCode, message: String) -> *mut CResult {
         let c_string = match CString::new(message) {
             Ok(s) => s,
             Err(_) => CString::new("Failed to create error message").unwrap(),
@@ -34,9 +79,31 @@ impl CResult {
         let result = CResult {
             ok: false,
             error_message: c_string.into_raw(),
+            cancelled: code == ErrorCode::Cancelled,
+            error_code: code as i32,
+            error_category: code.category() as i32,
+            data: std::ptr::null_mut(),
         };
         Box::into_raw(Box::new(result))
     }
+
+    /// Create an error result from an operation failure
+    ///
+    /// The message is the error chain on one line; the full debug output with
+    /// backtrace only goes to the log.
+    fn from_error(description: &str, error: &anyhow::Error) -> *mut CResult {
+        eprintln!("{}: {:?}\nBacktrace:\n{}", description, error, error.backtrace());
+        CResult::error(ErrorCode::from_error(error), format!("{}: {:#}", description, error))
+    }
+
+    /// Create the result of an operation that returns no data
+    fn from_result(description: &str, result: Result<(), anyhow::Error>) -> *mut CResult {
+        match result {
+            Ok(()) => CResult::success(),
+            Err(e) if e.downcast_ref::<Cancelled>().is_some() => CResult::cancelled(),
+            Err(e) => CResult::from_error(description, &e),
+        }
+    }
 }
 
 /// Free the CResult structure and associated error message
@@ -54,37 +121,76 @@ pub extern "C" fn free_c_result(result: *mut CResult) {
         if !boxed_result.error_message.is_null() {
             let _ = CString::from_raw(boxed_result.error_message);
         }
+
+        // Free the data payload if it exists
+        if !boxed_result.data.is_null() {
+            let _ = CString::from_raw(boxed_result.data);
+        }
         
         // boxed_result is automatically dropped here
     }
 }
 
-macro_rules! lock_mutex_or_log {
-    (
-        $MUTEX: ident
-    ) => {
-        match $MUTEX.lock() {
-            Ok(val) => val,
-            Err(err) => {
-                eprintln!("Cannot lock mutex: {}", err);
-                return;
-            }
-        }
-    };
+/// What the opaque plugin pointer handed to Swift points to
+///
+/// The plugin synchronises its own state, so it is shared without an outer
+/// lock and independent jobs can run in parallel.
+type PluginHandle = Arc<CapacitorFFmpegPlugin>;
+
+/// Progress callback signature shared by all operations
+///
+/// `progress` is only valid for the duration of the call.
+type InformAboutProgress = extern "C" fn(progress: *const CProgress, swift_internal_data_structure_pointer: *mut c_void) -> c_int;
+
+/// Completion callback signature for submitted jobs
+///
+/// The receiver owns `result` and must call free_c_result() when done.
+type OnJobComplete = extern "C" fn(job_handle: u64, result: *mut CResult, swift_internal_data_structure_pointer: *mut c_void);
+
+/// Pointer owned by Swift that we only ever hand back to Swift's callbacks
+///
+/// Swift keeps the pointee alive until the completion callback has run, which
+/// is what makes moving it to a worker thread sound.
+#[derive(Clone, Copy)]
+struct SwiftPointer(*mut c_void);
+
+unsafe impl Send for SwiftPointer {}
+
+impl SwiftPointer {
+    // Accessing the field through a method makes closures capture the whole
+    // wrapper (which is Send) rather than the raw pointer inside it.
+    fn get(self) -> *mut c_void {
+        self.0
+    }
 }
 
-macro_rules! lock_mutex_or_return {
-    (
-        $MUTEX: ident, $error_code: expr
-    ) => {
-        match $MUTEX.lock() {
-            Ok(val) => val,
-            Err(err) => {
-                eprintln!("Cannot lock mutex: {}", err);
-                return $error_code;
-            }
+/// Calls the completion callback of a submitted job exactly once
+///
+/// If the job is dropped before it runs (for example because the plugin was
+/// deinitialized while it was still queued), Swift is told it was cancelled.
+struct JobCompletion {
+    job_handle: u64,
+    plugin: PluginHandle,
+    on_complete: OnJobComplete,
+    swift_pointer: SwiftPointer,
+    completed: bool,
+}
+
+impl JobCompletion {
+    fn complete(mut self, result: *mut CResult) {
+        self.completed = true;
+        self.plugin.jobs().remove(self.job_handle);
+        (self.on_complete)(self.job_handle, result, self.swift_pointer.get());
+    }
+}
+
+impl Drop for JobCompletion {
+    fn drop(&mut self) {
+        if !self.completed {
+            self.plugin.jobs().remove(self.job_handle);
+            (self.on_complete)(self.job_handle, CResult::cancelled(), self.swift_pointer.get());
         }
-    };
+    }
 }
 
 /// Convert a C string to a Rust String safely
@@ -100,6 +206,135 @@ unsafe fn c_str_to_string(ptr: *const c_char) -> Option<String> {
     }
 }
 
+/// Convert the input and output paths every job takes
+unsafe fn c_paths(input_path: *const c_char, output_path: *const c_char) -> Result<(String, String), PluginError> {
+    let input_path = c_str_to_string(input_path)
+        .ok_or_else(|| PluginError::new(ErrorCode::InvalidArgument, "Invalid input path"))?;
+    let output_path = c_str_to_string(output_path)
+        .ok_or_else(|| PluginError::new(ErrorCode::InvalidArgument, "Invalid output path"))?;
+    Ok((input_path, output_path))
+}
+
+/// Arguments of a job, converted from their C representation
+trait JobRequest: Send + 'static {
+    /// Run the job on the calling thread and convert the outcome into a CResult
+    fn run(
+        self,
+        plugin: &CapacitorFFmpegPlugin,
+        inform_about_progress: ProgressCallback,
+        cancellation: CancellationToken,
+    ) -> *mut CResult;
+}
+
+/// Run a job on the calling thread
+///
+/// `job_handle` is a handle returned by `create_job`, or 0 if the job does not need
+/// to be cancellable. It is forgotten once the job is done.
+fn run_job<R: JobRequest>(
+    plugin: *mut c_void,
+    job_handle: u64,
+    request: Result<R, PluginError>,
+    swift_pointer: SwiftPointer,
+    inform_about_progress: InformAboutProgress,
+) -> *mut CResult {
+    // Safety check: ensure plugin pointer is not null
+    if plugin.is_null() {
+        return CResult::error(ErrorCode::InvalidArgument, "Plugin pointer is null".to_string());
+    }
+
+    let request = match request {
+        Ok(request) => request,
+        Err(e) => {
+            return CResult::error(e.code(), e.to_string());
+        }
+    };
+
+    // Keep our own reference so that the plugin outlives this call even if it is deinitialized meanwhile
+    let handle = unsafe { &*(plugin as *const PluginHandle) }.clone();
+    let cancellation = if job_handle == 0 {
+        CancellationToken::new()
+    } else {
+        match handle.jobs().token(job_handle) {
+            Some(token) => token,
+            None => {
+                return CResult::error(ErrorCode::InvalidArgument, format!("Unknown job handle: {}", job_handle));
+            }
+        }
+    };
+
+    let wrapped_inform_about_progress = wrap_inform_about_progress(inform_about_progress, swift_pointer);
+    let result = request.run(&handle, wrapped_inform_about_progress, cancellation);
+    if job_handle != 0 {
+        handle.jobs().remove(job_handle);
+    }
+    result
+}
+
+/// Queue a job on the plugin runtime and return immediately
+///
+/// Progress is reported from a worker thread while the job runs; `on_complete` is
+/// called exactly once from a worker thread with the final result.
+///
+/// Returns a non-zero job handle usable with `cancel_job`, or 0 if the job could not
+/// be queued (in which case `on_complete` is never called).
+fn submit_job<R: JobRequest>(
+    plugin: *mut c_void,
+    request: Result<R, PluginError>,
+    swift_pointer: SwiftPointer,
+    inform_about_progress: InformAboutProgress,
+    on_complete: OnJobComplete,
+) -> u64 {
+    if plugin.is_null() {
+        eprintln!("Cannot submit job: plugin pointer is null");
+        return 0;
+    }
+
+    let request = match request {
+        Ok(request) => request,
+        Err(e) => {
+            eprintln!("Cannot submit job: {}", e);
+            return 0;
+        }
+    };
+
+    let handle = unsafe { &*(plugin as *const PluginHandle) };
+    let job_handle = handle.jobs().create();
+    let cancellation = match handle.jobs().token(job_handle) {
+        Some(token) => token,
+        None => {
+            eprintln!("Cannot submit job: failed to register job {}", job_handle);
+            return 0;
+        }
+    };
+
+    let runtime = match handle.runtime_handle() {
+        Some(runtime) => runtime,
+        None => {
+            eprintln!("Cannot submit job: plugin has been destroyed");
+            handle.jobs().remove(job_handle);
+            return 0;
+        }
+    };
+    let completion = JobCompletion {
+        job_handle,
+        plugin: handle.clone(),
+        on_complete,
+        swift_pointer,
+        completed: false,
+    };
+    let plugin = handle.clone();
+
+    // FFmpeg calls block, so the job goes to the runtime's blocking pool rather than an async task.
+    // The plugin's job slots decide how many of them actually encode at once.
+    runtime.spawn_blocking(move || {
+        let wrapped_inform_about_progress = wrap_inform_about_progress(inform_about_progress, swift_pointer);
+        let result = request.run(&plugin, wrapped_inform_about_progress, cancellation);
+        completion.complete(result);
+    });
+
+    job_handle
+}
+
 /// Initialize FFmpeg 
 /// 
 /// # Returns
@@ -107,7 +342,30 @@ unsafe fn c_str_to_string(ptr: *const c_char) -> Option<String> {
 /// A pointer to the plugin on success, NULL on failure
 #[no_mangle]
 pub extern "C" fn init_ffmpeg_plugin() -> *mut c_void {
-    let plugin = match CapacitorFFmpegPlugin::new() {
+    init_ffmpeg_plugin_with_concurrency(0)
+}
+
+/// Initialize FFmpeg with a limit on how many encodes may run at once
+/// 
+/// Jobs beyond the limit wait for a free slot; they can still be cancelled
+/// while waiting.
+/// 
+/// # Arguments
+/// 
+/// * `max_concurrent_jobs` - Maximum number of concurrent encodes (0 for the runtime's worker count)
+/// 
+/// # Returns
+/// 
+/// A pointer to the plugin on success, NULL on failure
+#[no_mangle]
+pub extern "C" fn init_ffmpeg_plugin_with_concurrency(max_concurrent_jobs: c_int) -> *mut c_void {
+    let max_concurrent_jobs = if max_concurrent_jobs <= 0 {
+        None // Use the runtime's worker count.
+    } else {
+        Some(max_concurrent_jobs as usize)
+    };
+
+    let plugin = match CapacitorFFmpegPlugin::new(max_concurrent_jobs) {
         Ok(p) => p,
         Err(e) => {
             eprintln!("Failed to initialize FFmpeg plugin: {:?}", e);
@@ -115,114 +373,625 @@ pub extern "C" fn init_ffmpeg_plugin() -> *mut c_void {
         }
     };
 
-    // Now, we have some things to do... It's not going to be pretty.
-    let boxed_plugin = Box::new(plugin);
-
-    let arc_mutex_plugin = Arc::new(Mutex::new(boxed_plugin));
-    Box::into_raw(Box::new(arc_mutex_plugin)) as *mut c_void
+    let handle: PluginHandle = Arc::new(plugin);
+    Box::into_raw(Box::new(handle)) as *mut c_void
 }
 
 /// Deinitialize the plugin
 /// 
+/// Running jobs are cancelled and this returns without waiting for them.
+/// Submitted jobs that have not started yet receive a cancelled completion.
+/// 
 /// # Arguments
 /// 
 /// * `plugin` - A pointer to the plugin
 #[no_mangle]
 pub extern "C" fn deinit_ffmpeg_plugin(plugin: *mut c_void) {
-    let plugin = unsafe { Box::from_raw(plugin as *mut Arc<Mutex<Box<CapacitorFFmpegPlugin>>>) };
-    let mut locked_plugin = lock_mutex_or_log!(plugin);
-    locked_plugin.destroy();
+    if plugin.is_null() {
+        return;
+    }
+
+    // Jobs still running hold their own reference and release the plugin when they finish
+    let handle = unsafe { Box::from_raw(plugin as *mut PluginHandle) };
+    handle.destroy();
+}
+
+/// Create a job handle that can later be passed to `reencode_video` and `cancel_job`
+/// 
+/// # Arguments
+/// 
+/// * `plugin` - A pointer to the plugin
+/// 
+/// # Returns
+/// 
+/// A non-zero job handle, or 0 if the plugin pointer is null
+#[no_mangle]
+pub extern "C" fn create_job(plugin: *mut c_void) -> u64 {
+    if plugin.is_null() {
+        return 0;
+    }
+
+    let handle = unsafe { &*(plugin as *const PluginHandle) };
+    handle.jobs().create()
+}
+
+/// Request cancellation of a running job
+/// 
+/// This does not wait for the job to stop; the job's own call returns a
+/// cancelled CResult once it notices the request.
+/// 
+/// # Arguments
+/// 
+/// * `plugin` - A pointer to the plugin
+/// * `job_handle` - A handle returned by `create_job` or a `submit_*` function
+/// 
+/// # Returns
+/// 
+/// true if the job was found, false if it is unknown or already finished
+#[no_mangle]
+pub extern "C" fn cancel_job(plugin: *mut c_void, job_handle: u64) -> bool {
+    if plugin.is_null() {
+        return false;
+    }
+
+    let handle = unsafe { &*(plugin as *const PluginHandle) };
+    handle.jobs().cancel(job_handle)
+}
+
+/// Wrap the C progress callback in the closure type the plugin expects
+fn wrap_inform_about_progress(
+    inform_about_progress: InformAboutProgress,
+    swift_pointer: SwiftPointer,
+) -> ProgressCallback {
+    Arc::new(Box::new(move |progress: &CProgress| {
+        
+        // Call the C function with a pointer to the progress payload
+        let result = inform_about_progress(progress as *const CProgress, swift_pointer.get());
+        if result != 0 {
+            return Err(anyhow!("Failed to inform about progress"));
+        }
+        
+        // The payload is borrowed, so the C function must not rely on it persisting
+        Ok(())
+    }))
+}
+
+/// Arguments of a re-encode request, converted from their C representation
+struct ReencodeVideoRequest {
+    input_path: String,
+    output_path: String,
+    target_width: u32,
+    target_height: u32,
+    bitrate: Option<u64>,
+    video: VideoSettings,
+    audio: Option<AudioSettings>,
+    incompatible_streams: IncompatibleStreamPolicy,
+}
+
+impl ReencodeVideoRequest {
+    unsafe fn from_c(
+        input_path: *const c_char,
+        output_path: *const c_char,
+        target_width: c_int,
+        target_height: c_int,
+        bitrate: c_int,
+        video_options: *const CVideoOptions,
+        audio_options: *const CAudioOptions,
+        incompatible_stream_policy: c_int,
+    ) -> Result<Self, PluginError> {
+        let (input_path, output_path) = c_paths(input_path, output_path)?;
+
+        let bitrate = if bitrate <= 0 {
+            None // Use default bitrate.
+        } else {
+            Some(bitrate as u64)
+        };
+
+        // No options scales to exactly the target size
+        let video = match video_options.as_ref() {
+            Some(options) => VideoSettings::from_c(options)?,
+            None => VideoSettings::default(),
+        };
+
+        // No options means audio streams are copied as before
+        let audio = match audio_options.as_ref() {
+            Some(options) => Some(AudioSettings::from_c(options)?),
+            None => None,
+        };
+        let incompatible_streams = IncompatibleStreamPolicy::from_c(incompatible_stream_policy)
+            .ok_or_else(|| PluginError::new(
+                ErrorCode::InvalidArgument,
+                format!("Unknown incompatible stream policy {}", incompatible_stream_policy),
+            ))?;
+
+        Ok(Self {
+            input_path,
+            output_path,
+            target_width: target_width.max(0) as u32,
+            target_height: target_height.max(0) as u32,
+            bitrate,
+            video,
+            audio,
+            incompatible_streams,
+        })
+    }
+}
+
+impl JobRequest for ReencodeVideoRequest {
+    fn run(
+        self,
+        plugin: &CapacitorFFmpegPlugin,
+        inform_about_progress: ProgressCallback,
+        cancellation: CancellationToken,
+    ) -> *mut CResult {
+        let result = plugin.reencode_video(&self.input_path, &self.output_path, self.target_width, self.target_height, self.bitrate, self.video, self.audio, self.incompatible_streams, inform_about_progress, cancellation);
+
+        match result {
+            Ok(outcome) => {
+                println!("Video re-encoding completed successfully ({})", outcome.path.name());
+                CResult::success_with_data(
+                    serde_json::json!({ "path": outcome.path.name(), "droppedStreams": outcome.dropped_streams }).to_string(),
+                )
+            }
+            Err(e) if e.downcast_ref::<Cancelled>().is_some() => {
+                println!("Video re-encoding was cancelled");
+                CResult::cancelled()
+            }
+            Err(e) => CResult::from_error("Video re-encoding failed", &e),
+        }
+    }
 }
 
 /// Re-encode a video file to a lower resolution
 /// 
+/// Blocks until the job is done. See `submit_reencode_video` for the
+/// asynchronous variant.
+/// 
 /// # Arguments
 /// 
 /// * `plugin` - A pointer to the plugin
+/// * `job_handle` - A handle returned by `create_job`, or 0 if the job does not need to be cancellable
 /// * `input_path` - The path to the input video file
 /// * `output_path` - The path to the output video file
-/// * `target_width` - The target width of the output video
-/// * `target_height` - The target height of the output video
+/// * `target_width` - The target width of the output video (0 to derive it from the height)
+/// * `target_height` - The target height of the output video (0 to derive it from the width)
 /// * `bitrate` - Target bitrate in bits per second (0 or negative for default)
+/// * `video_options` - How to fit the picture into the target size, or null to scale to exactly that size
+/// * `audio_options` - How to transcode audio streams, or null to copy them
+/// * `incompatible_stream_policy` - 0 to transcode audio the output container cannot hold (dropping other such streams), 1 to drop those streams, 2 to fail
 /// * `swift_internal_data_structure_pointer` - Pointer to Swift data structure for callbacks
 /// * `inform_about_progress` - Callback function for progress updates
 /// 
 /// # Returns
 /// 
-/// Pointer to CResult structure - caller must call free_c_result() when done
+/// Pointer to CResult structure - caller must call free_c_result() when done. On success
+/// `data` holds `{"path": "reencoded" | "remuxed" | "copied", "droppedStreams": [...]}`, the
+/// indices of the input streams the output container could not hold.
 #[no_mangle]
 pub extern "C" fn reencode_video(
     plugin: *mut c_void, 
+    job_handle: u64,
     input_path: *const c_char, 
     output_path: *const c_char, 
     target_width: c_int, 
     target_height: c_int, 
     bitrate: c_int,
+    video_options: *const CVideoOptions,
+    audio_options: *const CAudioOptions,
+    incompatible_stream_policy: c_int,
     swift_internal_data_structure_pointer: *mut c_void,
-    inform_about_progress: extern "C" fn(progress: f64, swift_internal_data_structure_pointer: *mut c_void) -> c_int,
+    inform_about_progress: InformAboutProgress,
 ) -> *mut CResult {
-    // Safety check: ensure plugin pointer is not null
-    if plugin.is_null() {
-        return CResult::error("Plugin pointer is null".to_string());
+    let request = unsafe { ReencodeVideoRequest::from_c(input_path, output_path, target_width, target_height, bitrate, video_options, audio_options, incompatible_stream_policy) };
+    run_job(plugin, job_handle, request, SwiftPointer(swift_internal_data_structure_pointer), inform_about_progress)
+}
+
+/// Queue a video re-encode on the plugin runtime and return immediately
+/// 
+/// Takes the same arguments as `reencode_video`, minus the job handle, plus
+/// a completion callback. Progress is reported from a worker thread while the
+/// job runs; `on_complete` is called exactly once from a worker thread with the
+/// final result.
+/// 
+/// # Arguments
+/// 
+/// * `on_complete` - Callback receiving the job handle and the final CResult, which it must free with free_c_result()
+/// 
+/// # Returns
+/// 
+/// A non-zero job handle usable with `cancel_job`, or 0 if the job could not
+/// be queued (in which case `on_complete` is never called)
+#[no_mangle]
+pub extern "C" fn submit_reencode_video(
+    plugin: *mut c_void,
+    input_path: *const c_char,
+    output_path: *const c_char,
+    target_width: c_int,
+    target_height: c_int,
+    bitrate: c_int,
+    video_options: *const CVideoOptions,
+    audio_options: *const CAudioOptions,
+    incompatible_stream_policy: c_int,
+    swift_internal_data_structure_pointer: *mut c_void,
+    inform_about_progress: InformAboutProgress,
+    on_complete: OnJobComplete,
+) -> u64 {
+    let request = unsafe { ReencodeVideoRequest::from_c(input_path, output_path, target_width, target_height, bitrate, video_options, audio_options, incompatible_stream_policy) };
+    submit_job(plugin, request, SwiftPointer(swift_internal_data_structure_pointer), inform_about_progress, on_complete)
+}
+
+/// Arguments of a remux request, converted from their C representation
+struct RemuxRequest {
+    input_path: String,
+    output_path: String,
+}
+
+impl RemuxRequest {
+    unsafe fn from_c(input_path: *const c_char, output_path: *const c_char) -> Result<Self, PluginError> {
+        let (input_path, output_path) = c_paths(input_path, output_path)?;
+        Ok(Self { input_path, output_path })
+    }
+}
+
+impl JobRequest for RemuxRequest {
+    fn run(
+        self,
+        plugin: &CapacitorFFmpegPlugin,
+        inform_about_progress: ProgressCallback,
+        cancellation: CancellationToken,
+    ) -> *mut CResult {
+        let result = plugin.remux(&self.input_path, &self.output_path, inform_about_progress, cancellation);
+        CResult::from_result("Remux failed", result)
+    }
+}
+
+/// Copy every stream of a media file into another container without re-encoding
+/// 
+/// Blocks until the job is done. See `submit_remux` for the asynchronous variant.
+/// 
+/// # Arguments
+/// 
+/// * `plugin` - A pointer to the plugin
+/// * `job_handle` - A handle returned by `create_job`, or 0 if the job does not need to be cancellable
+/// * `input_path` - The path to the input media file
+/// * `output_path` - The path to the output file; its extension picks the container
+/// * `swift_internal_data_structure_pointer` - Pointer to Swift data structure for callbacks
+/// * `inform_about_progress` - Callback function for progress updates
+/// 
+/// # Returns
+/// 
+/// Pointer to CResult structure - caller must call free_c_result() when done
+#[no_mangle]
+pub extern "C" fn remux(
+    plugin: *mut c_void,
+    job_handle: u64,
+    input_path: *const c_char,
+    output_path: *const c_char,
+    swift_internal_data_structure_pointer: *mut c_void,
+    inform_about_progress: InformAboutProgress,
+) -> *mut CResult {
+    let request = unsafe { RemuxRequest::from_c(input_path, output_path) };
+    run_job(plugin, job_handle, request, SwiftPointer(swift_internal_data_structure_pointer), inform_about_progress)
+}
+
+/// Queue a remux on the plugin runtime and return immediately
+/// 
+/// Takes the same arguments as `remux`, minus the job handle, plus a completion
+/// callback, and behaves like `submit_reencode_video`.
+/// 
+/// # Arguments
+/// 
+/// * `on_complete` - Callback receiving the job handle and the final CResult, which it must free with free_c_result()
+/// 
+/// # Returns
+/// 
+/// A non-zero job handle usable with `cancel_job`, or 0 if the job could not
+/// be queued (in which case `on_complete` is never called)
+#[no_mangle]
+pub extern "C" fn submit_remux(
+    plugin: *mut c_void,
+    input_path: *const c_char,
+    output_path: *const c_char,
+    swift_internal_data_structure_pointer: *mut c_void,
+    inform_about_progress: InformAboutProgress,
+    on_complete: OnJobComplete,
+) -> u64 {
+    let request = unsafe { RemuxRequest::from_c(input_path, output_path) };
+    submit_job(plugin, request, SwiftPointer(swift_internal_data_structure_pointer), inform_about_progress, on_complete)
+}
+
+/// Arguments of an audio extraction request, converted from their C representation
+struct ExtractAudioRequest {
+    input_path: String,
+    output_path: String,
+    stream_index: Option<usize>,
+    audio: Option<AudioSettings>,
+}
+
+impl ExtractAudioRequest {
+    unsafe fn from_c(
+        input_path: *const c_char,
+        output_path: *const c_char,
+        stream_index: c_int,
+        audio_options: *const CAudioOptions,
+    ) -> Result<Self, PluginError> {
+        let (input_path, output_path) = c_paths(input_path, output_path)?;
+        let stream_index = if stream_index < 0 { None } else { Some(stream_index as usize) };
+        let audio = match audio_options.as_ref() {
+            Some(options) => Some(AudioSettings::from_c(options)?),
+            None => None,
+        };
+        Ok(Self { input_path, output_path, stream_index, audio })
+    }
+}
+
+impl JobRequest for ExtractAudioRequest {
+    fn run(
+        self,
+        plugin: &CapacitorFFmpegPlugin,
+        inform_about_progress: ProgressCallback,
+        cancellation: CancellationToken,
+    ) -> *mut CResult {
+        let result = plugin.extract_audio(&self.input_path, &self.output_path, self.stream_index, self.audio, inform_about_progress, cancellation);
+        CResult::from_result("Audio extraction failed", result)
     }
+}
+
+/// Write one audio stream of a media file to a standalone audio file
+/// 
+/// Blocks until the job is done. See `submit_extract_audio` for the asynchronous variant.
+/// 
+/// # Arguments
+/// 
+/// * `plugin` - A pointer to the plugin
+/// * `job_handle` - A handle returned by `create_job`, or 0 if the job does not need to be cancellable
+/// * `input_path` - The path to the input media file
+/// * `output_path` - The path to the output audio file; its extension picks the container
+/// * `stream_index` - Index of the audio stream to extract (negative for the best one)
+/// * `audio_options` - How to transcode the stream, or null to copy it when the container allows
+/// * `swift_internal_data_structure_pointer` - Pointer to Swift data structure for callbacks
+/// * `inform_about_progress` - Callback function for progress updates
+/// 
+/// # Returns
+/// 
+/// Pointer to CResult structure - caller must call free_c_result() when done
+#[no_mangle]
+pub extern "C" fn extract_audio(
+    plugin: *mut c_void,
+    job_handle: u64,
+    input_path: *const c_char,
+    output_path: *const c_char,
+    stream_index: c_int,
+    audio_options: *const CAudioOptions,
+    swift_internal_data_structure_pointer: *mut c_void,
+    inform_about_progress: InformAboutProgress,
+) -> *mut CResult {
+    let request = unsafe { ExtractAudioRequest::from_c(input_path, output_path, stream_index, audio_options) };
+    run_job(plugin, job_handle, request, SwiftPointer(swift_internal_data_structure_pointer), inform_about_progress)
+}
+
+/// Queue an audio extraction on the plugin runtime and return immediately
+/// 
+/// Takes the same arguments as `extract_audio`, minus the job handle, plus a
+/// completion callback, and behaves like `submit_reencode_video`.
+/// 
+/// # Arguments
+/// 
+/// * `on_complete` - Callback receiving the job handle and the final CResult, which it must free with free_c_result()
+/// 
+/// # Returns
+/// 
+/// A non-zero job handle usable with `cancel_job`, or 0 if the job could not
+/// be queued (in which case `on_complete` is never called)
+#[no_mangle]
+pub extern "C" fn submit_extract_audio(
+    plugin: *mut c_void,
+    input_path: *const c_char,
+    output_path: *const c_char,
+    stream_index: c_int,
+    audio_options: *const CAudioOptions,
+    swift_internal_data_structure_pointer: *mut c_void,
+    inform_about_progress: InformAboutProgress,
+    on_complete: OnJobComplete,
+) -> u64 {
+    let request = unsafe { ExtractAudioRequest::from_c(input_path, output_path, stream_index, audio_options) };
+    submit_job(plugin, request, SwiftPointer(swift_internal_data_structure_pointer), inform_about_progress, on_complete)
+}
 
-    // Convert C strings to Rust strings safely
-    let input_path_str = unsafe { c_str_to_string(input_path) };
-    let output_path_str = unsafe { c_str_to_string(output_path) };
+/// Arguments of a trim request, converted from their C representation
+struct TrimRequest {
+    input_path: String,
+    output_path: String,
+    start_seconds: f64,
+    end_seconds: Option<f64>,
+    mode: TrimMode,
+}
 
-    let input_path_str = match input_path_str {
+impl TrimRequest {
+    unsafe fn from_c(
+        input_path: *const c_char,
+        output_path: *const c_char,
+        start_seconds: f64,
+        end_seconds: f64,
+        mode: c_int,
+    ) -> Result<Self, PluginError> {
+        let (input_path, output_path) = c_paths(input_path, output_path)?;
+        let mode = TrimMode::from_c(mode)
+            .ok_or_else(|| PluginError::new(ErrorCode::InvalidArgument, format!("Unknown trim mode {}", mode)))?;
+        let end_seconds = if end_seconds > 0.0 { Some(end_seconds) } else { None };
+        Ok(Self { input_path, output_path, start_seconds, end_seconds, mode })
+    }
+}
+
+impl JobRequest for TrimRequest {
+    fn run(
+        self,
+        plugin: &CapacitorFFmpegPlugin,
+        inform_about_progress: ProgressCallback,
+        cancellation: CancellationToken,
+    ) -> *mut CResult {
+        let result = plugin.trim(&self.input_path, &self.output_path, self.start_seconds, self.end_seconds, self.mode, inform_about_progress, cancellation);
+        CResult::from_result("Trim failed", result)
+    }
+}
+
+/// Cut a media file down to a time range
+/// 
+/// Blocks until the job is done. See `submit_trim` for the asynchronous variant.
+/// 
+/// # Arguments
+/// 
+/// * `plugin` - A pointer to the plugin
+/// * `job_handle` - A handle returned by `create_job`, or 0 if the job does not need to be cancellable
+/// * `input_path` - The path to the input media file
+/// * `output_path` - The path to the output file
+/// * `start_seconds` - Start of the kept range in seconds
+/// * `end_seconds` - End of the kept range in seconds (0 or negative to keep everything after the start)
+/// * `mode` - 0 to stream-copy from the previous keyframe, 1 to re-encode video for a frame-accurate cut
+/// * `swift_internal_data_structure_pointer` - Pointer to Swift data structure for callbacks
+/// * `inform_about_progress` - Callback function for progress updates
+/// 
+/// # Returns
+/// 
+/// Pointer to CResult structure - caller must call free_c_result() when done
+#[no_mangle]
+pub extern "C" fn trim(
+    plugin: *mut c_void,
+    job_handle: u64,
+    input_path: *const c_char,
+    output_path: *const c_char,
+    start_seconds: f64,
+    end_seconds: f64,
+    mode: c_int,
+    swift_internal_data_structure_pointer: *mut c_void,
+    inform_about_progress: InformAboutProgress,
+) -> *mut CResult {
+    let request = unsafe { TrimRequest::from_c(input_path, output_path, start_seconds, end_seconds, mode) };
+    run_job(plugin, job_handle, request, SwiftPointer(swift_internal_data_structure_pointer), inform_about_progress)
+}
+
+/// Queue a trim on the plugin runtime and return immediately
+/// 
+/// Takes the same arguments as `trim`, minus the job handle, plus a completion
+/// callback, and behaves like `submit_reencode_video`.
+/// 
+/// # Arguments
+/// 
+/// * `on_complete` - Callback receiving the job handle and the final CResult, which it must free with free_c_result()
+/// 
+/// # Returns
+/// 
+/// A non-zero job handle usable with `cancel_job`, or 0 if the job could not
+/// be queued (in which case `on_complete` is never called)
+#[no_mangle]
+pub extern "C" fn submit_trim(
+    plugin: *mut c_void,
+    input_path: *const c_char,
+    output_path: *const c_char,
+    start_seconds: f64,
+    end_seconds: f64,
+    mode: c_int,
+    swift_internal_data_structure_pointer: *mut c_void,
+    inform_about_progress: InformAboutProgress,
+    on_complete: OnJobComplete,
+) -> u64 {
+    let request = unsafe { TrimRequest::from_c(input_path, output_path, start_seconds, end_seconds, mode) };
+    submit_job(plugin, request, SwiftPointer(swift_internal_data_structure_pointer), inform_about_progress, on_complete)
+}
+
+/// Inspect a media file without decoding it
+/// 
+/// # Arguments
+/// 
+/// * `plugin` - A pointer to the plugin
+/// * `input_path` - The path to the media file
+/// 
+/// # Returns
+/// 
+/// Pointer to CResult structure whose `data` holds the probe JSON on success -
+/// caller must call free_c_result() when done
+#[no_mangle]
+pub extern "C" fn probe_media(plugin: *mut c_void, input_path: *const c_char) -> *mut CResult {
+    if plugin.is_null() {
+        return CResult::error(ErrorCode::InvalidArgument, "Plugin pointer is null".to_string());
+    }
+
+    let input_path_str = match unsafe { c_str_to_string(input_path) } {
         Some(path) => path,
         None => {
-            return CResult::error("Invalid input path".to_string());
+            return CResult::error(ErrorCode::InvalidArgument, "Invalid input path".to_string());
         }
     };
 
-    let output_path_str = match output_path_str {
+    let handle = unsafe { &*(plugin as *const PluginHandle) }.clone();
+    match handle.probe_media(&input_path_str) {
+        Ok(json) => CResult::success_with_data(json),
+        Err(e) => CResult::from_error("Media probe failed", &e),
+    }
+}
+
+/// Write a still image of the video frame nearest to a timestamp
+/// 
+/// # Arguments
+/// 
+/// * `plugin` - A pointer to the plugin
+/// * `input_path` - The path to the input video file
+/// * `output_path` - The path to the output image
+/// * `time_seconds` - Position of the frame in seconds from the start of the media
+/// * `target_width` - The width of the image (0 to derive it from the height or keep the source size)
+/// * `target_height` - The height of the image (0 to derive it from the width or keep the source size)
+/// * `image_format` - 0 for JPEG, 1 for PNG, 2 for WebP
+/// * `quality` - 0.0 (smallest) to 1.0 (best); ignored for PNG
+/// 
+/// # Returns
+/// 
+/// Pointer to CResult structure - caller must call free_c_result() when done
+#[no_mangle]
+pub extern "C" fn generate_thumbnail(
+    plugin: *mut c_void,
+    input_path: *const c_char,
+    output_path: *const c_char,
+    time_seconds: f64,
+    target_width: c_int,
+    target_height: c_int,
+    image_format: c_int,
+    quality: f64,
+) -> *mut CResult {
+    if plugin.is_null() {
+        return CResult::error(ErrorCode::InvalidArgument, "Plugin pointer is null".to_string());
+    }
+
+    let input_path_str = match unsafe { c_str_to_string(input_path) } {
         Some(path) => path,
         None => {
-            return CResult::error("Invalid output path".to_string());
+            return CResult::error(ErrorCode::InvalidArgument, "Invalid input path".to_string());
         }
     };
-
-    // Get reference to the plugin without consuming the Box
-    let plugin_ref = unsafe { &*(plugin as *const Arc<Mutex<Box<CapacitorFFmpegPlugin>>>) };
-    let locked_plugin = match plugin_ref.lock() {
-        Ok(plugin) => plugin,
-        Err(e) => {
-            let anyhow_error = anyhow!("Failed to lock plugin mutex: {:?}", e);
-            let error_message = format!("Mutex lock failed: {:?}\nBacktrace:\n{}", anyhow_error, anyhow_error.backtrace());
-            return CResult::error(error_message);
+    let output_path_str = match unsafe { c_str_to_string(output_path) } {
+        Some(path) => path,
+        None => {
+            return CResult::error(ErrorCode::InvalidArgument, "Invalid output path".to_string());
         }
     };
-
-    // Call the actual re-encoding function
-    let bitrate_option = if bitrate <= 0 {
-        None // Use default bitrate.
-    } else {
-        Some(bitrate as u64)
-    };
-
-    let wrapped_inform_about_progress: Arc<Box<dyn Fn(f64) -> Result<(), anyhow::Error>>> = Arc::new(Box::new(move |progress: f64| {
-        
-        // Call the C function with proper C string pointer
-        let result = inform_about_progress(progress, swift_internal_data_structure_pointer);
-        if result != 0 {
-            return Err(anyhow!("Failed to inform about progress"));
+    let image_format = match ImageFormat::from_c(image_format) {
+        Some(image_format) => image_format,
+        None => {
+            return CResult::error(ErrorCode::InvalidArgument, format!("Unknown image format {}", image_format));
         }
-        
-        // CString is dropped here, so C function must not rely on data persisting
-        Ok(())
-    }));
+    };
 
-    match locked_plugin.reencode_video(&input_path_str, &output_path_str, target_width as u32, target_height as u32, bitrate_option, wrapped_inform_about_progress) {
-        Ok(()) => {
-            println!("Video re-encoding completed successfully");
-            CResult::success()
-        }
-        Err(e) => {
-            let error_message = format!("Video re-encoding failed: {:?}\nBacktrace:\n{}", e, e.backtrace());
-            eprintln!("{}", error_message);
-            CResult::error(error_message)
-        }
+    let handle = unsafe { &*(plugin as *const PluginHandle) }.clone();
+    let result = handle.generate_thumbnail(
+        &input_path_str,
+        &output_path_str,
+        time_seconds,
+        target_width.max(0) as u32,
+        target_height.max(0) as u32,
+        image_format,
+        quality,
+    );
+    match result {
+        Ok(()) => CResult::success(),
+        Err(e) => CResult::from_error("Thumbnail generation failed", &e),
     }
-}
\ No newline at end of file
+}
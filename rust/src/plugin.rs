@@ -2,39 +2,162 @@ use anyhow::{Result, Context, bail};
 use ffmpeg_next::format;
 use ffmpeg_next::media;
 use ffmpeg_next::Codec;
-use ffmpeg_next::{codec, decoder, encoder, frame, picture, Dictionary, Packet, Rational};
+use ffmpeg_next::{codec, decoder, encoder, frame, picture, rescale, Packet, Rational, Rescale};
 use ffmpeg_next::software::scaling::{context::Context as ScalingContext, flag::Flags as ScalingFlags};
 use ffmpeg_next::util::format::Pixel;
 use std::collections::HashMap;
-use std::sync::Arc;
-use std::time::Instant;
-use tokio::runtime::{Builder, Runtime};
+use std::ffi::CString;
+use std::path::Path;
+use std::sync::Mutex;
+use tokio::runtime::{Builder, Handle, Runtime};
+
+use crate::audio::{self, AudioSettings, AudioTranscoder};
+use crate::error::{ErrorCode, PluginError};
+use crate::job::{Cancelled, CancellationToken, JobRegistry, JobSlots};
+use crate::probe;
+use crate::progress::{ProgressCallback, ProgressReporter, ProgressStage};
+use crate::thumbnail::{self, ImageFormat};
+use crate::trim::{TrimMode, TrimWindow};
+use crate::video::{self, FrameRotator, RateControl, ResizeLayout, VideoCodec, VideoSettings};
 
 // Bitrate constants (in bits per second)
 const MIN_BITRATE: u64 = 100_000;      // 100 Kbps minimum
 const MAX_BITRATE: u64 = 100_000_000;  // 100 Mbps maximum  
 const DEFAULT_BITRATE: u64 = 1_000_000; // 1 Mbps default
 
+// Target size encoding
+const TARGET_SIZE_ATTEMPTS: usize = 3;
+const TARGET_SIZE_OVERHEAD: f64 = 0.03; // share of the budget kept for container overhead
+const TARGET_SIZE_RETRY_MARGIN: f64 = 1.2; // take off 20% more than the overshoot when retrying
+
+const WORKER_THREADS: usize = 2; // 2 threads for now, but perhaps we will do more later
+
+/// What `reencode_video` does with a stream it would copy but the output container cannot hold
+///
+/// The values are part of the C ABI.
+#[repr(i32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IncompatibleStreamPolicy {
+    /// Transcode audio to the container's preferred codec; drop streams that cannot be transcoded
+    Transcode = 0,
+    /// Drop the stream with a warning
+    Drop = 1,
+    /// Fail before writing anything
+    Fail = 2,
+}
+
+impl IncompatibleStreamPolicy {
+    pub fn from_c(value: i32) -> Option<Self> {
+        match value {
+            0 => Some(IncompatibleStreamPolicy::Transcode),
+            1 => Some(IncompatibleStreamPolicy::Drop),
+            2 => Some(IncompatibleStreamPolicy::Fail),
+            _ => None,
+        }
+    }
+}
+
+/// How `reencode_video` produced its output
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReencodePath {
+    /// Decoded and encoded again
+    Reencoded,
+    /// Already met the request; streams copied into the output container
+    Remuxed,
+    /// Already met the request in the same container; file copied as is
+    Copied,
+}
+
+impl ReencodePath {
+    pub fn name(self) -> &'static str {
+        match self {
+            ReencodePath::Reencoded => "reencoded",
+            ReencodePath::Remuxed => "remuxed",
+            ReencodePath::Copied => "copied",
+        }
+    }
+}
+
+/// What a successful `reencode_video` did
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReencodeOutcome {
+    pub path: ReencodePath,
+    /// Indices of the input streams left out because the output container cannot hold them
+    pub dropped_streams: Vec<usize>,
+}
+
+impl ReencodeOutcome {
+    fn new(path: ReencodePath) -> Self {
+        Self { path, dropped_streams: Vec::new() }
+    }
+}
+
 // We don't take a function that is global for inform_about_progress, but rather the function itself (reencode_video) takes the function as a parameter
 // This is really important for Swift
+//
+// The plugin is shared between threads without an outer lock: every operation takes `&self`,
+// and each piece of shared state carries its own synchronisation.
 pub struct CapacitorFFmpegPlugin {
-    runtime: Runtime,
+    runtime: Mutex<Option<Runtime>>, // None once the plugin has been destroyed
+    jobs: JobRegistry,
+    job_slots: JobSlots,
 }
 
 impl CapacitorFFmpegPlugin {
-    pub fn new() -> Result<Self, anyhow::Error> {
+    /// Create the plugin
+    /// 
+    /// # Arguments
+    /// 
+    /// * `max_concurrent_jobs` - How many encodes may run at once (defaults to the runtime's worker count)
+    pub fn new(max_concurrent_jobs: Option<usize>) -> Result<Self, anyhow::Error> {
+        ffmpeg_next::init()?;
+
         let runtime = Builder::new_multi_thread()
-            .worker_threads(2) // 2 threads for now, but perhaps we will do more later
+            .worker_threads(WORKER_THREADS)
             .thread_name("ffmpeg-worker")
             .build()?;
         
         Ok(Self { 
-            runtime
+            runtime: Mutex::new(Some(runtime)),
+            jobs: JobRegistry::new(),
+            job_slots: JobSlots::new(max_concurrent_jobs.unwrap_or(WORKER_THREADS)),
         })
     }
 
-    pub fn destroy(&mut self) {
-        // TODO: Implement
+    /// Table of cancellable jobs
+    pub fn jobs(&self) -> &JobRegistry {
+        &self.jobs
+    }
+
+    /// Handle used to submit background jobs to the plugin runtime
+    ///
+    /// Returns None once the plugin has been destroyed.
+    pub fn runtime_handle(&self) -> Option<Handle> {
+        match self.runtime.lock() {
+            Ok(runtime) => runtime.as_ref().map(|runtime| runtime.handle().clone()),
+            Err(err) => {
+                eprintln!("Cannot lock runtime: {}", err);
+                None
+            }
+        }
+    }
+
+    /// Cancel every job and shut down the runtime
+    ///
+    /// Jobs that are already running stop at their next cancellation check;
+    /// jobs still queued on the runtime are dropped without running.
+    pub fn destroy(&self) {
+        self.jobs.cancel_all();
+        let runtime = match self.runtime.lock() {
+            Ok(mut runtime) => runtime.take(),
+            Err(err) => {
+                eprintln!("Cannot lock runtime: {}", err);
+                return;
+            }
+        };
+        if let Some(runtime) = runtime {
+            runtime.shutdown_background();
+        }
     }
 
     /// Validates and converts bitrate to a safe usize value
@@ -50,229 +173,1762 @@ impl CapacitorFFmpegPlugin {
         let bitrate = bitrate.unwrap_or(DEFAULT_BITRATE);
         
         if bitrate < MIN_BITRATE {
-            bail!("Bitrate {} is too low. Minimum is {} bps", bitrate, MIN_BITRATE);
+            bail!(PluginError::new(
+                ErrorCode::InvalidBitrate,
+                format!("Bitrate {} is too low. Minimum is {} bps", bitrate, MIN_BITRATE),
+            ));
         }
         
         if bitrate > MAX_BITRATE {
-            bail!("Bitrate {} is too high. Maximum is {} bps", bitrate, MAX_BITRATE);
+            bail!(PluginError::new(
+                ErrorCode::InvalidBitrate,
+                format!("Bitrate {} is too high. Maximum is {} bps", bitrate, MAX_BITRATE),
+            ));
+        }
+        
+        // Safe conversion from u64 to usize
+        bitrate.try_into()
+            .with_context(|| format!("Bitrate {} cannot be converted to usize on this platform", bitrate))
+    }
+
+    /// Turn a path or `file://` URL coming from Swift into a filesystem path
+    fn file_path(path: &str) -> String {
+        if path.starts_with("file://") {
+            path.replace("file://", "")
+        } else {
+            path.to_string()
+        }
+    }
+
+    fn ensure_input_exists(input_file: &str) -> Result<()> {
+        if !Path::new(input_file).exists() {
+            bail!(PluginError::new(
+                ErrorCode::InputNotFound,
+                format!("Input file does not exist: {}", input_file),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Fail when `output_file` is `input_file` under another name
+    ///
+    /// Opening the output truncates it, so this has to come before `format::output`.
+    fn ensure_distinct_output(input_file: &str, output_file: &str) -> Result<()> {
+        let same_file = match (std::fs::canonicalize(input_file), std::fs::canonicalize(output_file)) {
+            (Ok(input), Ok(output)) => input == output,
+            _ => false,
+        };
+        if same_file {
+            bail!(PluginError::new(ErrorCode::InvalidArgument, "Input and output are the same file"));
+        }
+        Ok(())
+    }
+
+    fn remove_partial_output(output_file: &str) {
+        if let Err(remove_err) = std::fs::remove_file(output_file) {
+            eprintln!("Failed to remove partial output {}: {:?}", output_file, remove_err);
+        }
+    }
+
+    /// Duration in seconds, frame count and file size used as progress totals
+    ///
+    /// Prefers the duration of the best video stream, which is the one progress
+    /// is reported for, then the container's. Any of them may be 0 when unknown.
+    fn media_totals(ictx: &format::context::Input, input_file: &str) -> (f64, i64, u64) {
+        let (stream_seconds, total_frames) = ictx
+            .streams()
+            .best(media::Type::Video)
+            .map(|stream| {
+                let seconds = if stream.duration() > 0 {
+                    stream.duration() as f64 * f64::from(stream.time_base())
+                } else {
+                    0.0
+                };
+                (seconds, stream.frames())
+            })
+            .unwrap_or((0.0, 0));
+        let total_seconds = if stream_seconds > 0.0 {
+            stream_seconds
+        } else if ictx.duration() > 0 {
+            ictx.duration() as f64 * f64::from(rescale::TIME_BASE)
+        } else {
+            0.0
+        };
+        let input_size = std::fs::metadata(input_file)
+            .map(|metadata| metadata.len())
+            .unwrap_or(0);
+        (total_seconds, total_frames, input_size)
+    }
+
+    /// Add an output stream that carries `ist` without decoding it
+    fn add_copy_stream(ist: &format::stream::Stream, octx: &mut format::context::Output) -> Result<()> {
+        // Copied HEVC keeps an hvc1 tag where one is needed, which the reset below would lose
+        let hvc1_tag = VideoCodec::Hevc.codec_tag(octx.format().name())
+            .filter(|tag| ist.parameters().id() == codec::Id::HEVC && unsafe { (*ist.parameters().as_ptr()).codec_tag } == *tag);
+        let mut ost = octx.add_stream(encoder::find(codec::Id::None))
+            .with_context(|| format!("Failed to add stream for copying stream {}", ist.index()))?;
+        ost.set_parameters(ist.parameters());
+        // We need to set codec_tag to 0 lest we run into incompatible codec tag
+        // issues when muxing into a different container format. Unfortunately
+        // there's no high level API to do this (yet).
+        unsafe {
+            (*ost.parameters().as_mut_ptr()).codec_tag = hvc1_tag.unwrap_or(0);
+        }
+        Ok(())
+    }
+
+    fn write_copied_packet(
+        packet: &mut Packet,
+        ist_time_base: Rational,
+        ost_time_base: Rational,
+        ost_index: usize,
+        octx: &mut format::context::Output,
+    ) -> Result<()> {
+        packet.rescale_ts(ist_time_base, ost_time_base);
+        packet.set_position(-1);
+        packet.set_stream(ost_index);
+        packet.write_interleaved(octx)
+            .context("Failed to write packet to output")?;
+        Ok(())
+    }
+
+    /// Inspect a media file without decoding it
+    /// 
+    /// # Arguments
+    /// 
+    /// * `input_path` - Path to the media file
+    /// 
+    /// # Returns
+    /// 
+    /// JSON describing the container, duration, bitrate, metadata, chapters and every stream
+    pub fn probe_media(&self, input_path: &String) -> Result<String> {
+        let input_file = Self::file_path(input_path);
+        Self::ensure_input_exists(&input_file)?;
+
+        let ictx = format::input(&input_file)
+            .with_context(|| format!("Failed to open input file: {}", input_file))?;
+
+        Ok(probe::describe_input(&ictx).to_string())
+    }
+
+    /// Write a still image of the video frame nearest to a timestamp
+    ///
+    /// # Arguments
+    ///
+    /// * `input_path` - Path to the input video file
+    /// * `output_path` - Path to save the image
+    /// * `time_seconds` - Position of the frame, from the start of the media; past the end, the last frame is used
+    /// * `target_width` - Width of the upright image (0 derives it from the height, or keeps the source width)
+    /// * `target_height` - Height of the upright image (0 derives it from the width, or keeps the source height)
+    /// * `image_format` - JPEG, PNG or WebP
+    /// * `quality` - 0.0 (smallest) to 1.0 (best); ignored for PNG
+    ///
+    /// # Returns
+    ///
+    /// Result indicating success or error
+    pub fn generate_thumbnail(
+        &self,
+        input_path: &String,
+        output_path: &String,
+        time_seconds: f64,
+        target_width: u32,
+        target_height: u32,
+        image_format: ImageFormat,
+        quality: f64,
+    ) -> Result<()> {
+        if !time_seconds.is_finite() || time_seconds < 0.0 {
+            bail!(PluginError::new(
+                ErrorCode::InvalidArgument,
+                format!("Thumbnail time {} must be a non-negative number of seconds", time_seconds),
+            ));
+        }
+        if !(0.0..=1.0).contains(&quality) {
+            bail!(PluginError::new(
+                ErrorCode::InvalidArgument,
+                format!("Thumbnail quality {} must be between 0.0 and 1.0", quality),
+            ));
+        }
+
+        let input_file = Self::file_path(input_path);
+        let output_file = Self::file_path(output_path);
+        Self::ensure_input_exists(&input_file)?;
+        let codec = image_format.encoder()?;
+
+        let mut ictx = format::input(&input_file)
+            .with_context(|| format!("Failed to open input file: {}", input_file))?;
+        let (stream_index, time_base, rotation, target_timestamp, mut decoder) = {
+            let stream = ictx
+                .streams()
+                .best(media::Type::Video)
+                .ok_or_else(|| PluginError::new(ErrorCode::UnsupportedFormat, "Input has no video stream"))?;
+            let start_time = if stream.start_time() == ffmpeg_next::ffi::AV_NOPTS_VALUE { 0 } else { stream.start_time() };
+            let target_timestamp = start_time + (time_seconds / f64::from(stream.time_base())) as i64;
+            let decoder = codec::context::Context::from_parameters(stream.parameters())?
+                .decoder()
+                .video()
+                .context("Failed to open video decoder")?;
+            (stream.index(), stream.time_base(), probe::rotation(&stream), target_timestamp, decoder)
+        };
+
+        // Land on the keyframe at or before the requested time, then decode forward from it
+        let seek_timestamp = target_timestamp.rescale(time_base, rescale::TIME_BASE);
+        if let Err(e) = ictx.seek(seek_timestamp, ..seek_timestamp) {
+            eprintln!("Seeking to {}s failed, decoding from the start: {:?}", time_seconds, e);
+        }
+
+        let distance = |frame: &frame::Video| frame.timestamp().map(|ts| (ts - target_timestamp).abs());
+        let mut previous: Option<frame::Video> = None;
+        let mut nearest: Option<frame::Video> = None;
+        let mut decoded = frame::Video::empty();
+        'packets: for (stream, packet) in ictx.packets() {
+            if stream.index() != stream_index {
+                continue;
+            }
+            decoder.send_packet(&packet)
+                .context("Failed to send packet to decoder")?;
+            while decoder.receive_frame(&mut decoded).is_ok() {
+                let current = std::mem::replace(&mut decoded, frame::Video::empty());
+                if current.timestamp().map_or(false, |ts| ts >= target_timestamp) {
+                    // The requested time may fall between two frames: keep whichever is closer
+                    nearest = match previous.take() {
+                        Some(previous) if matches!(
+                            (distance(&previous), distance(&current)),
+                            (Some(before), Some(after)) if before < after
+                        ) => Some(previous),
+                        _ => Some(current),
+                    };
+                    break 'packets;
+                }
+                previous = Some(current);
+            }
+        }
+        if nearest.is_none() {
+            decoder.send_eof()
+                .context("Failed to send EOF to decoder")?;
+            while decoder.receive_frame(&mut decoded).is_ok() {
+                previous = Some(std::mem::replace(&mut decoded, frame::Video::empty()));
+            }
+            nearest = previous;
+        }
+        let source = nearest
+            .ok_or_else(|| PluginError::new(ErrorCode::CorruptInput, "No video frame could be decoded"))?;
+
+        // Turned upright first, so the target size is in display orientation like for re-encodes
+        let (display_width, display_height) = video::display_size(source.width(), source.height(), rotation);
+        let rotator = FrameRotator::new(
+            source.format(),
+            source.width(),
+            source.height(),
+            time_base,
+            decoder.aspect_ratio(),
+            rotation,
+        )?;
+        let source = match rotator {
+            Some(mut rotator) => rotator.rotate(&source).context("Failed to rotate decoded frame")?,
+            None => source,
+        };
+
+        let (width, height) = thumbnail::thumbnail_size(display_width, display_height, target_width, target_height);
+        let mut scaler = ScalingContext::get(
+            source.format(),
+            display_width,
+            display_height,
+            ImageFormat::pixel_format(codec),
+            width,
+            height,
+            ScalingFlags::BICUBIC,
+        )?;
+        let mut picture = frame::Video::empty();
+        scaler
+            .run(&source, &mut picture)
+            .context("Failed to scale decoded frame")?;
+        picture.set_pts(Some(0));
+
+        let image = thumbnail::encode_image(codec, image_format, &picture, quality)?;
+        std::fs::write(&output_file, image)
+            .with_context(|| format!("Failed to write thumbnail: {}", output_file))?;
+        Ok(())
+    }
+
+    /// Re-encode a video file to a lower resolution
+    /// 
+    /// # Arguments
+    /// 
+    /// * `input_path` - Path to the input video file
+    /// * `output_path` - Path to save the re-encoded video
+    /// * `target_width` - Target width for the output video (0 to derive it from the height)
+    /// * `target_height` - Target height for the output video (0 to derive it from the width)
+    /// * `bitrate` - Target bitrate in bits per second (optional, defaults to 1Mbps); ignored for
+    ///   a target size and by the intermediate codecs
+    /// * `video` - How the picture is fitted into the target size and encoded
+    /// * `audio` - How to transcode audio streams; None copies them
+    /// * `incompatible_streams` - What to do with streams the output container cannot hold as is
+    /// * `inform_about_progress` - Receives throttled progress reports; an error return cancels the job
+    /// * `cancellation` - Token checked between packets; when set, the partial output is removed
+    /// 
+    /// # Returns
+    /// 
+    /// The path taken to produce the output and the streams it dropped, or an error. A cancelled
+    /// job fails with [`Cancelled`].
+    pub fn reencode_video(
+        &self,
+        input_path: &String,
+        output_path: &String,
+        target_width: u32,
+        target_height: u32,
+        bitrate: Option<u64>,
+        video: VideoSettings,
+        audio: Option<AudioSettings>,
+        incompatible_streams: IncompatibleStreamPolicy,
+        inform_about_progress: ProgressCallback,
+        cancellation: CancellationToken,
+    ) -> Result<ReencodeOutcome, anyhow::Error> {
+        // Validate bitrate early, unless the target size or the intermediate codec's profile sets it
+        let validated_bitrate = if video.target_size.is_some() || video.codec.is_intermediate() {
+            DEFAULT_BITRATE as usize
+        } else {
+            Self::validate_bitrate(bitrate).context("Invalid bitrate specified")?
+        };
+        let validated_max_bitrate = video.max_bit_rate
+            .map(|max_bit_rate| Self::validate_bitrate(Some(max_bit_rate)))
+            .transpose()
+            .context("Invalid maximum bitrate specified")?;
+
+        // Held until the encode is done, so that at most `max_concurrent_jobs` run at once
+        let _slot = self.job_slots.acquire(&cancellation)?;
+        
+        let input_file = Self::file_path(input_path);
+        let output_file = Self::file_path(output_path);
+
+        let mut progress = ProgressReporter::new(inform_about_progress, cancellation.clone(), &output_file);
+
+        let result = (|| {
+            if video.skip_if_compliant {
+                let path = Self::passthrough_path(
+                    &input_file,
+                    &output_file,
+                    target_width,
+                    target_height,
+                    validated_bitrate,
+                    validated_max_bitrate,
+                    &video,
+                    audio.as_ref(),
+                )?;
+                match path {
+                    ReencodePath::Copied => {
+                        progress.set_stage(ProgressStage::Finalizing);
+                        std::fs::copy(&input_file, &output_file)
+                            .with_context(|| format!("Failed to copy {} to {}", input_file, output_file))?;
+                        return Ok(ReencodeOutcome::new(path));
+                    }
+                    ReencodePath::Remuxed => {
+                        Self::remux_streams(&input_file, &output_file, &mut progress, &cancellation)?;
+                        return Ok(ReencodeOutcome::new(path));
+                    }
+                    ReencodePath::Reencoded => {}
+                }
+            }
+
+            let dropped_streams = match video.target_size {
+                Some(target_size) => Self::reencode_video_to_size(
+                    &input_file,
+                    &output_file,
+                    target_width,
+                    target_height,
+                    target_size,
+                    validated_max_bitrate,
+                    &video,
+                    audio.as_ref(),
+                    incompatible_streams,
+                    &mut progress,
+                    &cancellation,
+                ),
+                None => Self::reencode_video_passes(
+                    &input_file,
+                    &output_file,
+                    target_width,
+                    target_height,
+                    validated_bitrate,
+                    validated_max_bitrate,
+                    &video,
+                    audio.as_ref(),
+                    incompatible_streams,
+                    &mut progress,
+                    &cancellation,
+                ),
+            }?;
+            Ok(ReencodeOutcome { path: ReencodePath::Reencoded, dropped_streams })
+        })();
+
+        // Handle the result - you can log errors, call a callback, etc.
+        if let Err(e) = result {
+            if e.downcast_ref::<Cancelled>().is_some() {
+                return Err(e);
+            }
+            eprintln!("Error during video encoding: {:?}", e);
+            // You could also call a callback to inform about the error
+            // if let Err(callback_err) = inform_about_progress(0.0, format!("Error: {}", e)) {
+            //     eprintln!("Failed to report error: {:?}", callback_err);
+            // }
+            return Err(e);
+        }
+    
+        result
+    }
+
+    /// How `reencode_video` should produce the output in smart mode
+    ///
+    /// The input is copied as is when it meets the request and the output has the
+    /// same extension, remuxed when it meets the request in another container, and
+    /// re-encoded otherwise. The target size is an upper bound here: smaller inputs
+    /// are not scaled up or padded.
+    fn passthrough_path(
+        input_file: &str,
+        output_file: &str,
+        target_width: u32,
+        target_height: u32,
+        bitrate: usize,
+        max_bitrate: Option<usize>,
+        video: &VideoSettings,
+        audio: Option<&AudioSettings>,
+    ) -> Result<ReencodePath> {
+        Self::ensure_input_exists(input_file)?;
+        Self::ensure_distinct_output(input_file, output_file)?;
+        let ictx = format::input(input_file)
+            .with_context(|| format!("Failed to open input file: {}", input_file))?;
+        // Looked up by name: opening the output here would truncate it before a path is chosen
+        let muxer = Self::guess_muxer(output_file)?;
+        let reason =
+            Self::reencode_reason(&ictx, &muxer, input_file, target_width, target_height, bitrate, max_bitrate, video, audio);
+        if reason.is_some() {
+            return Ok(ReencodePath::Reencoded);
+        }
+
+        let extension = |path: &str| Path::new(path).extension().map(|extension| extension.to_ascii_lowercase());
+        let dropped_streams = ictx.streams().any(|ist| {
+            !matches!(ist.parameters().medium(), media::Type::Audio | media::Type::Video | media::Type::Subtitle)
+        });
+        if extension(input_file) == extension(output_file) && !dropped_streams {
+            Ok(ReencodePath::Copied)
+        } else {
+            Ok(ReencodePath::Remuxed)
+        }
+    }
+
+    /// Why the input has to be re-encoded to meet the request, or None if it already does
+    fn reencode_reason(
+        ictx: &format::context::Input,
+        muxer: &format::Output,
+        input_file: &str,
+        target_width: u32,
+        target_height: u32,
+        bitrate: usize,
+        max_bitrate: Option<usize>,
+        video: &VideoSettings,
+        audio: Option<&AudioSettings>,
+    ) -> Option<String> {
+        if audio.is_some() {
+            return Some("audio transcoding was requested".to_string());
+        }
+        if video.has_gop_options() {
+            return Some("keyframe options were requested".to_string());
+        }
+        let (total_seconds, _, input_size) = Self::media_totals(ictx, input_file);
+        if let Some(target_size) = video.target_size {
+            if input_size == 0 || input_size > target_size {
+                return Some(format!("the input is {} bytes, over the {} byte target", input_size, target_size));
+            }
+        }
+        let bitrate_limit = Self::bitrate_limit(video, bitrate, max_bitrate);
+
+        let mut has_video = false;
+        for ist in ictx.streams() {
+            let medium = ist.parameters().medium();
+            if !matches!(medium, media::Type::Audio | media::Type::Video | media::Type::Subtitle) {
+                continue;
+            }
+            if !Self::can_copy_stream(muxer, &ist) {
+                return Some(Self::incompatible_stream_message(muxer, &ist));
+            }
+            if medium != media::Type::Video {
+                continue;
+            }
+            has_video = true;
+
+            let (width, height, format, aspect_ratio, profile, level, stream_bit_rate, codec_tag) = unsafe {
+                let parameters = &*ist.parameters().as_ptr();
+                (
+                    parameters.width as u32,
+                    parameters.height as u32,
+                    probe::pixel_format(parameters.format),
+                    Rational::from(parameters.sample_aspect_ratio),
+                    parameters.profile,
+                    parameters.level,
+                    parameters.bit_rate,
+                    parameters.codec_tag,
+                )
+            };
+            let stream = VideoStreamFacts {
+                index: ist.index(),
+                codec_id: ist.parameters().id(),
+                width,
+                height,
+                format,
+                aspect_ratio,
+                rotation: probe::rotation(&ist),
+                profile,
+                level,
+                bit_rate: if stream_bit_rate > 0 {
+                    stream_bit_rate
+                } else if total_seconds > 0.0 {
+                    // Whole-file bitrate, an upper bound for the video stream
+                    (input_size as f64 * 8.0 / total_seconds) as i64
+                } else {
+                    0
+                },
+                codec_tag,
+            };
+            let reason = Self::video_stream_reason(
+                &stream,
+                muxer.name(),
+                target_width,
+                target_height,
+                bitrate_limit,
+                video,
+            );
+            if reason.is_some() {
+                return reason;
+            }
+        }
+        if !has_video {
+            return Some("the input has no video stream".to_string());
+        }
+        None
+    }
+
+    /// Bitrate a stream may have to be kept as is, or None if any bitrate will do
+    ///
+    /// Streams are judged against the bitrate the requested rate control would allow; the
+    /// intermediate codecs have none, and are judged on their profile instead.
+    fn bitrate_limit(video: &VideoSettings, bitrate: usize, max_bitrate: Option<usize>) -> Option<i64> {
+        match video.rate_control {
+            _ if video.codec.is_intermediate() => None,
+            RateControl::Abr | RateControl::Cbr => Some(bitrate as i64),
+            RateControl::Crf => max_bitrate.map(|max_bitrate| max_bitrate as i64),
+            RateControl::TargetSize => None,
+        }
+    }
+
+    /// Why a video stream has to be re-encoded to meet the request, or None if it can be kept
+    ///
+    /// # Arguments
+    ///
+    /// * `muxer` - Name of the output format, which decides the codec tag the stream needs
+    /// * `bitrate_limit` - Highest bitrate the stream may have, from [`Self::bitrate_limit`]
+    fn video_stream_reason(
+        stream: &VideoStreamFacts,
+        muxer: &str,
+        target_width: u32,
+        target_height: u32,
+        bitrate_limit: Option<i64>,
+        video: &VideoSettings,
+    ) -> Option<String> {
+        let index = stream.index;
+        if stream.codec_id != video.codec.id() {
+            return Some(format!("stream {} is {}, not {}", index, stream.codec_id.name(), video.codec.name()));
+        }
+        if !video.codec.keeps_format(stream.format) {
+            return Some(format!(
+                "stream {} has pixel format {:?}, which {} output does not keep",
+                index, stream.format, video.codec.name()
+            ));
+        }
+        if let Some(tag) = video.codec.codec_tag(muxer) {
+            if stream.codec_tag != tag {
+                return Some(format!("stream {} is not tagged {}", index, String::from_utf8_lossy(&tag.to_le_bytes())));
+            }
+        }
+
+        let (display_width, display_height) = video::display_size(stream.width, stream.height, stream.rotation);
+        let display_aspect_ratio = if stream.rotation % 180 == 90 { stream.aspect_ratio.invert() } else { stream.aspect_ratio };
+        let layout = ResizeLayout::new(
+            display_width,
+            display_height,
+            display_aspect_ratio,
+            stream.format,
+            target_width,
+            target_height,
+            video.resize_mode,
+        );
+        let fits = display_width <= layout.width && display_height <= layout.height;
+        if !fits || layout.crop.is_some() {
+            return Some(format!(
+                "stream {} is {}x{}, larger than the requested {}x{}",
+                index, display_width, display_height, layout.width, layout.height
+            ));
+        }
+
+        if let Some(limit) = bitrate_limit {
+            if stream.bit_rate <= 0 {
+                return Some(format!("the bitrate of stream {} is unknown", index));
+            }
+            if stream.bit_rate > limit {
+                return Some(format!("stream {} is {} bps, over the {} bps limit", index, stream.bit_rate, limit));
+            }
+        }
+        if let Some(requested) = video.profile {
+            if !requested.accepts(stream.profile) {
+                return Some(format!(
+                    "stream {} uses H.264 profile {}, which {:?} players may not decode",
+                    index, stream.profile, requested
+                ));
+            }
+        }
+        if let Some(requested) = video.av_profile() {
+            if stream.profile != requested {
+                return Some(format!(
+                    "stream {} is {} profile {}, not the requested {}",
+                    index, video.codec.name(), stream.profile, requested
+                ));
+            }
+        }
+        if let Some(requested) = video.level {
+            // HEVC streams carry general_level_idc, which is 30 times the level number
+            let level = if video.codec == VideoCodec::Hevc { stream.level / 3 } else { stream.level };
+            if level <= 0 || level as u32 > requested {
+                return Some(format!(
+                    "stream {} is at {} level {}, above the requested {}",
+                    index, video.codec.name(), level, requested
+                ));
+            }
+        }
+        None
+    }
+
+    /// Re-encode so that the output fits in `target_size` bytes
+    ///
+    /// The video bitrate is what the budget leaves after audio and container
+    /// overhead, spread over the duration. An output that still overshoots is
+    /// removed and encoded again at a bitrate lowered by the overshoot, up to
+    /// `TARGET_SIZE_ATTEMPTS` times; progress carries on across the attempts.
+    fn reencode_video_to_size(
+        input_file: &str,
+        output_file: &str,
+        target_width: u32,
+        target_height: u32,
+        target_size: u64,
+        max_bitrate: Option<usize>,
+        video: &VideoSettings,
+        audio: Option<&AudioSettings>,
+        incompatible_streams: IncompatibleStreamPolicy,
+        progress: &mut ProgressReporter,
+        cancellation: &CancellationToken,
+    ) -> Result<Vec<usize>> {
+        progress.set_stage(ProgressStage::Probing);
+        Self::ensure_input_exists(input_file)?;
+        let (duration, audio_bitrate) = {
+            let ictx = format::input(input_file)
+                .with_context(|| format!("Failed to open input file: {}", input_file))?;
+            let (total_seconds, _, _) = Self::media_totals(&ictx, input_file);
+            let audio_bitrate: u64 = ictx
+                .streams()
+                .filter(|ist| ist.parameters().medium() == media::Type::Audio)
+                .map(|ist| audio::estimated_bit_rate(&ist, audio) as u64)
+                .sum();
+            (total_seconds, audio_bitrate)
+        };
+        if duration <= 0.0 {
+            bail!(PluginError::new(
+                ErrorCode::UnsupportedFormat,
+                "Cannot encode to a target size: the input duration is unknown",
+            ));
+        }
+
+        let budget_bits = target_size as f64 * 8.0 * (1.0 - TARGET_SIZE_OVERHEAD);
+        let mut video_bitrate = budget_bits / duration - audio_bitrate as f64;
+        for attempt in 0..TARGET_SIZE_ATTEMPTS {
+            if video_bitrate < MIN_BITRATE as f64 {
+                bail!(PluginError::new(
+                    ErrorCode::InvalidBitrate,
+                    format!(
+                        "Target size of {} bytes is too small for {:.1} s of video with {} bps of audio",
+                        target_size, duration, audio_bitrate
+                    ),
+                ));
+            }
+            let bitrate = Self::validate_bitrate(Some((video_bitrate as u64).min(MAX_BITRATE)))?;
+            if attempt > 0 {
+                progress.start_retry();
+            }
+            let dropped_streams = Self::reencode_video_passes(
+                input_file,
+                output_file,
+                target_width,
+                target_height,
+                bitrate,
+                max_bitrate,
+                video,
+                audio,
+                incompatible_streams,
+                progress,
+                cancellation,
+            )?;
+
+            let output_size = std::fs::metadata(output_file)
+                .with_context(|| format!("Failed to read output file size: {}", output_file))?
+                .len();
+            if output_size <= target_size {
+                return Ok(dropped_streams);
+            }
+            Self::remove_partial_output(output_file);
+            // Shrink the video share by the overshoot, with some margin so the next attempt lands under
+            let overshoot_bits = (output_size - target_size) as f64 * 8.0 * TARGET_SIZE_RETRY_MARGIN;
+            video_bitrate = bitrate as f64 - overshoot_bits / duration;
+        }
+
+        bail!(PluginError::new(
+            ErrorCode::InvalidBitrate,
+            format!("Could not fit the output in {} bytes after {} attempts", target_size, TARGET_SIZE_ATTEMPTS),
+        ))
+    }
+
+    /// Encode in one pass, or in an analysis pass followed by the final pass when `video` asks for two
+    ///
+    /// The encoder stats of a two-pass encode are kept next to the output and removed afterwards.
+    fn reencode_video_passes(
+        input_file: &str,
+        output_file: &str,
+        target_width: u32,
+        target_height: u32,
+        bitrate: usize,
+        max_bitrate: Option<usize>,
+        video: &VideoSettings,
+        audio: Option<&AudioSettings>,
+        incompatible_streams: IncompatibleStreamPolicy,
+        progress: &mut ProgressReporter,
+        cancellation: &CancellationToken,
+    ) -> Result<Vec<usize>> {
+        if !video.two_pass {
+            progress.start_pass(0, 1);
+            return Self::reencode_video_pass(
+                input_file,
+                output_file,
+                target_width,
+                target_height,
+                bitrate,
+                max_bitrate,
+                video,
+                audio,
+                incompatible_streams,
+                EncoderPass::Single,
+                progress,
+                cancellation,
+            );
+        }
+
+        let stats_prefix = format!("{}.{}-stats", output_file, video.codec.library());
+        let result = (|| {
+            progress.start_pass(0, 2);
+            Self::reencode_video_pass(
+                input_file,
+                output_file,
+                target_width,
+                target_height,
+                bitrate,
+                max_bitrate,
+                video,
+                audio,
+                incompatible_streams,
+                EncoderPass::Analysis { stats_prefix: &stats_prefix },
+                progress,
+                cancellation,
+            )?;
+            progress.start_pass(1, 2);
+            Self::reencode_video_pass(
+                input_file,
+                output_file,
+                target_width,
+                target_height,
+                bitrate,
+                max_bitrate,
+                video,
+                audio,
+                incompatible_streams,
+                EncoderPass::Final { stats_prefix: &stats_prefix },
+                progress,
+                cancellation,
+            )
+        })();
+        Self::remove_stats_files(&stats_prefix);
+        result
+    }
+
+    /// Remove the files the encoder wrote for a two-pass encode: the stats and their `.mbtree`,
+    /// `.cutree` and `.temp` companions
+    fn remove_stats_files(stats_prefix: &str) {
+        let prefix = Path::new(stats_prefix);
+        let directory = match prefix.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        let Some(name) = prefix.file_name().map(|name| name.to_string_lossy().into_owned()) else {
+            return;
+        };
+        let Ok(entries) = std::fs::read_dir(directory) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            if entry.file_name().to_string_lossy().starts_with(&name) {
+                if let Err(e) = std::fs::remove_file(entry.path()) {
+                    eprintln!("Warning: failed to remove two-pass stats file {}: {}", entry.path().display(), e);
+                }
+            }
+        }
+    }
+
+    /// Decode the input once and write the re-encoded output
+    ///
+    /// An analysis pass only encodes video; its output is overwritten by the final pass.
+    fn reencode_video_pass(
+        input_file: &str,
+        output_file: &str,
+        target_width: u32,
+        target_height: u32,
+        bitrate: usize,
+        max_bitrate: Option<usize>,
+        video: &VideoSettings,
+        audio: Option<&AudioSettings>,
+        incompatible_streams: IncompatibleStreamPolicy,
+        pass: EncoderPass,
+        progress: &mut ProgressReporter,
+        cancellation: &CancellationToken,
+    ) -> Result<Vec<usize>> {
+        progress.set_stage(ProgressStage::Probing);
+        Self::ensure_input_exists(input_file)?;
+        Self::ensure_distinct_output(input_file, output_file)?;
+        let mut ictx = format::input(input_file)
+            .with_context(|| format!("Failed to open input file: {}", input_file))?;
+        let mut partial_output = PartialOutput::new(output_file);
+        let mut octx = format::output(output_file)
+            .with_context(|| format!("Failed to create output file: {}", output_file))?;
+        partial_output.arm();
+
+        format::context::input::dump(&ictx, 0, Some(input_file));
+
+        let best_video_stream_index = ictx
+            .streams()
+            .best(media::Type::Video)
+            .map(|stream| stream.index());
+        let (total_seconds, total_frames, input_size) = Self::media_totals(&ictx, input_file);
+        progress.set_totals(total_seconds, total_frames, input_size);
+        let mut stream_mapping: Vec<isize> = vec![0; ictx.nb_streams() as _];
+        let mut ist_time_bases = vec![Rational(0, 0); ictx.nb_streams() as _];
+        let mut ost_time_bases = vec![Rational(0, 0); ictx.nb_streams() as _];
+        let mut transcoders = HashMap::new();
+        let mut audio_transcoders = HashMap::new();
+        let mut dropped_streams = Vec::new();
+        let mut ost_index = 0;
+        for (ist_index, ist) in ictx.streams().enumerate() {
+            let ist_medium = ist.parameters().medium();
+            if ist_medium != media::Type::Audio
+                && ist_medium != media::Type::Video
+                && ist_medium != media::Type::Subtitle
+            {
+                stream_mapping[ist_index] = -1;
+                continue;
+            }
+            if ist_medium != media::Type::Video && matches!(pass, EncoderPass::Analysis { .. }) {
+                // The analysis pass only needs the video
+                stream_mapping[ist_index] = -1;
+                continue;
+            }
+            stream_mapping[ist_index] = ost_index;
+            ist_time_bases[ist_index] = ist.time_base();
+            if ist_medium == media::Type::Video {
+                // Initialize transcoder for video stream.
+                transcoders.insert(
+                    ist_index,
+                    Transcoder::new(
+                        &ist,
+                        &mut octx,
+                        ost_index as _,
+                        Some(ist_index) == best_video_stream_index,
+                        target_width,
+                        target_height,
+                        bitrate,
+                        max_bitrate,
+                        video,
+                        pass,
+                    )
+                    .with_context(|| format!("Failed to create transcoder for stream {}", ist_index))?,
+                );
+            } else if let (media::Type::Audio, Some(settings)) = (ist_medium, audio) {
+                let codec = settings.encoder_for(&octx)?;
+                audio_transcoders.insert(
+                    ist_index,
+                    AudioTranscoder::new(&ist, &mut octx, ost_index as _, codec, settings, false)
+                        .with_context(|| format!("Failed to create audio transcoder for stream {}", ist_index))?,
+                );
+            } else if !Self::can_copy_stream(&octx.format(), &ist) {
+                match (incompatible_streams, ist_medium) {
+                    (IncompatibleStreamPolicy::Fail, _) => {
+                        bail!(PluginError::new(ErrorCode::UnsupportedCodec, Self::incompatible_stream_message(&octx.format(), &ist)));
+                    }
+                    (IncompatibleStreamPolicy::Transcode, media::Type::Audio) => {
+                        let settings = AudioSettings::default();
+                        let codec = match settings.encoder_for(&octx) {
+                            Ok(codec) => codec,
+                            Err(e) => {
+                                eprintln!(
+                                    "Warning: dropping stream: {}, and it cannot be transcoded: {:#}",
+                                    Self::incompatible_stream_message(&octx.format(), &ist),
+                                    e
+                                );
+                                stream_mapping[ist_index] = -1;
+                                dropped_streams.push(ist_index);
+                                continue;
+                            }
+                        };
+                        audio_transcoders.insert(
+                            ist_index,
+                            AudioTranscoder::new(&ist, &mut octx, ost_index as _, codec, &settings, false)
+                                .with_context(|| format!("Failed to create audio transcoder for stream {}", ist_index))?,
+                        );
+                    }
+                    _ => {
+                        eprintln!("Warning: dropping stream: {}", Self::incompatible_stream_message(&octx.format(), &ist));
+                        stream_mapping[ist_index] = -1;
+                        dropped_streams.push(ist_index);
+                        continue;
+                    }
+                }
+            } else {
+                // Set up for stream copy for non-video stream.
+                Self::add_copy_stream(&ist, &mut octx)?;
+            }
+            ost_index += 1;
+        }
+
+        octx.set_metadata(ictx.metadata().to_owned());
+        format::context::output::dump(&octx, 0, Some(output_file));
+        octx.write_header()
+            .context("Failed to write output file header")?;
+
+        for (ost_index, _) in octx.streams().enumerate() {
+            ost_time_bases[ost_index] = octx.stream(ost_index as _)
+                .with_context(|| format!("Failed to get stream {}", ost_index))?
+                .time_base();
+        }
+        progress.set_stage(ProgressStage::Encoding);
+        for (stream, mut packet) in ictx.packets() {
+            if cancellation.is_cancelled() {
+                bail!(Cancelled);
+            }
+            progress.packet_read(packet.position());
+            let ist_index = stream.index();
+            let ost_index = stream_mapping[ist_index];
+            if ost_index < 0 {
+                continue;
+            }
+            let ost_time_base = ost_time_bases[ost_index as usize];
+            if let Some(transcoder) = transcoders.get_mut(&ist_index) {
+                transcoder.send_packet_to_decoder(&packet)?;
+                transcoder.receive_and_process_decoded_frames(&mut octx, ost_time_base, progress)?;
+            } else if let Some(audio_transcoder) = audio_transcoders.get_mut(&ist_index) {
+                audio_transcoder.send_packet_to_decoder(&packet)?;
+                audio_transcoder.receive_and_process_decoded_frames(&mut octx, ost_time_base, progress)?;
+            } else {
+                // Do stream copy on the remaining streams.
+                Self::write_copied_packet(&mut packet, ist_time_bases[ist_index], ost_time_base, ost_index as _, &mut octx)?;
+            }
+        }
+
+        // Flush encoders and decoders.
+        progress.set_stage(ProgressStage::Flushing);
+        for transcoder in transcoders.values_mut() {
+            let ost_time_base = ost_time_bases[transcoder.ost_index];
+            transcoder.send_eof_to_decoder()?;
+            transcoder.receive_and_process_decoded_frames(&mut octx, ost_time_base, progress)?;
+            transcoder.send_eof_to_encoder()?;
+            transcoder.receive_and_process_encoded_packets(&mut octx, ost_time_base)?;
+        }
+        for audio_transcoder in audio_transcoders.values_mut() {
+            let ost_time_base = ost_time_bases[audio_transcoder.ost_index()];
+            audio_transcoder.send_eof_to_decoder()?;
+            audio_transcoder.receive_and_process_decoded_frames(&mut octx, ost_time_base, progress)?;
+            audio_transcoder.send_eof_to_encoder(&mut octx, ost_time_base)?;
+            audio_transcoder.receive_and_process_encoded_packets(&mut octx, ost_time_base)?;
+        }
+
+        progress.set_stage(ProgressStage::Finalizing);
+        octx.write_trailer()
+            .context("Failed to write output file trailer")?;
+        partial_output.keep();
+        
+        Ok(dropped_streams)
+    }
+
+    /// Copy every stream into another container without decoding
+    /// 
+    /// # Arguments
+    /// 
+    /// * `input_path` - Path to the input media file
+    /// * `output_path` - Path to save the remuxed file; its extension picks the container
+    /// * `inform_about_progress` - Receives throttled progress reports; an error return cancels the job
+    /// * `cancellation` - Token checked between packets
+    /// 
+    /// # Returns
+    /// 
+    /// Result indicating success or error. Fails with [`ErrorCode::UnsupportedCodec`] before
+    /// writing anything if a stream's codec cannot be stored in the output container.
+    /// No partial output is left behind on failure.
+    pub fn remux(
+        &self,
+        input_path: &String,
+        output_path: &String,
+        inform_about_progress: ProgressCallback,
+        cancellation: CancellationToken,
+    ) -> Result<()> {
+        let _slot = self.job_slots.acquire(&cancellation)?;
+
+        let input_file = Self::file_path(input_path);
+        let output_file = Self::file_path(output_path);
+
+        let mut progress = ProgressReporter::new(inform_about_progress, cancellation.clone(), &output_file);
+
+        let result = Self::remux_streams(&input_file, &output_file, &mut progress, &cancellation);
+
+        if let Err(e) = result {
+            if e.downcast_ref::<Cancelled>().is_none() {
+                eprintln!("Error during remux: {:?}", e);
+            }
+            return Err(e);
+        }
+
+        Ok(())
+    }
+
+    /// Copy every audio, video and subtitle stream of `input_file` into `output_file`
+    fn remux_streams(
+        input_file: &str,
+        output_file: &str,
+        progress: &mut ProgressReporter,
+        cancellation: &CancellationToken,
+    ) -> Result<()> {
+        progress.set_stage(ProgressStage::Probing);
+        Self::ensure_input_exists(input_file)?;
+        Self::ensure_distinct_output(input_file, output_file)?;
+        let mut ictx = format::input(input_file)
+            .with_context(|| format!("Failed to open input file: {}", input_file))?;
+        let mut partial_output = PartialOutput::new(output_file);
+        let mut octx = format::output(output_file)
+            .with_context(|| format!("Failed to create output file: {}", output_file))?;
+        partial_output.arm();
+
+        let (total_seconds, total_frames, input_size) = Self::media_totals(&ictx, input_file);
+        progress.set_totals(total_seconds, total_frames, input_size);
+        let progress_stream_index = ictx
+            .streams()
+            .best(media::Type::Video)
+            .or_else(|| ictx.streams().best(media::Type::Audio))
+            .map(|stream| stream.index());
+
+        let stream_mapping = Self::add_copy_streams(&ictx, &mut octx)?;
+        let ist_time_bases = ictx.streams().map(|ist| ist.time_base()).collect::<Vec<_>>();
+        let input_start_times = ictx
+            .streams()
+            .map(|ist| if ist.start_time() == ffmpeg_next::ffi::AV_NOPTS_VALUE { 0 } else { ist.start_time() })
+            .collect::<Vec<_>>();
+
+        octx.set_metadata(ictx.metadata().to_owned());
+        octx.write_header()
+            .context("Failed to write output file header")?;
+
+        let ost_time_bases = octx.streams().map(|ost| ost.time_base()).collect::<Vec<_>>();
+        progress.set_stage(ProgressStage::Encoding);
+        for (stream, mut packet) in ictx.packets() {
+            if cancellation.is_cancelled() {
+                bail!(Cancelled);
+            }
+            progress.packet_read(packet.position());
+            let ist_index = stream.index();
+            let ost_index = stream_mapping[ist_index];
+            if ost_index < 0 {
+                continue;
+            }
+            if Some(ist_index) == progress_stream_index {
+                if let Some(timestamp) = packet.pts().or(packet.dts()) {
+                    progress.packet_copied((timestamp - input_start_times[ist_index]) as f64 * f64::from(ist_time_bases[ist_index]));
+                }
+            }
+            Self::write_copied_packet(&mut packet, ist_time_bases[ist_index], ost_time_bases[ost_index as usize], ost_index as _, &mut octx)?;
+        }
+
+        progress.set_stage(ProgressStage::Finalizing);
+        octx.write_trailer()
+            .context("Failed to write output file trailer")?;
+        partial_output.keep();
+
+        Ok(())
+    }
+
+    /// Write one audio stream of a media file to a standalone audio file
+    /// 
+    /// # Arguments
+    /// 
+    /// * `input_path` - Path to the input media file
+    /// * `output_path` - Path to save the audio; its extension picks the container (m4a, aac, mka, ogg, wav, ...)
+    /// * `stream_index` - Index of the audio stream to extract, or None for the best one
+    /// * `audio` - How to transcode the stream; None copies it when the container allows
+    /// * `inform_about_progress` - Receives throttled progress reports; an error return cancels the job
+    /// * `cancellation` - Token checked between packets
+    /// 
+    /// # Returns
+    /// 
+    /// Result indicating success or error. Without `audio` settings, the stream is copied when
+    /// the container can hold its codec and transcoded otherwise. No partial output is left
+    /// behind on failure.
+    pub fn extract_audio(
+        &self,
+        input_path: &String,
+        output_path: &String,
+        stream_index: Option<usize>,
+        audio: Option<AudioSettings>,
+        inform_about_progress: ProgressCallback,
+        cancellation: CancellationToken,
+    ) -> Result<()> {
+        let _slot = self.job_slots.acquire(&cancellation)?;
+
+        let input_file = Self::file_path(input_path);
+        let output_file = Self::file_path(output_path);
+
+        let mut progress = ProgressReporter::new(inform_about_progress, cancellation.clone(), &output_file);
+
+        let result: Result<()> = (|| {
+            progress.set_stage(ProgressStage::Probing);
+            Self::ensure_input_exists(&input_file)?;
+            Self::ensure_distinct_output(&input_file, &output_file)?;
+            let mut ictx = format::input(&input_file)
+                .with_context(|| format!("Failed to open input file: {}", input_file))?;
+            let mut partial_output = PartialOutput::new(&output_file);
+            let mut octx = format::output(&output_file)
+                .with_context(|| format!("Failed to create output file: {}", output_file))?;
+            partial_output.arm();
+
+            let ist = match stream_index {
+                Some(stream_index) => ictx
+                    .stream(stream_index)
+                    .filter(|stream| stream.parameters().medium() == media::Type::Audio)
+                    .ok_or_else(|| PluginError::new(
+                        ErrorCode::InvalidArgument,
+                        format!("Stream {} is not an audio stream", stream_index),
+                    ))?,
+                None => ictx
+                    .streams()
+                    .best(media::Type::Audio)
+                    .ok_or_else(|| PluginError::new(ErrorCode::UnsupportedFormat, "Input has no audio stream"))?,
+            };
+            let ist_index = ist.index();
+            let ist_time_base = ist.time_base();
+            let input_start_time = if ist.start_time() == ffmpeg_next::ffi::AV_NOPTS_VALUE { 0 } else { ist.start_time() };
+            let total_seconds = if ist.duration() > 0 {
+                ist.duration() as f64 * f64::from(ist_time_base)
+            } else if ictx.duration() > 0 {
+                ictx.duration() as f64 * f64::from(rescale::TIME_BASE)
+            } else {
+                0.0
+            };
+            let input_size = std::fs::metadata(&input_file)
+                .map(|metadata| metadata.len())
+                .unwrap_or(0);
+            progress.set_totals(total_seconds, 0, input_size);
+
+            let mut transcoder = if audio.is_none() && audio::can_copy_audio(&octx.format(), ist.parameters().id()) {
+                Self::add_copy_stream(&ist, &mut octx)?;
+                None
+            } else {
+                let settings = audio.unwrap_or_default();
+                let codec = settings.encoder_for(&octx)?;
+                Some(
+                    AudioTranscoder::new(&ist, &mut octx, 0, codec, &settings, true)
+                        .with_context(|| format!("Failed to create audio transcoder for stream {}", ist_index))?,
+                )
+            };
+
+            octx.set_metadata(ictx.metadata().to_owned());
+            octx.write_header()
+                .context("Failed to write output file header")?;
+            let ost_time_base = octx.stream(0)
+                .context("Failed to get output stream")?
+                .time_base();
+
+            progress.set_stage(ProgressStage::Encoding);
+            for (stream, mut packet) in ictx.packets() {
+                if cancellation.is_cancelled() {
+                    bail!(Cancelled);
+                }
+                progress.packet_read(packet.position());
+                if stream.index() != ist_index {
+                    continue;
+                }
+                match transcoder.as_mut() {
+                    Some(transcoder) => {
+                        transcoder.send_packet_to_decoder(&packet)?;
+                        transcoder.receive_and_process_decoded_frames(&mut octx, ost_time_base, &mut progress)?;
+                    }
+                    None => {
+                        if let Some(timestamp) = packet.pts() {
+                            progress.packet_copied((timestamp - input_start_time) as f64 * f64::from(ist_time_base));
+                        }
+                        Self::write_copied_packet(&mut packet, ist_time_base, ost_time_base, 0, &mut octx)?;
+                    }
+                }
+            }
+
+            if let Some(transcoder) = transcoder.as_mut() {
+                progress.set_stage(ProgressStage::Flushing);
+                transcoder.send_eof_to_decoder()?;
+                transcoder.receive_and_process_decoded_frames(&mut octx, ost_time_base, &mut progress)?;
+                transcoder.send_eof_to_encoder(&mut octx, ost_time_base)?;
+                transcoder.receive_and_process_encoded_packets(&mut octx, ost_time_base)?;
+            }
+
+            progress.set_stage(ProgressStage::Finalizing);
+            octx.write_trailer()
+                .context("Failed to write output file trailer")?;
+            partial_output.keep();
+
+            Ok(())
+        })();
+
+        if let Err(e) = result {
+            if e.downcast_ref::<Cancelled>().is_none() {
+                eprintln!("Error during audio extraction: {:?}", e);
+            }
+            return Err(e);
         }
-        
-        // Safe conversion from u64 to usize
-        bitrate.try_into()
-            .with_context(|| format!("Bitrate {} cannot be converted to usize on this platform", bitrate))
+
+        Ok(())
     }
 
-    /// Re-encode a video file to a lower resolution
+    /// Cut a media file down to a time range
     /// 
     /// # Arguments
     /// 
-    /// * `input_path` - Path to the input video file
-    /// * `output_path` - Path to save the re-encoded video
-    /// * `target_width` - Target width for the output video
-    /// * `target_height` - Target height for the output video
-    /// * `bitrate` - Target bitrate in bits per second (optional, defaults to 1Mbps)
+    /// * `input_path` - Path to the input media file
+    /// * `output_path` - Path to save the trimmed file
+    /// * `start_seconds` - Start of the kept range, from the start of the media
+    /// * `end_seconds` - End of the kept range, or None to keep everything after the start
+    /// * `mode` - [`TrimMode::Copy`] cuts on the keyframe before the start without re-encoding;
+    ///   [`TrimMode::Accurate`] re-encodes video so the cut lands on the exact frame
+    /// * `inform_about_progress` - Receives throttled progress reports; an error return cancels the job
+    /// * `cancellation` - Token checked between packets
     /// 
     /// # Returns
     /// 
-    /// Result indicating success or error
-    pub fn reencode_video(
+    /// Result indicating success or error. The output starts at zero with every stream
+    /// shifted by the same amount. No partial output is left behind on failure.
+    pub fn trim(
         &self,
         input_path: &String,
         output_path: &String,
-        target_width: u32,
-        target_height: u32,
-        bitrate: Option<u64>,
-        inform_about_progress: Arc<Box<dyn Fn(f64) -> Result<(), anyhow::Error>>>,
-    ) -> Result<(), anyhow::Error> {
-        // Validate bitrate early
-        let validated_bitrate = Self::validate_bitrate(bitrate)
-            .context("Invalid bitrate specified")?;
-        
-        let input_file = if input_path.starts_with("file://") {
-            input_path.replace("file://", "")
-        } else {
-            input_path.to_string()
-        };
-        let output_file = if output_path.starts_with("file://") {
-            output_path.replace("file://", "")
-        } else {
-            output_path.to_string()
-        };
-    
-        ffmpeg_next::init()?;
+        start_seconds: f64,
+        end_seconds: Option<f64>,
+        mode: TrimMode,
+        inform_about_progress: ProgressCallback,
+        cancellation: CancellationToken,
+    ) -> Result<()> {
+        TrimWindow::check_range(start_seconds, end_seconds)?;
 
-        let inform_about_progress = inform_about_progress.clone();
+        let _slot = self.job_slots.acquire(&cancellation)?;
 
-        // Doing this allows us some better error handling down the road
-        let result: Result<(), anyhow::Error> = (|| {
-            let mut x264_opts = Dictionary::new();
-            x264_opts.set("preset", "medium");
+        let input_file = Self::file_path(input_path);
+        let output_file = Self::file_path(output_path);
 
+        let mut progress = ProgressReporter::new(inform_about_progress, cancellation.clone(), &output_file);
+
+        let result: Result<()> = (|| {
+            progress.set_stage(ProgressStage::Probing);
+            Self::ensure_input_exists(&input_file)?;
+            Self::ensure_distinct_output(&input_file, &output_file)?;
             let mut ictx = format::input(&input_file)
                 .with_context(|| format!("Failed to open input file: {}", input_file))?;
+            let mut partial_output = PartialOutput::new(&output_file);
             let mut octx = format::output(&output_file)
                 .with_context(|| format!("Failed to create output file: {}", output_file))?;
+            partial_output.arm();
 
-            format::context::input::dump(&ictx, 0, Some(&input_file));
+            let media_start = match unsafe { (*ictx.as_ptr()).start_time } {
+                ffmpeg_next::ffi::AV_NOPTS_VALUE => 0,
+                start_time => start_time,
+            };
+            let window = TrimWindow::new(media_start, start_seconds, end_seconds);
+            let (media_seconds, _, _) = Self::media_totals(&ictx, &input_file);
+            progress.set_totals(TrimWindow::output_seconds(start_seconds, end_seconds, media_seconds), 0, 0);
 
-            let best_video_stream_index = ictx
-                .streams()
-                .best(media::Type::Video)
-                .map(|stream| stream.index());
-            let mut stream_mapping: Vec<isize> = vec![0; ictx.nb_streams() as _];
-            let mut ist_time_bases = vec![Rational(0, 0); ictx.nb_streams() as _];
-            let mut ost_time_bases = vec![Rational(0, 0); ictx.nb_streams() as _];
-            let mut transcoders = HashMap::new();
-            let mut ost_index = 0;
-            for (ist_index, ist) in ictx.streams().enumerate() {
-                let ist_medium = ist.parameters().medium();
-                if ist_medium != media::Type::Audio
-                    && ist_medium != media::Type::Video
-                    && ist_medium != media::Type::Subtitle
-                {
-                    stream_mapping[ist_index] = -1;
+            // Land on the keyframe at or before the start; both modes read forward from there
+            if start_seconds > 0.0 {
+                ictx.seek(window.start(), ..window.start())
+                    .with_context(|| format!("Failed to seek to {}s", start_seconds))?;
+            }
+
+            match mode {
+                TrimMode::Copy => Self::trim_with_stream_copy(&mut ictx, &mut octx, window, &mut progress, &cancellation)?,
+                TrimMode::Accurate => Self::trim_with_reencode(&mut ictx, &mut octx, window, &mut progress, &cancellation)?,
+            }
+
+            progress.set_stage(ProgressStage::Finalizing);
+            octx.write_trailer()
+                .context("Failed to write output file trailer")?;
+            partial_output.keep();
+
+            Ok(())
+        })();
+
+        if let Err(e) = result {
+            if e.downcast_ref::<Cancelled>().is_none() {
+                eprintln!("Error during trim: {:?}", e);
+            }
+            return Err(e);
+        }
+
+        Ok(())
+    }
+
+    /// Copy packets from the keyframe before the window start to the window end
+    ///
+    /// The output is rebased on the first keyframe of the reference stream (video, or audio
+    /// for audio-only files), so it may start slightly before the requested time. Packets of
+    /// other streams read before that keyframe are held back until the offset is known.
+    fn trim_with_stream_copy(
+        ictx: &mut format::context::Input,
+        octx: &mut format::context::Output,
+        window: TrimWindow,
+        progress: &mut ProgressReporter,
+        cancellation: &CancellationToken,
+    ) -> Result<()> {
+        let reference_index = ictx
+            .streams()
+            .best(media::Type::Video)
+            .or_else(|| ictx.streams().best(media::Type::Audio))
+            .map(|stream| stream.index())
+            .ok_or_else(|| PluginError::new(ErrorCode::UnsupportedFormat, "Input has no audio or video stream to trim"))?;
+
+        let stream_mapping = Self::add_copy_streams(ictx, octx)?;
+        let ist_time_bases = ictx.streams().map(|ist| ist.time_base()).collect::<Vec<_>>();
+        octx.set_metadata(ictx.metadata().to_owned());
+        octx.write_header()
+            .context("Failed to write output file header")?;
+        let ost_time_bases = octx.streams().map(|ost| ost.time_base()).collect::<Vec<_>>();
+
+        // Container clock timestamp everything is shifted by, known once the reference keyframe is read
+        let mut rebase_offset: Option<i64> = None;
+        let mut pending: Vec<Packet> = Vec::new();
+        let mut finished = stream_mapping.iter().map(|ost_index| *ost_index < 0).collect::<Vec<_>>();
+
+        progress.set_stage(ProgressStage::Encoding);
+        for (stream, packet) in ictx.packets() {
+            if cancellation.is_cancelled() {
+                bail!(Cancelled);
+            }
+            progress.packet_read(packet.position());
+            let ist_index = stream.index();
+            if finished[ist_index] {
+                continue;
+            }
+            // Decode order: once a packet is past the end, every later one of the stream is too
+            if let (Some(end), Some(timestamp)) = (window.end_in(ist_time_bases[ist_index]), packet.dts().or(packet.pts())) {
+                if timestamp >= end {
+                    finished[ist_index] = true;
+                    if finished.iter().all(|finished| *finished) {
+                        break;
+                    }
                     continue;
                 }
-                stream_mapping[ist_index] = ost_index;
-                ist_time_bases[ist_index] = ist.time_base();
-                if ist_medium == media::Type::Video {
-                    // Initialize transcoder for video stream.
-                    transcoders.insert(
-                        ist_index,
-                        Transcoder::new(
-                            &ist,
-                            &mut octx,
-                            ost_index as _,
-                            x264_opts.clone(),
-                            Some(ist_index) == best_video_stream_index,
-                            target_width,
-                            target_height,
-                            validated_bitrate,
-                            inform_about_progress.clone(),
-                        )
-                        .with_context(|| format!("Failed to create transcoder for stream {}", ist_index))?,
-                    );
-                } else {
-                    // Set up for stream copy for non-video stream.
-                    let mut ost = octx.add_stream(encoder::find(codec::Id::None))
-                        .with_context(|| format!("Failed to add stream for copying stream {}", ist_index))?;
-                    ost.set_parameters(ist.parameters());
-                    // We need to set codec_tag to 0 lest we run into incompatible codec tag
-                    // issues when muxing into a different container format. Unfortunately
-                    // there's no high level API to do this (yet).
-                    unsafe {
-                        (*ost.parameters().as_mut_ptr()).codec_tag = 0;
+            }
+
+            let offset = match rebase_offset {
+                Some(offset) => offset,
+                None if ist_index == reference_index && packet.is_key() => {
+                    let timestamp = packet.dts().or(packet.pts()).unwrap_or(0);
+                    let reference_offset = timestamp.rescale(ist_time_bases[ist_index], rescale::TIME_BASE);
+                    rebase_offset = Some(reference_offset);
+                    for pending_packet in pending.drain(..) {
+                        let pending_index = pending_packet.stream();
+                        Self::write_rebased_packet(
+                            pending_packet,
+                            reference_offset,
+                            ist_time_bases[pending_index],
+                            ost_time_bases[stream_mapping[pending_index] as usize],
+                            stream_mapping[pending_index] as _,
+                            octx,
+                        )?;
                     }
+                    reference_offset
+                }
+                None => {
+                    // Non-key packets of the reference stream cannot be decoded without what came before
+                    if ist_index != reference_index {
+                        pending.push(packet);
+                    }
+                    continue;
+                }
+            };
+
+            if ist_index == reference_index {
+                if let Some(pts) = packet.pts() {
+                    let reference = offset.rescale(rescale::TIME_BASE, ist_time_bases[ist_index]);
+                    progress.packet_copied((pts - reference) as f64 * f64::from(ist_time_bases[ist_index]));
                 }
-                ost_index += 1;
             }
+            Self::write_rebased_packet(
+                packet,
+                offset,
+                ist_time_bases[ist_index],
+                ost_time_bases[stream_mapping[ist_index] as usize],
+                stream_mapping[ist_index] as _,
+                octx,
+            )?;
+        }
+        Ok(())
+    }
 
-            octx.set_metadata(ictx.metadata().to_owned());
-            format::context::output::dump(&octx, 0, Some(&output_file));
-            octx.write_header()
-                .context("Failed to write output file header")?;
+    /// Re-encode video frames inside the window and copy the other streams' packets inside it
+    ///
+    /// Everything is rebased on the window start, so the first video frame is at zero.
+    fn trim_with_reencode(
+        ictx: &mut format::context::Input,
+        octx: &mut format::context::Output,
+        window: TrimWindow,
+        progress: &mut ProgressReporter,
+        cancellation: &CancellationToken,
+    ) -> Result<()> {
+        let best_video_stream_index = ictx
+            .streams()
+            .best(media::Type::Video)
+            .map(|stream| stream.index());
+        let progress_stream_index = best_video_stream_index.or_else(|| {
+            ictx.streams()
+                .best(media::Type::Audio)
+                .map(|stream| stream.index())
+        });
 
-            for (ost_index, _) in octx.streams().enumerate() {
-                ost_time_bases[ost_index] = octx.stream(ost_index as _)
-                    .with_context(|| format!("Failed to get stream {}", ost_index))?
-                    .time_base();
+        let mut stream_mapping: Vec<isize> = vec![-1; ictx.nb_streams() as _];
+        let ist_time_bases = ictx.streams().map(|ist| ist.time_base()).collect::<Vec<_>>();
+        let mut transcoders = HashMap::new();
+        let mut ost_index = 0;
+        for (ist_index, ist) in ictx.streams().enumerate() {
+            let ist_medium = ist.parameters().medium();
+            if ist_medium != media::Type::Audio
+                && ist_medium != media::Type::Video
+                && ist_medium != media::Type::Subtitle
+            {
+                continue;
             }
-            for (stream, mut packet) in ictx.packets() {
-                let ist_index = stream.index();
-                let ost_index = stream_mapping[ist_index];
-                if ost_index < 0 {
+            stream_mapping[ist_index] = ost_index;
+            if ist_medium == media::Type::Video {
+                // Keep the source size and, within the allowed range, its bitrate
+                let (width, height, bit_rate) = unsafe {
+                    let parameters = &*ist.parameters().as_ptr();
+                    (parameters.width as u32, parameters.height as u32, parameters.bit_rate)
+                };
+                // The transcoder turns frames upright, and takes its size in that orientation
+                let (width, height) = video::display_size(width, height, probe::rotation(&ist));
+                let bitrate = if bit_rate > 0 {
+                    (bit_rate as u64).clamp(MIN_BITRATE, MAX_BITRATE)
+                } else {
+                    DEFAULT_BITRATE
+                };
+                let mut transcoder = Transcoder::new(
+                    &ist,
+                    octx,
+                    ost_index as _,
+                    Some(ist_index) == best_video_stream_index,
+                    width,
+                    height,
+                    Self::validate_bitrate(Some(bitrate))?,
+                    None,
+                    &VideoSettings::default(),
+                    EncoderPass::Single,
+                )
+                .with_context(|| format!("Failed to create transcoder for stream {}", ist_index))?;
+                transcoder.set_trim(window.start_in(ist.time_base()), window.end_in(ist.time_base()));
+                transcoders.insert(ist_index, transcoder);
+            } else {
+                Self::ensure_muxer_supports(octx, &ist)?;
+                Self::add_copy_stream(&ist, octx)?;
+            }
+            ost_index += 1;
+        }
+        if ost_index == 0 {
+            bail!(PluginError::new(ErrorCode::UnsupportedFormat, "Input has no audio, video or subtitle stream to trim"));
+        }
+
+        octx.set_metadata(ictx.metadata().to_owned());
+        octx.write_header()
+            .context("Failed to write output file header")?;
+        let ost_time_bases = octx.streams().map(|ost| ost.time_base()).collect::<Vec<_>>();
+
+        let mut finished = stream_mapping.iter().map(|ost_index| *ost_index < 0).collect::<Vec<_>>();
+        progress.set_stage(ProgressStage::Encoding);
+        for (stream, packet) in ictx.packets() {
+            if cancellation.is_cancelled() {
+                bail!(Cancelled);
+            }
+            progress.packet_read(packet.position());
+            let ist_index = stream.index();
+            if finished[ist_index] {
+                continue;
+            }
+            let ist_time_base = ist_time_bases[ist_index];
+            if let (Some(end), Some(timestamp)) = (window.end_in(ist_time_base), packet.dts().or(packet.pts())) {
+                if timestamp >= end {
+                    finished[ist_index] = true;
+                    if finished.iter().all(|finished| *finished) {
+                        break;
+                    }
                     continue;
                 }
-                let ost_time_base = ost_time_bases[ost_index as usize];
-                match transcoders.get_mut(&ist_index) {
-                    Some(transcoder) => {
-                        transcoder.send_packet_to_decoder(&packet)?;
-                        transcoder.receive_and_process_decoded_frames(&mut octx, ost_time_base)?;
+            }
+
+            let ost_index = stream_mapping[ist_index] as usize;
+            match transcoders.get_mut(&ist_index) {
+                Some(transcoder) => {
+                    transcoder.send_packet_to_decoder(&packet)?;
+                    transcoder.receive_and_process_decoded_frames(octx, ost_time_bases[ost_index], progress)?;
+                }
+                None => {
+                    // Packets of copied streams cannot be split, so keep those that start inside the window
+                    if packet.pts().map_or(false, |pts| pts < window.start_in(ist_time_base)) {
+                        continue;
                     }
-                    None => {
-                        // Do stream copy on non-video streams.
-                        packet.rescale_ts(ist_time_bases[ist_index], ost_time_base);
-                        packet.set_position(-1);
-                        packet.set_stream(ost_index as _);
-                        packet.write_interleaved(&mut octx)
-                            .context("Failed to write packet to output")?;
+                    if Some(ist_index) == progress_stream_index {
+                        if let Some(pts) = packet.pts() {
+                            progress.packet_copied((pts - window.start_in(ist_time_base)) as f64 * f64::from(ist_time_base));
+                        }
                     }
+                    Self::write_rebased_packet(packet, window.start(), ist_time_base, ost_time_bases[ost_index], ost_index, octx)?;
                 }
             }
+        }
 
-            // Flush encoders and decoders.
-            for (ost_index, transcoder) in transcoders.iter_mut() {
-                let ost_time_base = ost_time_bases[*ost_index];
-                transcoder.send_eof_to_decoder()?;
-                transcoder.receive_and_process_decoded_frames(&mut octx, ost_time_base)?;
-                transcoder.send_eof_to_encoder()?;
-                transcoder.receive_and_process_encoded_packets(&mut octx, ost_time_base)?;
+        progress.set_stage(ProgressStage::Flushing);
+        for transcoder in transcoders.values_mut() {
+            let ost_time_base = ost_time_bases[transcoder.ost_index];
+            transcoder.send_eof_to_decoder()?;
+            transcoder.receive_and_process_decoded_frames(octx, ost_time_base, progress)?;
+            transcoder.send_eof_to_encoder()?;
+            transcoder.receive_and_process_encoded_packets(octx, ost_time_base)?;
+        }
+        Ok(())
+    }
+
+    /// Add a copy of every audio, video and subtitle stream to the output
+    ///
+    /// Returns the output index of each input stream, or -1 for streams that are left out.
+    fn add_copy_streams(ictx: &format::context::Input, octx: &mut format::context::Output) -> Result<Vec<isize>> {
+        let mut stream_mapping: Vec<isize> = vec![-1; ictx.nb_streams() as _];
+        let mut ost_index = 0;
+        for (ist_index, ist) in ictx.streams().enumerate() {
+            let ist_medium = ist.parameters().medium();
+            if ist_medium != media::Type::Audio
+                && ist_medium != media::Type::Video
+                && ist_medium != media::Type::Subtitle
+            {
+                continue;
             }
+            Self::ensure_muxer_supports(octx, &ist)?;
+            stream_mapping[ist_index] = ost_index;
+            Self::add_copy_stream(&ist, octx)?;
+            ost_index += 1;
+        }
+        if ost_index == 0 {
+            bail!(PluginError::new(ErrorCode::UnsupportedFormat, "Input has no audio, video or subtitle stream to copy"));
+        }
+        Ok(stream_mapping)
+    }
 
-            octx.write_trailer()
-                .context("Failed to write output file trailer")?;
-            
-            Ok(())
-        })();
+    /// Shift a copied packet by `offset` (container clock) and write it
+    ///
+    /// Packets that would start before zero are dropped.
+    fn write_rebased_packet(
+        mut packet: Packet,
+        offset: i64,
+        ist_time_base: Rational,
+        ost_time_base: Rational,
+        ost_index: usize,
+        octx: &mut format::context::Output,
+    ) -> Result<()> {
+        let offset = offset.rescale(rescale::TIME_BASE, ist_time_base);
+        if packet.pts().or(packet.dts()).map_or(false, |timestamp| timestamp < offset) {
+            return Ok(());
+        }
+        packet.set_pts(packet.pts().map(|pts| pts - offset));
+        packet.set_dts(packet.dts().map(|dts| dts - offset));
+        Self::write_copied_packet(&mut packet, ist_time_base, ost_time_base, ost_index, octx)
+    }
 
-        // Handle the result - you can log errors, call a callback, etc.
-        if let Err(e) = result {
-            eprintln!("Error during video encoding: {:?}", e);
-            // You could also call a callback to inform about the error
-            // if let Err(callback_err) = inform_about_progress(0.0, format!("Error: {}", e)) {
-            //     eprintln!("Failed to report error: {:?}", callback_err);
-            // }
-            return Err(e);
+    /// Whether the output container can store the stream as is
+    ///
+    /// Containers that do not declare their codecs are given the benefit of the doubt;
+    /// the muxer then rejects the stream when the header is written.
+    fn can_copy_stream(muxer: &format::Output, ist: &format::stream::Stream) -> bool {
+        Self::muxer_accepts(muxer, ist.parameters().medium(), ist.parameters().id())
+    }
+
+    /// Whether `muxer` can store a stream of `codec_id` without re-encoding
+    fn muxer_accepts(muxer: &format::Output, medium: media::Type, codec_id: codec::Id) -> bool {
+        if medium == media::Type::Audio {
+            return audio::can_copy_audio(muxer, codec_id);
+        }
+        let supported = unsafe {
+            ffmpeg_next::ffi::avformat_query_codec(
+                muxer.as_ptr(),
+                codec_id.into(),
+                ffmpeg_next::ffi::FF_COMPLIANCE_NORMAL,
+            )
+        };
+        supported != 0
+    }
+
+    fn incompatible_stream_message(muxer: &format::Output, ist: &format::stream::Stream) -> String {
+        format!(
+            "Stream {} ({} {}) cannot be stored in {} without re-encoding",
+            ist.index(),
+            ist.parameters().id().name(),
+            match ist.parameters().medium() {
+                media::Type::Video => "video",
+                media::Type::Audio => "audio",
+                media::Type::Subtitle => "subtitles",
+                _ => "data",
+            },
+            muxer.name(),
+        )
+    }
+
+    /// Fail with a clear reason if the output container cannot store the stream as is
+    fn ensure_muxer_supports(octx: &format::context::Output, ist: &format::stream::Stream) -> Result<()> {
+        let muxer = octx.format();
+        if !Self::can_copy_stream(&muxer, ist) {
+            bail!(PluginError::new(ErrorCode::UnsupportedCodec, Self::incompatible_stream_message(&muxer, ist)));
         }
-    
         Ok(())
     }
+
+    /// The muxer `format::output` would pick for `output_file`, found without creating the file
+    fn guess_muxer(output_file: &str) -> Result<format::Output> {
+        let file_name = CString::new(output_file)
+            .map_err(|_| PluginError::new(ErrorCode::InvalidArgument, "Output path contains a NUL byte"))?;
+        let muxer = unsafe { ffmpeg_next::ffi::av_guess_format(std::ptr::null(), file_name.as_ptr(), std::ptr::null()) };
+        if muxer.is_null() {
+            bail!(PluginError::new(
+                ErrorCode::UnsupportedFormat,
+                format!("No output format matches the file name {}", output_file),
+            ));
+        }
+        Ok(unsafe { format::Output::wrap(muxer as *mut _) })
+    }
 }
 
-// Given an input file, transcode all video streams into H.264 (using libx264)
-// while copying audio and subtitle streams.
-//
-// Invocation:
-//
-//   transcode-x264 <input> <output> [<x264_opts>]
-//
-// <x264_opts> is a comma-delimited list of key=val. default is "preset=medium".
-// See https://ffmpeg.org/ffmpeg-codecs.html#libx264_002c-libx264rgb and
-// https://trac.ffmpeg.org/wiki/Encode/H.264 for available and commonly used
-// options.
-//
-// Examples:
-//
-//   transcode-x264 input.flv output.mp4
-//   transcode-x264 input.mkv output.mkv 'preset=veryslow,crf=18'
+/// What the passthrough decision needs to know about an input video stream
+#[derive(Clone, Copy, Debug)]
+struct VideoStreamFacts {
+    index: usize,
+    codec_id: codec::Id,
+    /// Coded size, before the rotation is applied
+    width: u32,
+    height: u32,
+    format: Pixel,
+    aspect_ratio: Rational,
+    rotation: i32,
+    profile: i32,
+    level: i32,
+    /// Bits per second, or an estimate from the file size; 0 if unknown
+    bit_rate: i64,
+    codec_tag: u32,
+}
+
+/// Removes an output file that an operation opened but did not finish writing
+///
+/// Created before the output context, so that it is dropped after it and the file is closed
+/// by then, and armed once the context exists: an operation that fails before that must not
+/// delete a file it never touched.
+struct PartialOutput<'a> {
+    path: &'a str,
+    armed: bool,
+}
+
+impl<'a> PartialOutput<'a> {
+    fn new(path: &'a str) -> Self {
+        Self { path, armed: false }
+    }
+
+    /// The output has been opened, and truncated; from here on a failure removes it
+    fn arm(&mut self) {
+        self.armed = true;
+    }
 
+    /// The output is complete
+    fn keep(mut self) {
+        self.armed = false;
+    }
+}
+
+impl Drop for PartialOutput<'_> {
+    fn drop(&mut self) {
+        if self.armed {
+            CapacitorFFmpegPlugin::remove_partial_output(self.path);
+        }
+    }
+}
+
+/// Which pass of the encode a [`Transcoder`] runs
+#[derive(Clone, Copy, Debug)]
+enum EncoderPass<'a> {
+    Single,
+    /// First of two passes: analyse the video and write encoder stats
+    Analysis { stats_prefix: &'a str },
+    /// Second of two passes: encode with the stats of the analysis pass
+    Final { stats_prefix: &'a str },
+}
 
+impl EncoderPass<'_> {
+    /// Stats file of one video stream; every stream needs its own
+    fn stats_file(stats_prefix: &str, ist_index: usize) -> String {
+        format!("{}-{}.log", stats_prefix, ist_index)
+    }
+}
 
 struct Transcoder {
     ost_index: usize,
     decoder: decoder::Video,
     input_time_base: Rational,
+    input_start_time: i64,
     encoder: encoder::Video,
+    rotator: Option<FrameRotator>, // None when the decoded frames are already upright
     scaler: ScalingContext,
-    frame_count: usize,
-    starting_time: Instant,
-    frames: i64,
+    layout: ResizeLayout,
+    pad_color: [u32; 4], // pixel components of the pad colour, in the encoder's pixel format
+    keyframe_times: Vec<f64>, // output seconds at which a keyframe is forced, sorted
+    next_keyframe: usize,     // index of the first forced keyframe not placed yet
     should_inform_about_progress: bool,
-    inform_about_progress: Arc<Box<dyn Fn(f64) -> Result<(), anyhow::Error>>>,
+    // Frames outside [start, end) are dropped and the rest shifted to start at zero, in the input time base
+    trim: Option<(i64, Option<i64>)>,
 }
 
 impl Transcoder {
-    fn select_output_pixel_format(codec: Codec, decoder_format: Pixel) -> Pixel {
+    fn select_output_pixel_format(settings: &VideoSettings, codec: Codec, decoder_format: Pixel) -> Pixel {
         let supported_formats: Vec<Pixel> = codec
             .video()
             .ok()
             .and_then(|video| video.formats().map(|formats| formats.collect()))
             .unwrap_or_default();
 
-        for candidate in [Pixel::YUV420P, Pixel::NV12, decoder_format] {
+        for candidate in settings.output_formats(decoder_format) {
             if supported_formats.is_empty() || supported_formats.contains(&candidate) {
                 eprintln!(
-                    "Using H.264 output pixel format {:?}; supported formats: {:?}",
+                    "Using {} output pixel format {:?}; supported formats: {:?}",
+                    settings.codec.name(),
                     candidate,
                     supported_formats
                 );
@@ -282,7 +1938,8 @@ impl Transcoder {
 
         let fallback = supported_formats.first().copied().unwrap_or(decoder_format);
         eprintln!(
-            "Using fallback H.264 output pixel format {:?}; supported formats: {:?}",
+            "Using fallback {} output pixel format {:?}; supported formats: {:?}",
+            settings.codec.name(),
             fallback,
             supported_formats
         );
@@ -293,30 +1950,82 @@ impl Transcoder {
         ist: &format::stream::Stream,
         octx: &mut format::context::Output,
         ost_index: usize,
-        x264_opts: Dictionary,
         should_inform_about_progress: bool,
         target_width: u32,
         target_height: u32,
         bitrate: usize,
-        inform_about_progress: Arc<Box<dyn Fn(f64) -> Result<(), anyhow::Error>>>,
-    ) -> Result<Self, ffmpeg_next::Error> {
+        max_bitrate: Option<usize>,
+        settings: &VideoSettings,
+        pass: EncoderPass,
+    ) -> Result<Self> {
         let global_header = octx.format().flags().contains(format::Flags::GLOBAL_HEADER);
+        let muxer = octx.format().name().to_owned();
         let decoder = ffmpeg_next::codec::context::Context::from_parameters(ist.parameters())?
             .decoder()
             .video()?;
-        let codec = encoder::find(codec::Id::H264).ok_or(ffmpeg_next::Error::EncoderNotFound)?;
-        let output_format = Self::select_output_pixel_format(codec, decoder.format());
-        let scaler = ScalingContext::get(
+        let codec = settings.codec.encoder()?;
+        let mut encoder_opts = settings.encoder_options(codec);
+        // The muxer comes from the output extension, so a .webm output needs VP9 and so on
+        let supported = unsafe {
+            ffmpeg_next::ffi::avformat_query_codec(
+                octx.format().as_ptr(),
+                settings.codec.id().into(),
+                ffmpeg_next::ffi::FF_COMPLIANCE_NORMAL,
+            )
+        };
+        if supported == 0 {
+            bail!(PluginError::new(
+                ErrorCode::UnsupportedCodec,
+                format!("{} video cannot be stored in {}", settings.codec.name(), muxer),
+            ));
+        }
+        let output_format = Self::select_output_pixel_format(settings, codec, decoder.format());
+        if let Some(profile) = settings.profile {
+            if !profile.supports(output_format) {
+                bail!(PluginError::new(
+                    ErrorCode::UnsupportedFormat,
+                    format!("H.264 {:?} profile cannot encode {:?} frames", profile, output_format),
+                ));
+            }
+        }
+
+        // Frames are turned upright before scaling, so the target size is in display orientation
+        let rotation = probe::rotation(ist);
+        let rotator = FrameRotator::new(
             decoder.format(),
             decoder.width(),
             decoder.height(),
-            output_format,
+            ist.time_base(),
+            decoder.aspect_ratio(),
+            rotation,
+        )?;
+        let (display_width, display_height) = video::display_size(decoder.width(), decoder.height(), rotation);
+        let display_aspect_ratio = if rotation % 180 == 90 {
+            decoder.aspect_ratio().invert()
+        } else {
+            decoder.aspect_ratio()
+        };
+        let layout = ResizeLayout::new(
+            display_width,
+            display_height,
+            display_aspect_ratio,
+            decoder.format(),
             target_width,
             target_height,
+            settings.resize_mode,
+        );
+        let (scaler_width, scaler_height) = layout.source_size(display_width, display_height);
+        let scaler = ScalingContext::get(
+            decoder.format(),
+            scaler_width,
+            scaler_height,
+            output_format,
+            layout.picture.width,
+            layout.picture.height,
             ScalingFlags::BILINEAR,
         )?;
+        let pad_color = video::color_components(settings.pad_color, output_format, decoder.color_space());
 
-        let frames = ist.frames();
         let mut ost = octx.add_stream(codec)?;
 
         let mut encoder =
@@ -325,38 +2034,66 @@ impl Transcoder {
                 .video()?;
         ost.set_parameters(&encoder);
         
-        // Use target dimensions instead of original dimensions
-        encoder.set_height(target_height);
-        encoder.set_width(target_width);
-        encoder.set_aspect_ratio(decoder.aspect_ratio());
+        encoder.set_height(layout.height);
+        encoder.set_width(layout.width);
+        encoder.set_aspect_ratio(layout.sample_aspect_ratio);
         encoder.set_format(output_format);
         encoder.set_frame_rate(decoder.frame_rate());
         encoder.set_time_base(ist.time_base());
         
-        // Set validated bitrate
-        encoder.set_bit_rate(bitrate);
+        settings.apply_rate_control(&mut encoder, &mut encoder_opts, bitrate, max_bitrate, &muxer);
+        settings.apply_gop(&mut encoder, &mut encoder_opts, decoder.frame_rate().or(Some(ist.avg_frame_rate())))?;
 
+        let mut flags = codec::Flags::empty();
         if global_header {
-            encoder.set_flags(codec::Flags::GLOBAL_HEADER);
+            flags |= codec::Flags::GLOBAL_HEADER;
+        }
+        if settings.closed_gop {
+            flags |= codec::Flags::CLOSED_GOP;
+        }
+        match pass {
+            EncoderPass::Single => {}
+            EncoderPass::Analysis { stats_prefix } => {
+                let stats_file = EncoderPass::stats_file(stats_prefix, ist.index());
+                settings.codec.set_pass(&mut flags, &mut encoder_opts, 1, &stats_file);
+            }
+            EncoderPass::Final { stats_prefix } => {
+                let stats_file = EncoderPass::stats_file(stats_prefix, ist.index());
+                settings.codec.set_pass(&mut flags, &mut encoder_opts, 2, &stats_file);
+            }
         }
+        encoder.set_flags(flags);
 
         let opened_encoder = encoder
-            .open_with(x264_opts)?;
+            .open_with(encoder_opts)?;
         ost.set_parameters(&opened_encoder);
+        if let Some(tag) = settings.codec.codec_tag(&muxer) {
+            unsafe {
+                (*ost.parameters().as_mut_ptr()).codec_tag = tag;
+            }
+        }
         Ok(Self {
             ost_index,
             decoder,
             input_time_base: ist.time_base(),
+            input_start_time: if ist.start_time() == ffmpeg_next::ffi::AV_NOPTS_VALUE { 0 } else { ist.start_time() },
             encoder: opened_encoder,
+            rotator,
             scaler,
-            frame_count: 0,
-            starting_time: Instant::now(),
-            frames,
+            layout,
+            pad_color,
+            keyframe_times: settings.keyframe_times.clone(),
+            next_keyframe: 0,
             should_inform_about_progress,
-            inform_about_progress,
+            trim: None,
         })
     }
 
+    /// Only keep the frames in `[start, end)` (input time base), rebased so that `start` becomes zero
+    fn set_trim(&mut self, start: i64, end: Option<i64>) {
+        self.trim = Some((start, end));
+    }
+
     fn send_packet_to_decoder(&mut self, packet: &Packet) -> Result<()> {
         self.decoder.send_packet(packet)
             .context("Failed to send packet to decoder")?;
@@ -373,17 +2110,25 @@ impl Transcoder {
         &mut self,
         octx: &mut format::context::Output,
         ost_time_base: Rational,
+        progress: &mut ProgressReporter,
     ) -> Result<()> {
         let mut frame = frame::Video::empty();
         while self.decoder.receive_frame(&mut frame).is_ok() {
-            self.frame_count += 1;
+            let mut timestamp = frame.timestamp();
+            let mut origin = self.input_start_time;
+            if let (Some((start, end)), Some(ts)) = (self.trim, timestamp) {
+                if ts < start || end.map_or(false, |end| ts >= end) {
+                    continue;
+                }
+                timestamp = Some(ts - start);
+                origin = 0;
+            }
 
-            let timestamp = frame.timestamp();
-            let inform_about_progress = self.inform_about_progress.clone();
-            let input_time_base = self.input_time_base.clone();
-            let starting_time = self.starting_time.clone();
-            let frame_count = self.frame_count.clone();
-            let frames = self.frames.clone();
+            // Relative to the stream start, as MPEG-TS and some phone recordings do not start at 0
+            let processed_seconds = match timestamp {
+                Some(timestamp) => (timestamp - origin) as f64 * f64::from(self.input_time_base),
+                None => 0.0,
+            };
 
             if self.should_inform_about_progress {
 
@@ -391,39 +2136,64 @@ impl Transcoder {
                 // As this code isn't thread safe, and in swift, we pass a pointer to the task into rust, it might get deallocated before the task is completed
                 // This would cause a sigsegv
 
-                let timestamp = Rational(timestamp.unwrap_or(0) as i32, 1) * input_time_base;
-                eprintln!(
-                    "time elpased: \t{:8.2}\tframe count: {:8}\ttimestamp: {:8.2}",
-                    starting_time.elapsed().as_secs_f64(),
-                    frame_count,
-                    timestamp
-                );
-                let progress = if frames > 0 {
-                    (frame_count as f64 / frames as f64).clamp(0.0, 1.0)
-                } else {
-                    0.0
-                };
-                let progress = if progress.is_finite() { progress } else { 0.0 };
-        
-                match inform_about_progress.as_ref()(progress) {
-                    Ok(_) => (),
-                    Err(e) => eprintln!("Error informing about progress: {:?}", e),
-                }
+                progress.frame_processed(processed_seconds);
             }
 
+            // The first frame at or after each forced keyframe time becomes a keyframe
+            let mut kind = picture::Type::None;
+            while self.keyframe_times.get(self.next_keyframe).map_or(false, |time| *time <= processed_seconds) {
+                kind = picture::Type::I;
+                self.next_keyframe += 1;
+            }
 
-            let mut scaled_frame = frame::Video::empty();
-            self.scaler
-                .run(&frame, &mut scaled_frame)
-                .context("Failed to scale decoded frame")?;
+            let mut scaled_frame = self.resize(&frame)?;
             scaled_frame.set_pts(timestamp);
-            scaled_frame.set_kind(picture::Type::None);
+            scaled_frame.set_kind(kind);
             self.send_frame_to_encoder(&scaled_frame)?;
             self.receive_and_process_encoded_packets(octx, ost_time_base)?;
         }
         Ok(())
     }
 
+    /// Rotate, crop, scale and pad a decoded frame into the encoder's frame size
+    fn resize(&mut self, frame: &frame::Video) -> Result<frame::Video> {
+        let rotated;
+        let frame = match self.rotator.as_mut() {
+            Some(rotator) => {
+                rotated = rotator.rotate(frame).context("Failed to rotate decoded frame")?;
+                &rotated
+            }
+            None => frame,
+        };
+
+        let cropped;
+        let source = match self.layout.crop {
+            Some(region) => {
+                cropped = video::crop_frame(frame, region).context("Failed to crop decoded frame")?;
+                &cropped
+            }
+            None => frame,
+        };
+
+        if !self.layout.is_padded() {
+            let mut scaled_frame = frame::Video::empty();
+            self.scaler
+                .run(source, &mut scaled_frame)
+                .context("Failed to scale decoded frame")?;
+            return Ok(scaled_frame);
+        }
+
+        // Scale straight into the middle of a frame filled with the pad colour
+        let padded_frame = video::solid_frame(self.encoder.format(), self.layout.width, self.layout.height, self.pad_color)
+            .context("Failed to allocate padded frame")?;
+        let mut picture = video::crop_frame(&padded_frame, self.layout.picture)
+            .context("Failed to reference padded frame")?;
+        self.scaler
+            .run(source, &mut picture)
+            .context("Failed to scale decoded frame")?;
+        Ok(padded_frame)
+    }
+
     fn send_frame_to_encoder(&mut self, frame: &frame::Video) -> Result<()> {
         self.encoder.send_frame(frame)
             .with_context(|| {
@@ -462,3 +2232,186 @@ impl Transcoder {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::video::H264Profile;
+
+    const MBPS: i64 = 1_000_000;
+
+    fn h264_stream() -> VideoStreamFacts {
+        VideoStreamFacts {
+            index: 0,
+            codec_id: codec::Id::H264,
+            width: 1280,
+            height: 720,
+            format: Pixel::YUV420P,
+            aspect_ratio: Rational(1, 1),
+            rotation: 0,
+            profile: 100, // High
+            level: 31,
+            bit_rate: 2 * MBPS,
+            codec_tag: 0,
+        }
+    }
+
+    fn settings(codec: VideoCodec) -> VideoSettings {
+        VideoSettings { codec, ..Default::default() }
+    }
+
+    fn reason(stream: &VideoStreamFacts, muxer: &str, target: (u32, u32), bitrate_limit: Option<i64>, video: &VideoSettings) -> Option<String> {
+        CapacitorFFmpegPlugin::video_stream_reason(stream, muxer, target.0, target.1, bitrate_limit, video)
+    }
+
+    fn muxer(extension: &str) -> format::Output {
+        CapacitorFFmpegPlugin::guess_muxer(&format!("output.{}", extension)).unwrap()
+    }
+
+    #[test]
+    fn compliant_stream_is_kept() {
+        let video = settings(VideoCodec::H264);
+        assert_eq!(reason(&h264_stream(), "mp4", (1280, 720), Some(2 * MBPS), &video), None);
+        assert_eq!(reason(&h264_stream(), "mp4", (1920, 1080), Some(4 * MBPS), &video), None);
+        assert_eq!(reason(&h264_stream(), "mp4", (0, 0), None, &video), None);
+    }
+
+    #[test]
+    fn other_codec_is_reencoded() {
+        let stream = VideoStreamFacts { codec_id: codec::Id::HEVC, ..h264_stream() };
+        let why = reason(&stream, "mp4", (0, 0), None, &settings(VideoCodec::H264)).unwrap();
+        assert!(why.contains("is hevc"), "{}", why);
+
+        let why = reason(&h264_stream(), "mp4", (0, 0), None, &settings(VideoCodec::Hevc)).unwrap();
+        assert!(why.contains("is h264"), "{}", why);
+    }
+
+    #[test]
+    fn pixel_format_the_codec_does_not_keep_is_reencoded() {
+        let stream = VideoStreamFacts { format: Pixel::YUV422P, ..h264_stream() };
+        let why = reason(&stream, "mp4", (0, 0), None, &settings(VideoCodec::H264)).unwrap();
+        assert!(why.contains("pixel format"), "{}", why);
+    }
+
+    #[test]
+    fn hevc_needs_the_hvc1_tag_in_mp4_and_mov() {
+        let video = settings(VideoCodec::Hevc);
+        let hev1 = VideoStreamFacts { codec_id: codec::Id::HEVC, codec_tag: u32::from_le_bytes(*b"hev1"), level: 93, profile: 1, ..h264_stream() };
+        let hvc1 = VideoStreamFacts { codec_tag: u32::from_le_bytes(*b"hvc1"), ..hev1 };
+        assert!(reason(&hev1, "mp4", (0, 0), None, &video).unwrap().contains("not tagged hvc1"));
+        assert!(reason(&hev1, "mov", (0, 0), None, &video).unwrap().contains("not tagged hvc1"));
+        assert_eq!(reason(&hvc1, "mp4", (0, 0), None, &video), None);
+        assert_eq!(reason(&hev1, "matroska", (0, 0), None, &video), None);
+    }
+
+    #[test]
+    fn larger_stream_is_reencoded() {
+        let video = settings(VideoCodec::H264);
+        let stream = VideoStreamFacts { width: 1920, height: 1080, ..h264_stream() };
+        let why = reason(&stream, "mp4", (1280, 720), None, &video).unwrap();
+        assert!(why.contains("1920x1080, larger than the requested 1280x720"), "{}", why);
+        // A 0 dimension is derived from the other one
+        assert!(reason(&stream, "mp4", (1280, 0), None, &video).is_some());
+        assert!(reason(&stream, "mp4", (0, 720), None, &video).is_some());
+        assert_eq!(reason(&stream, "mp4", (1920, 0), None, &video), None);
+    }
+
+    #[test]
+    fn cropping_fill_is_reencoded() {
+        let video = VideoSettings { resize_mode: video::ResizeMode::Fill, ..settings(VideoCodec::H264) };
+        // Small enough for a square box, but filling it would crop the sides
+        assert_eq!(reason(&h264_stream(), "mp4", (1280, 720), None, &video), None);
+        assert!(reason(&h264_stream(), "mp4", (1920, 1920), None, &video).is_some());
+    }
+
+    #[test]
+    fn rotated_stream_is_judged_upright() {
+        let video = settings(VideoCodec::H264);
+        // A portrait phone video: coded landscape, displayed 720x1280
+        let stream = VideoStreamFacts { rotation: 90, ..h264_stream() };
+        assert_eq!(reason(&stream, "mp4", (720, 1280), None, &video), None);
+        let why = reason(&stream, "mp4", (1280, 720), None, &video).unwrap();
+        assert!(why.contains("720x1280"), "{}", why);
+
+        let upside_down = VideoStreamFacts { rotation: 180, ..h264_stream() };
+        assert_eq!(reason(&upside_down, "mp4", (1280, 720), None, &video), None);
+    }
+
+    #[test]
+    fn stream_over_the_bitrate_ceiling_is_reencoded() {
+        let video = settings(VideoCodec::H264);
+        let why = reason(&h264_stream(), "mp4", (0, 0), Some(MBPS), &video).unwrap();
+        assert!(why.contains("2000000 bps, over the 1000000 bps limit"), "{}", why);
+        assert_eq!(reason(&h264_stream(), "mp4", (0, 0), Some(2 * MBPS), &video), None);
+
+        let unknown = VideoStreamFacts { bit_rate: 0, ..h264_stream() };
+        assert!(reason(&unknown, "mp4", (0, 0), Some(MBPS), &video).unwrap().contains("unknown"));
+        assert_eq!(reason(&unknown, "mp4", (0, 0), None, &video), None);
+    }
+
+    #[test]
+    fn bitrate_ceiling_follows_the_rate_control() {
+        let limit = |video: &VideoSettings, max_bitrate| CapacitorFFmpegPlugin::bitrate_limit(video, 2_000_000, max_bitrate);
+        let abr = settings(VideoCodec::H264);
+        assert_eq!(limit(&abr, None), Some(2 * MBPS));
+        let cbr = VideoSettings { rate_control: RateControl::Cbr, ..settings(VideoCodec::H264) };
+        assert_eq!(limit(&cbr, Some(5_000_000)), Some(2 * MBPS));
+        let crf = VideoSettings { rate_control: RateControl::Crf, ..settings(VideoCodec::H264) };
+        assert_eq!(limit(&crf, None), None);
+        assert_eq!(limit(&crf, Some(5_000_000)), Some(5 * MBPS));
+        let target_size = VideoSettings { rate_control: RateControl::TargetSize, ..settings(VideoCodec::H264) };
+        assert_eq!(limit(&target_size, None), None);
+        assert_eq!(limit(&settings(VideoCodec::ProRes), None), None);
+    }
+
+    #[test]
+    fn profile_and_level_are_checked() {
+        let main = VideoSettings { profile: Some(H264Profile::Main), ..settings(VideoCodec::H264) };
+        assert!(reason(&h264_stream(), "mp4", (0, 0), None, &main).unwrap().contains("profile 100"));
+        let high = VideoSettings { profile: Some(H264Profile::High), ..settings(VideoCodec::H264) };
+        assert_eq!(reason(&h264_stream(), "mp4", (0, 0), None, &high), None);
+
+        let level_30 = VideoSettings { level: Some(30), ..settings(VideoCodec::H264) };
+        assert!(reason(&h264_stream(), "mp4", (0, 0), None, &level_30).unwrap().contains("level 31"));
+        let level_31 = VideoSettings { level: Some(31), ..settings(VideoCodec::H264) };
+        assert_eq!(reason(&h264_stream(), "mp4", (0, 0), None, &level_31), None);
+
+        // HEVC level 3.1 is stored as 93
+        let hevc = VideoStreamFacts { codec_id: codec::Id::HEVC, level: 93, ..h264_stream() };
+        let hevc_31 = VideoSettings { level: Some(31), ..settings(VideoCodec::Hevc) };
+        assert_eq!(reason(&hevc, "matroska", (0, 0), None, &hevc_31), None);
+        let hevc_30 = VideoSettings { level: Some(30), ..settings(VideoCodec::Hevc) };
+        assert!(reason(&hevc, "matroska", (0, 0), None, &hevc_30).is_some());
+    }
+
+    #[test]
+    fn muxer_is_guessed_without_creating_the_file() {
+        let output = std::env::temp_dir().join("capacitor-ffmpeg-guess-muxer.mp4");
+        let _ = std::fs::remove_file(&output);
+        let guessed = CapacitorFFmpegPlugin::guess_muxer(output.to_str().unwrap()).unwrap();
+        assert_eq!(guessed.name(), "mp4");
+        assert!(!output.exists());
+
+        assert_eq!(muxer("mkv").name(), "matroska");
+        let e = CapacitorFFmpegPlugin::guess_muxer("output.unknown-extension").unwrap_err();
+        assert_eq!(ErrorCode::from_error(&e), ErrorCode::UnsupportedFormat);
+    }
+
+    #[test]
+    fn container_decides_what_can_be_copied() {
+        let mp4 = muxer("mp4");
+        let webm = muxer("webm");
+        let wav = muxer("wav");
+        let accepts = CapacitorFFmpegPlugin::muxer_accepts;
+
+        assert!(accepts(&mp4, media::Type::Video, codec::Id::H264));
+        assert!(accepts(&webm, media::Type::Video, codec::Id::VP9));
+        assert!(!accepts(&webm, media::Type::Video, codec::Id::H264));
+
+        assert!(accepts(&mp4, media::Type::Audio, codec::Id::AAC));
+        assert!(accepts(&webm, media::Type::Audio, codec::Id::OPUS));
+        assert!(!accepts(&webm, media::Type::Audio, codec::Id::AAC));
+        assert!(accepts(&wav, media::Type::Audio, codec::Id::PCM_S16LE));
+        assert!(!accepts(&wav, media::Type::Audio, codec::Id::AAC));
+    }
+}
@@ -0,0 +1,278 @@
+use ffmpeg_next::ffi;
+use ffmpeg_next::format::stream::Disposition;
+use ffmpeg_next::format::{self, Pixel, Sample};
+use ffmpeg_next::{decoder, media, rescale, DictionaryRef, Rational, Stream};
+use serde_json::{json, Map, Value};
+use std::ffi::CStr;
+use std::os::raw::c_char;
+
+/// Describe an opened input as JSON: container, chapters and every stream
+///
+/// Keys are camelCase for the JS layer. Values the container does not know
+/// are `null` rather than 0, so callers can tell "unknown" from "zero".
+pub fn describe_input(ictx: &format::context::Input) -> Value {
+    let format = ictx.format();
+    let size = unsafe {
+        let pb = (*ictx.as_ptr()).pb;
+        if pb.is_null() { -1 } else { ffi::avio_size(pb) }
+    };
+
+    json!({
+        "format": {
+            "name": format.name(),
+            "longName": format.description(),
+            "durationSeconds": timestamp_seconds(ictx.duration(), rescale::TIME_BASE),
+            "startSeconds": timestamp_seconds(unsafe { (*ictx.as_ptr()).start_time }, rescale::TIME_BASE),
+            "bitRate": positive(ictx.bit_rate()),
+            "size": positive(size),
+            "streamCount": ictx.nb_streams(),
+            "metadata": metadata(ictx.metadata()),
+        },
+        "chapters": ictx.chapters().map(|chapter| json!({
+            "id": chapter.id(),
+            "startSeconds": timestamp_seconds(chapter.start(), chapter.time_base()),
+            "endSeconds": timestamp_seconds(chapter.end(), chapter.time_base()),
+            "title": chapter.metadata().get("title"),
+            "metadata": metadata(chapter.metadata()),
+        })).collect::<Vec<_>>(),
+        "streams": ictx.streams().map(|stream| describe_stream(&stream)).collect::<Vec<_>>(),
+    })
+}
+
+fn describe_stream(stream: &Stream) -> Value {
+    let parameters = stream.parameters();
+    let codec_id = parameters.id();
+    let raw = unsafe { &*parameters.as_ptr() };
+
+    let mut description = json!({
+        "index": stream.index(),
+        "type": media_type_name(parameters.medium()),
+        "codec": codec_id.name(),
+        "codecLongName": decoder::find(codec_id).map(|codec| codec.description().to_string()),
+        "profile": profile_name(raw.codec_id, raw.profile),
+        "bitRate": positive(raw.bit_rate),
+        "durationSeconds": timestamp_seconds(stream.duration(), stream.time_base()),
+        "startSeconds": timestamp_seconds(stream.start_time(), stream.time_base()),
+        "frames": positive(stream.frames()),
+        "language": stream.metadata().get("language"),
+        "disposition": disposition(stream.disposition()),
+        "metadata": metadata(stream.metadata()),
+    });
+
+    let details = match parameters.medium() {
+        media::Type::Video => json!({
+            "width": raw.width,
+            "height": raw.height,
+            "pixelFormat": pixel_format_name(pixel_format(raw.format)),
+            "frameRate": rational(stream.avg_frame_rate()),
+            "realFrameRate": rational(stream.rate()),
+            "sampleAspectRatio": rational(Rational::from(raw.sample_aspect_ratio)),
+            "rotation": rotation(stream),
+        }),
+        media::Type::Audio => json!({
+            "sampleRate": raw.sample_rate,
+            "channels": raw.ch_layout.nb_channels,
+            "channelLayout": channel_layout_name(&raw.ch_layout),
+            "sampleFormat": sample_format_name(sample_format(raw.format)),
+        }),
+        _ => json!({}),
+    };
+    if let (Value::Object(description), Value::Object(details)) = (&mut description, details) {
+        description.extend(details);
+    }
+    description
+}
+
+/// Clockwise rotation in degrees (0, 90, 180 or 270) needed to display the stream upright
+///
+/// Read from the display matrix side data, falling back to the legacy `rotate` tag.
+pub fn rotation(stream: &Stream) -> i32 {
+    let angle = unsafe {
+        let parameters = stream.parameters();
+        let raw = &*parameters.as_ptr();
+        let side_data = ffi::av_packet_side_data_get(
+            raw.coded_side_data,
+            raw.nb_coded_side_data,
+            ffi::AVPacketSideDataType::AV_PKT_DATA_DISPLAYMATRIX,
+        );
+        if side_data.is_null() || (*side_data).size < 9 * 4 {
+            None
+        } else {
+            // av_display_rotation_get returns the counterclockwise angle
+            let angle = ffi::av_display_rotation_get((*side_data).data as *const i32);
+            if angle.is_finite() { Some(-angle) } else { None }
+        }
+    };
+    let angle = angle.or_else(|| {
+        stream
+            .metadata()
+            .get("rotate")
+            .and_then(|rotate| rotate.trim().parse::<f64>().ok())
+    });
+
+    match angle {
+        Some(angle) => (((angle / 90.0).round() as i32) * 90).rem_euclid(360),
+        None => 0,
+    }
+}
+
+/// Pixel format of a raw `AVCodecParameters::format`, or `Pixel::None` if FFmpeg does not know it
+///
+/// The value comes from the demuxer, so it is looked up among the known formats rather than
+/// transmuted into an `AVPixelFormat`.
+pub fn pixel_format(raw: i32) -> Pixel {
+    let mut descriptor = std::ptr::null();
+    loop {
+        descriptor = unsafe { ffi::av_pix_fmt_desc_next(descriptor) };
+        if descriptor.is_null() {
+            return Pixel::None;
+        }
+        let id = unsafe { ffi::av_pix_fmt_desc_get_id(descriptor) };
+        if id as i32 == raw {
+            return Pixel::from(id);
+        }
+    }
+}
+
+/// Sample format of a raw `AVCodecParameters::format`, or `Sample::None` if FFmpeg does not know it
+pub fn sample_format(raw: i32) -> Sample {
+    use ffi::AVSampleFormat::*;
+    const KNOWN: [ffi::AVSampleFormat; 12] = [
+        AV_SAMPLE_FMT_U8,
+        AV_SAMPLE_FMT_S16,
+        AV_SAMPLE_FMT_S32,
+        AV_SAMPLE_FMT_FLT,
+        AV_SAMPLE_FMT_DBL,
+        AV_SAMPLE_FMT_U8P,
+        AV_SAMPLE_FMT_S16P,
+        AV_SAMPLE_FMT_S32P,
+        AV_SAMPLE_FMT_FLTP,
+        AV_SAMPLE_FMT_DBLP,
+        AV_SAMPLE_FMT_S64,
+        AV_SAMPLE_FMT_S64P,
+    ];
+    KNOWN
+        .into_iter()
+        .find(|format| *format as i32 == raw)
+        .map_or(Sample::None, Sample::from)
+}
+
+fn media_type_name(medium: media::Type) -> &'static str {
+    match medium {
+        media::Type::Video => "video",
+        media::Type::Audio => "audio",
+        media::Type::Subtitle => "subtitle",
+        media::Type::Data => "data",
+        media::Type::Attachment => "attachment",
+        media::Type::Unknown => "unknown",
+    }
+}
+
+fn metadata(dictionary: DictionaryRef) -> Value {
+    let mut entries = Map::new();
+    for (key, value) in dictionary.iter() {
+        entries.insert(key.to_string(), Value::from(value));
+    }
+    Value::Object(entries)
+}
+
+fn disposition(disposition: Disposition) -> Value {
+    json!({
+        "default": disposition.contains(Disposition::DEFAULT),
+        "dub": disposition.contains(Disposition::DUB),
+        "original": disposition.contains(Disposition::ORIGINAL),
+        "comment": disposition.contains(Disposition::COMMENT),
+        "forced": disposition.contains(Disposition::FORCED),
+        "hearingImpaired": disposition.contains(Disposition::HEARING_IMPAIRED),
+        "visualImpaired": disposition.contains(Disposition::VISUAL_IMPAIRED),
+        "attachedPic": disposition.contains(Disposition::ATTACHED_PIC),
+        "captions": disposition.contains(Disposition::CAPTIONS),
+        "descriptions": disposition.contains(Disposition::DESCRIPTIONS),
+    })
+}
+
+fn timestamp_seconds(timestamp: i64, time_base: Rational) -> Option<f64> {
+    if timestamp == ffi::AV_NOPTS_VALUE || time_base.denominator() == 0 {
+        return None;
+    }
+    Some(timestamp as f64 * f64::from(time_base))
+}
+
+fn positive(value: i64) -> Option<i64> {
+    if value > 0 { Some(value) } else { None }
+}
+
+fn rational(value: Rational) -> Option<f64> {
+    if value.numerator() == 0 || value.denominator() == 0 {
+        return None;
+    }
+    Some(f64::from(value))
+}
+
+fn pixel_format_name(format: Pixel) -> Option<String> {
+    if format == Pixel::None {
+        return None;
+    }
+    format.descriptor().map(|descriptor| descriptor.name().to_string())
+}
+
+fn sample_format_name(format: Sample) -> Option<String> {
+    if format == Sample::None {
+        return None;
+    }
+    Some(format.name().to_string())
+}
+
+fn profile_name(codec_id: ffi::AVCodecID, profile: i32) -> Option<String> {
+    unsafe { c_string(ffi::avcodec_profile_name(codec_id, profile)) }
+}
+
+fn channel_layout_name(layout: &ffi::AVChannelLayout) -> Option<String> {
+    let mut buffer = [0 as c_char; 128];
+    let written = unsafe { ffi::av_channel_layout_describe(layout, buffer.as_mut_ptr(), buffer.len()) };
+    if written <= 0 {
+        return None;
+    }
+    unsafe { c_string(buffer.as_ptr()) }
+}
+
+unsafe fn c_string(ptr: *const c_char) -> Option<String> {
+    if ptr.is_null() {
+        return None;
+    }
+    Some(CStr::from_ptr(ptr).to_string_lossy().into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ffmpeg_next::format::sample;
+
+    #[test]
+    fn pixel_format_maps_known_values() {
+        assert_eq!(pixel_format(ffi::AVPixelFormat::AV_PIX_FMT_YUV420P as i32), Pixel::YUV420P);
+        assert_eq!(pixel_format(ffi::AVPixelFormat::AV_PIX_FMT_NV12 as i32), Pixel::NV12);
+        assert_eq!(pixel_format(ffi::AVPixelFormat::AV_PIX_FMT_YUV422P10LE as i32), Pixel::YUV422P10LE);
+    }
+
+    #[test]
+    fn pixel_format_rejects_unknown_values() {
+        assert_eq!(pixel_format(-1), Pixel::None);
+        assert_eq!(pixel_format(100_000), Pixel::None);
+        assert_eq!(pixel_format_name(pixel_format(100_000)), None);
+    }
+
+    #[test]
+    fn sample_format_maps_known_values() {
+        assert_eq!(sample_format(ffi::AVSampleFormat::AV_SAMPLE_FMT_S16 as i32), Sample::I16(sample::Type::Packed));
+        assert_eq!(sample_format(ffi::AVSampleFormat::AV_SAMPLE_FMT_FLTP as i32), Sample::F32(sample::Type::Planar));
+        assert_eq!(sample_format(ffi::AVSampleFormat::AV_SAMPLE_FMT_S64P as i32), Sample::I64(sample::Type::Planar));
+    }
+
+    #[test]
+    fn sample_format_rejects_unknown_values() {
+        assert_eq!(sample_format(-1), Sample::None);
+        assert_eq!(sample_format(ffi::AVSampleFormat::AV_SAMPLE_FMT_NB as i32), Sample::None);
+        assert_eq!(sample_format_name(sample_format(42)), None);
+    }
+}
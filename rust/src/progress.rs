@@ -0,0 +1,310 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::job::CancellationToken;
+
+/// Callback through which operations report progress to the host
+pub type ProgressCallback = Arc<Box<dyn Fn(&CProgress) -> Result<(), anyhow::Error>>>;
+
+// Minimum time between two progress reports within the same stage
+const MIN_REPORT_INTERVAL: Duration = Duration::from_millis(250);
+
+/// What a job is currently doing
+#[repr(i32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProgressStage {
+    Probing = 0,
+    Encoding = 1,
+    Flushing = 2,
+    Finalizing = 3,
+}
+
+/// C-compatible progress payload handed to the host's progress callback
+///
+/// Values that are not known (yet) are reported as 0, except `eta_seconds`
+/// which is negative when unknown.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct CProgress {
+    pub progress: f64,          // normalized 0.0..1.0
+    pub stage: i32,             // ProgressStage
+    pub processed_seconds: f64, // media time written so far
+    pub total_seconds: f64,     // media duration of the input
+    pub frames: u64,            // video frames decoded or encoded so far; 0 for copies and audio
+    pub fps: f64,               // average encode speed in video frames per second
+    pub speed: f64,             // media seconds processed per wall-clock second
+    pub bytes_written: u64,     // current size of the output file
+    pub eta_seconds: f64,       // estimated wall-clock time left
+}
+
+/// Turns raw position updates into throttled [`CProgress`] reports
+///
+/// A failing callback (non-zero return from the host) is treated as a
+/// cancellation request for the job.
+pub struct ProgressReporter {
+    callback: ProgressCallback,
+    cancellation: CancellationToken,
+    output_path: String,
+    started: Instant,
+    last_report: Option<Instant>,
+    stage: ProgressStage,
+    total_seconds: f64,
+    total_frames: i64,
+    input_size: u64,
+    input_position: u64,
+    processed_seconds: f64,
+    frames: u64,
+    copied_packets: u64,
+    last_fraction: f64,
+    pass: u32,
+    passes: u32,
+    pass_started: Instant,
+    attempt_start: f64, // overall fraction reached before the current attempt
+    last_overall: f64,
+}
+
+impl ProgressReporter {
+    pub fn new(callback: ProgressCallback, cancellation: CancellationToken, output_path: &str) -> Self {
+        Self {
+            callback,
+            cancellation,
+            output_path: output_path.to_string(),
+            started: Instant::now(),
+            last_report: None,
+            stage: ProgressStage::Probing,
+            total_seconds: 0.0,
+            total_frames: 0,
+            input_size: 0,
+            input_position: 0,
+            processed_seconds: 0.0,
+            frames: 0,
+            copied_packets: 0,
+            last_fraction: 0.0,
+            pass: 0,
+            passes: 1,
+            pass_started: Instant::now(),
+            attempt_start: 0.0,
+            last_overall: 0.0,
+        }
+    }
+
+    /// Record what the input looks like once it has been probed
+    ///
+    /// Any of the totals may be 0 when the container does not provide it.
+    pub fn set_totals(&mut self, total_seconds: f64, total_frames: i64, input_size: u64) {
+        self.total_seconds = if total_seconds.is_finite() { total_seconds.max(0.0) } else { 0.0 };
+        self.total_frames = total_frames;
+        self.input_size = input_size;
+    }
+
+    /// Start pass `index` (from 0) of a `count`-pass encode
+    ///
+    /// The passes are reported as one 0.0..1.0 range in which each pass takes an
+    /// equal share; frames, fps and speed restart with every pass.
+    pub fn start_pass(&mut self, index: u32, count: u32) {
+        self.passes = count.max(1);
+        self.pass = index.min(self.passes - 1);
+        self.pass_started = Instant::now();
+        self.input_position = 0;
+        self.processed_seconds = 0.0;
+        self.frames = 0;
+        self.copied_packets = 0;
+        self.last_fraction = 0.0;
+    }
+
+    /// Start over after an attempt whose output has to be encoded again
+    ///
+    /// The passes of the new attempt share what is left of the 0.0..1.0 range, so the
+    /// reported fraction carries on from where it is instead of going back to 0.
+    pub fn start_retry(&mut self) {
+        self.attempt_start = self.last_overall;
+    }
+
+    /// Record the byte position of the last packet read from the input
+    ///
+    /// Only used when neither a duration nor a frame count is known.
+    pub fn packet_read(&mut self, position: isize) {
+        if position > 0 {
+            self.input_position = self.input_position.max(position as u64);
+        }
+    }
+
+    /// Move to another stage; stage changes are always reported
+    pub fn set_stage(&mut self, stage: ProgressStage) {
+        self.stage = stage;
+        self.report();
+    }
+
+    /// Record a newly processed video frame and report if enough time has passed
+    pub fn frame_processed(&mut self, processed_seconds: f64) {
+        self.frames += 1;
+        self.advance(processed_seconds);
+    }
+
+    /// Record a packet of the progress stream copied without decoding
+    ///
+    /// Copied packets are not frames, so they only stand in for the frame count
+    /// when falling back to the number of frames in the input.
+    pub fn packet_copied(&mut self, processed_seconds: f64) {
+        self.copied_packets += 1;
+        self.advance(processed_seconds);
+    }
+
+    /// Record a newly processed audio frame, which only moves the media position
+    pub fn audio_processed(&mut self, processed_seconds: f64) {
+        self.advance(processed_seconds);
+    }
+
+    fn advance(&mut self, processed_seconds: f64) {
+        if processed_seconds.is_finite() && processed_seconds > self.processed_seconds {
+            self.processed_seconds = processed_seconds;
+        }
+
+        let due = match self.last_report {
+            Some(last_report) => last_report.elapsed() >= MIN_REPORT_INTERVAL,
+            None => true,
+        };
+        if due {
+            self.report();
+        }
+    }
+
+    /// Progress as a fraction, from the most reliable source available
+    ///
+    /// Media time against duration works for every container that knows its
+    /// duration; frame counts are missing for MKV, WebM and most MPEG-TS files,
+    /// so they and the input byte position are only fallbacks. The result never
+    /// goes backwards, even if the source changes between reports.
+    fn fraction(&mut self) -> f64 {
+        let fraction = if self.total_seconds > 0.0 && self.processed_seconds > 0.0 {
+            self.processed_seconds / self.total_seconds
+        } else if self.total_frames > 0 {
+            (self.frames + self.copied_packets) as f64 / self.total_frames as f64
+        } else if self.input_size > 0 {
+            self.input_position as f64 / self.input_size as f64
+        } else {
+            0.0
+        };
+        let fraction = if fraction.is_finite() { fraction.clamp(0.0, 1.0) } else { 0.0 };
+        self.last_fraction = self.last_fraction.max(fraction);
+        self.last_fraction
+    }
+
+    fn report(&mut self) {
+        self.last_report = Some(Instant::now());
+
+        let elapsed = self.started.elapsed().as_secs_f64();
+        let attempt_fraction = (self.pass as f64 + self.fraction()) / self.passes as f64;
+        let fraction = self.attempt_start + (1.0 - self.attempt_start) * attempt_fraction;
+        let fraction = self.last_overall.max(fraction);
+        self.last_overall = fraction;
+        let pass_elapsed = self.pass_started.elapsed().as_secs_f64();
+        let (fps, speed) = if pass_elapsed > 0.0 {
+            (self.frames as f64 / pass_elapsed, self.processed_seconds / pass_elapsed)
+        } else {
+            (0.0, 0.0)
+        };
+        let eta_seconds = if fraction > 0.0 {
+            elapsed * (1.0 - fraction) / fraction
+        } else {
+            -1.0
+        };
+        let bytes_written = std::fs::metadata(&self.output_path)
+            .map(|metadata| metadata.len())
+            .unwrap_or(0);
+
+        let progress = CProgress {
+            progress: fraction,
+            stage: self.stage as i32,
+            processed_seconds: self.processed_seconds,
+            total_seconds: self.total_seconds,
+            frames: self.frames,
+            fps,
+            speed,
+            bytes_written,
+            eta_seconds,
+        };
+        if let Err(e) = self.callback.as_ref()(&progress) {
+            eprintln!("Error informing about progress, cancelling job: {:?}", e);
+            self.cancellation.cancel();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    fn reporter() -> (ProgressReporter, Arc<Mutex<Vec<f64>>>) {
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let sink = reports.clone();
+        let callback: ProgressCallback = Arc::new(Box::new(move |progress: &CProgress| {
+            sink.lock().unwrap().push(progress.progress);
+            Ok(())
+        }));
+        let mut reporter = ProgressReporter::new(callback, CancellationToken::new(), "/nonexistent/output.mp4");
+        reporter.set_totals(10.0, 0, 0);
+        (reporter, reports)
+    }
+
+    /// Process media up to `seconds`, then force a report through a stage change
+    fn reach(reporter: &mut ProgressReporter, seconds: f64) {
+        reporter.frame_processed(seconds);
+        reporter.set_stage(ProgressStage::Encoding);
+    }
+
+    fn last(reports: &Arc<Mutex<Vec<f64>>>) -> f64 {
+        *reports.lock().unwrap().last().unwrap()
+    }
+
+    #[test]
+    fn passes_share_the_range() {
+        let (mut reporter, reports) = reporter();
+        reporter.start_pass(0, 2);
+        reach(&mut reporter, 5.0);
+        assert_eq!(last(&reports), 0.25);
+        reporter.start_pass(1, 2);
+        reach(&mut reporter, 5.0);
+        assert_eq!(last(&reports), 0.75);
+    }
+
+    #[test]
+    fn retry_carries_on_from_the_last_report() {
+        let (mut reporter, reports) = reporter();
+        reporter.start_pass(0, 1);
+        reach(&mut reporter, 8.0);
+        assert_eq!(last(&reports), 0.8);
+
+        reporter.start_retry();
+        reporter.start_pass(0, 1);
+        reporter.set_stage(ProgressStage::Probing);
+        assert_eq!(last(&reports), 0.8);
+        reach(&mut reporter, 5.0);
+        assert!((last(&reports) - 0.9).abs() < 1e-9);
+
+        let reports = reports.lock().unwrap();
+        assert!(reports.windows(2).all(|pair| pair[0] <= pair[1]), "{:?}", reports);
+    }
+
+    #[test]
+    fn only_video_frames_are_counted() {
+        let (mut reporter, _) = reporter();
+        reporter.start_pass(0, 1);
+        reporter.audio_processed(1.0);
+        reporter.packet_copied(2.0);
+        assert_eq!(reporter.frames, 0);
+        assert_eq!(reporter.processed_seconds, 2.0);
+        reporter.frame_processed(3.0);
+        assert_eq!(reporter.frames, 1);
+    }
+
+    #[test]
+    fn copied_packets_fall_back_on_the_frame_count() {
+        let (mut reporter, _) = reporter();
+        reporter.set_totals(0.0, 4, 0);
+        reporter.start_pass(0, 1);
+        reporter.packet_copied(f64::NAN);
+        assert_eq!(reporter.fraction(), 0.25);
+    }
+}
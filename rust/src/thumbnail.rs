@@ -0,0 +1,134 @@
+use anyhow::{bail, Context, Result};
+use ffmpeg_next::util::color;
+use ffmpeg_next::util::format::Pixel;
+use ffmpeg_next::{codec, encoder, frame, Codec, Dictionary, Packet, Rational};
+
+use crate::error::{ErrorCode, PluginError};
+
+// MJPEG quantiser range: 2 is the best quality, 31 the worst
+const MJPEG_BEST_QSCALE: f64 = 2.0;
+const MJPEG_WORST_QSCALE: f64 = 31.0;
+// FF_QP2LAMBDA from libavutil: global_quality is expressed in lambda units
+const QP2LAMBDA: f64 = 118.0;
+
+/// Image formats `generate_thumbnail` can write
+///
+/// The values are part of the C ABI.
+#[repr(i32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImageFormat {
+    Jpeg = 0,
+    Png = 1,
+    Webp = 2,
+}
+
+impl ImageFormat {
+    pub fn from_c(value: i32) -> Option<Self> {
+        match value {
+            0 => Some(ImageFormat::Jpeg),
+            1 => Some(ImageFormat::Png),
+            2 => Some(ImageFormat::Webp),
+            _ => None,
+        }
+    }
+
+    /// Find the encoder for this format
+    ///
+    /// WebP needs FFmpeg to be built with libwebp (the `webp` feature), so it may be missing.
+    pub fn encoder(self) -> Result<Codec> {
+        let codec = match self {
+            ImageFormat::Jpeg => encoder::find(codec::Id::MJPEG),
+            ImageFormat::Png => encoder::find(codec::Id::PNG),
+            ImageFormat::Webp => encoder::find_by_name("libwebp"),
+        };
+        codec.ok_or_else(|| {
+            PluginError::new(
+                ErrorCode::EncoderUnavailable,
+                format!("No {:?} encoder is available in this FFmpeg build", self),
+            )
+            .into()
+        })
+    }
+
+    /// Pixel format the scaler should produce for this encoder
+    pub fn pixel_format(codec: Codec) -> Pixel {
+        codec
+            .video()
+            .ok()
+            .and_then(|video| video.formats().and_then(|mut formats| formats.next()))
+            .unwrap_or(Pixel::YUV420P)
+    }
+}
+
+/// Output dimensions for a thumbnail
+///
+/// A 0 width or height is derived from the other one so that the source
+/// aspect ratio is kept; both 0 keeps the source size.
+pub fn thumbnail_size(source_width: u32, source_height: u32, width: u32, height: u32) -> (u32, u32) {
+    let scale = |value: u32, numerator: u32, denominator: u32| -> u32 {
+        ((value as u64 * numerator as u64 + denominator as u64 / 2) / denominator.max(1) as u64).max(1) as u32
+    };
+    match (width, height) {
+        (0, 0) => (source_width, source_height),
+        (width, 0) => (width, scale(width, source_height, source_width)),
+        (0, height) => (scale(height, source_width, source_height), height),
+        (width, height) => (width, height),
+    }
+}
+
+/// Encode a single frame as a still image
+///
+/// # Arguments
+///
+/// * `codec` - Encoder returned by [`ImageFormat::encoder`]
+/// * `format` - Format the encoder belongs to
+/// * `picture` - Frame already scaled to the output size and pixel format
+/// * `quality` - 0.0 (smallest) to 1.0 (best); ignored for PNG, which is lossless
+///
+/// # Returns
+///
+/// The encoded image file contents
+pub fn encode_image(codec: Codec, format: ImageFormat, picture: &frame::Video, quality: f64) -> Result<Vec<u8>> {
+    let mut encoder = codec::context::Context::new_with_codec(codec)
+        .encoder()
+        .video()?;
+    encoder.set_width(picture.width());
+    encoder.set_height(picture.height());
+    encoder.set_format(picture.format());
+    encoder.set_time_base(Rational(1, 1));
+
+    let mut options = Dictionary::new();
+    match format {
+        ImageFormat::Jpeg => {
+            let qscale = MJPEG_WORST_QSCALE - quality * (MJPEG_WORST_QSCALE - MJPEG_BEST_QSCALE);
+            encoder.set_flags(codec::Flags::QSCALE);
+            encoder.set_global_quality((qscale * QP2LAMBDA).round() as i32);
+            encoder.set_color_range(color::Range::JPEG);
+        }
+        ImageFormat::Png => {}
+        ImageFormat::Webp => {
+            options.set("quality", &format!("{}", (quality * 100.0).round()));
+        }
+    }
+
+    let mut encoder = encoder
+        .open_with(options)
+        .with_context(|| format!("Failed to open {:?} encoder", format))?;
+
+    encoder.send_frame(picture)
+        .context("Failed to send frame to image encoder")?;
+    encoder.send_eof()
+        .context("Failed to send EOF to image encoder")?;
+
+    let mut image = Vec::new();
+    let mut encoded = Packet::empty();
+    while encoder.receive_packet(&mut encoded).is_ok() {
+        if let Some(data) = encoded.data() {
+            image.extend_from_slice(data);
+        }
+    }
+    if image.is_empty() {
+        bail!("{:?} encoder produced no data", format);
+    }
+    Ok(image)
+}
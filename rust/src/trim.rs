@@ -0,0 +1,169 @@
+use ffmpeg_next::{rescale, Rational, Rescale};
+
+use crate::error::{ErrorCode, PluginError};
+
+/// How `trim` cuts the input
+///
+/// The values are part of the C ABI.
+#[repr(i32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TrimMode {
+    /// Start at the keyframe before the requested start and copy packets; fast and lossless,
+    /// but the cut may begin a little early
+    Copy = 0,
+    /// Decode and re-encode video so the cut lands on the exact frame
+    Accurate = 1,
+}
+
+impl TrimMode {
+    pub fn from_c(value: i32) -> Option<Self> {
+        match value {
+            0 => Some(TrimMode::Copy),
+            1 => Some(TrimMode::Accurate),
+            _ => None,
+        }
+    }
+}
+
+/// Part of the input to keep, on the container clock in `AV_TIME_BASE` units
+///
+/// Every stream is cut and rebased against the same window, which is what keeps
+/// audio in sync with video.
+#[derive(Clone, Copy, Debug)]
+pub struct TrimWindow {
+    start: i64,
+    end: Option<i64>,
+}
+
+impl TrimWindow {
+    /// Check a requested range before any file is opened
+    ///
+    /// The start must be a non-negative number of seconds and the end, if any, must come after it.
+    pub fn check_range(start_seconds: f64, end_seconds: Option<f64>) -> Result<(), PluginError> {
+        if !start_seconds.is_finite() || start_seconds < 0.0 {
+            return Err(PluginError::new(
+                ErrorCode::InvalidArgument,
+                format!("Trim start {} must be a non-negative number of seconds", start_seconds),
+            ));
+        }
+        if let Some(end_seconds) = end_seconds {
+            if !end_seconds.is_finite() || end_seconds <= start_seconds {
+                return Err(PluginError::new(
+                    ErrorCode::InvalidArgument,
+                    format!("Trim end {} must be after the start {}", end_seconds, start_seconds),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Length of the output in seconds, used as the progress total
+    ///
+    /// The end is capped at `media_seconds` when the media duration is known (non-zero);
+    /// a range starting past the end of the media gives 0.
+    pub fn output_seconds(start_seconds: f64, end_seconds: Option<f64>, media_seconds: f64) -> f64 {
+        let seconds = match end_seconds {
+            Some(end_seconds) if media_seconds <= 0.0 => end_seconds - start_seconds,
+            Some(end_seconds) => end_seconds.min(media_seconds) - start_seconds,
+            None => media_seconds - start_seconds,
+        };
+        seconds.max(0.0)
+    }
+
+    /// # Arguments
+    ///
+    /// * `media_start` - Start time of the container, in `AV_TIME_BASE` units
+    /// * `start_seconds` - Start of the window, relative to the start of the media
+    /// * `end_seconds` - End of the window, or None to keep everything after the start
+    pub fn new(media_start: i64, start_seconds: f64, end_seconds: Option<f64>) -> Self {
+        let to_timestamp = |seconds: f64| media_start + (seconds / f64::from(rescale::TIME_BASE)).round() as i64;
+        Self {
+            start: to_timestamp(start_seconds),
+            end: end_seconds.map(to_timestamp),
+        }
+    }
+
+    pub fn start(&self) -> i64 {
+        self.start
+    }
+
+    pub fn start_in(&self, time_base: Rational) -> i64 {
+        self.start.rescale(rescale::TIME_BASE, time_base)
+    }
+
+    pub fn end_in(&self, time_base: Rational) -> Option<i64> {
+        self.end.map(|end| end.rescale(rescale::TIME_BASE, time_base))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn window_is_on_the_container_clock() {
+        let window = TrimWindow::new(0, 1.5, Some(3.0));
+        assert_eq!(window.start(), 1_500_000);
+        assert_eq!(window.end, Some(3_000_000));
+
+        // Offset by the container start time
+        let window = TrimWindow::new(1_000_000, 0.0, None);
+        assert_eq!(window.start(), 1_000_000);
+        assert_eq!(window.end, None);
+
+        // Rounded to the nearest microsecond
+        let window = TrimWindow::new(0, 1.0 / 3.0, Some(2.0 / 3.0));
+        assert_eq!(window.start(), 333_333);
+        assert_eq!(window.end, Some(666_667));
+    }
+
+    #[test]
+    fn window_in_stream_time_bases() {
+        let window = TrimWindow::new(0, 1.5, Some(3.0));
+        assert_eq!(window.start_in(Rational(1, 90_000)), 135_000);
+        assert_eq!(window.end_in(Rational(1, 90_000)), Some(270_000));
+        assert_eq!(window.start_in(Rational(1, 48_000)), 72_000);
+        assert_eq!(window.end_in(Rational(1, 48_000)), Some(144_000));
+
+        let window = TrimWindow::new(500_000, 1.0, None);
+        assert_eq!(window.start_in(Rational(1, 1_000)), 1_500);
+        assert_eq!(window.end_in(Rational(1, 1_000)), None);
+    }
+
+    #[test]
+    fn range_boundaries() {
+        assert!(TrimWindow::check_range(0.0, None).is_ok());
+        assert!(TrimWindow::check_range(1.0, Some(2.0)).is_ok());
+        assert!(TrimWindow::check_range(0.0, Some(0.001)).is_ok());
+
+        for (start, end) in [
+            (-1.0, None),
+            (f64::NAN, None),
+            (f64::INFINITY, None),
+            (2.0, Some(1.0)),
+            (2.0, Some(2.0)),
+            (0.0, Some(-1.0)),
+            (0.0, Some(f64::NAN)),
+            (0.0, Some(f64::INFINITY)),
+        ] {
+            match TrimWindow::check_range(start, end) {
+                Ok(()) => panic!("{} to {:?} accepted", start, end),
+                Err(e) => assert_eq!(e.code(), ErrorCode::InvalidArgument),
+            }
+        }
+    }
+
+    #[test]
+    fn output_length() {
+        assert_eq!(TrimWindow::output_seconds(10.0, Some(20.0), 60.0), 10.0);
+        assert_eq!(TrimWindow::output_seconds(10.0, None, 60.0), 50.0);
+        // An end past the duration is capped
+        assert_eq!(TrimWindow::output_seconds(50.0, Some(90.0), 60.0), 10.0);
+        // A start past the duration leaves nothing
+        assert_eq!(TrimWindow::output_seconds(70.0, Some(80.0), 60.0), 0.0);
+        assert_eq!(TrimWindow::output_seconds(70.0, None, 60.0), 0.0);
+        // Unknown duration
+        assert_eq!(TrimWindow::output_seconds(10.0, Some(20.0), 0.0), 10.0);
+        assert_eq!(TrimWindow::output_seconds(10.0, None, 0.0), 0.0);
+    }
+}
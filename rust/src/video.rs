@@ -0,0 +1,2167 @@
+use ffmpeg_next::ffi;
+use ffmpeg_next::util::color;
+use ffmpeg_next::util::format::Pixel;
+use ffmpeg_next::{codec, encoder, filter, frame, Codec, Dictionary, Rational};
+use std::os::raw::c_int;
+
+use crate::error::{ErrorCode, PluginError};
+
+// From libavutil/frame.h: crop to the exact pixel even if the data pointers end up unaligned
+const AV_FRAME_CROP_UNALIGNED: c_int = 1;
+// From libavutil/pixdesc.h
+const AV_PIX_FMT_FLAG_RGB: u64 = 1 << 5;
+const AV_PIX_FMT_FLAG_ALPHA: u64 = 1 << 7;
+
+// x264 and x265 CRF scale: 0 is lossless, 51 the worst; the defaults are the encoders' own
+const MAX_CRF: f64 = 51.0;
+const X264_DEFAULT_CRF: f64 = 23.0;
+const X265_DEFAULT_CRF: f64 = 28.0;
+// libvpx has a 0-63 scale and no default; 31 is the usual choice for HD
+const VP9_MAX_CRF: f64 = 63.0;
+const VP9_DEFAULT_CRF: f64 = 31.0;
+// SVT-AV1 and libaom share the 0-63 scale; 35 is SVT-AV1's default
+const AV1_MAX_CRF: f64 = 63.0;
+const AV1_DEFAULT_CRF: f64 = 35.0;
+// AV1 at x264's medium takes minutes per minute of video on a phone CPU, so without a
+// preset AV1 starts from the fast end
+const AV1_DEFAULT_PRESET: EncoderPreset = EncoderPreset::Veryfast;
+
+// Slice counts ffv1 can lay out as a grid at any frame size
+const FFV1_SLICES: [u32; 7] = [4, 6, 9, 12, 16, 24, 30];
+
+// libvpx tile limits, as counts: 64 columns (at 4096 pixels wide and up) and 4 rows
+const VP9_MAX_TILE_COLUMNS: i32 = 64;
+const VP9_MAX_TILE_ROWS: i32 = 4;
+
+// Muxers based on ISO BMFF, which has no room for the filler data of strict CBR
+const ISO_BMFF_MUXERS: [&str; 8] = ["mp4", "mov", "ipod", "3gp", "3g2", "ismv", "f4v", "psp"];
+
+// MKTAG('h', 'v', 'c', '1'): HEVC with the parameter sets in the sample entry, the only
+// HEVC tag Apple players accept
+const HVC1_TAG: u32 = u32::from_le_bytes(*b"hvc1");
+
+/// Codec `reencode_video` encodes video streams to
+///
+/// The values are part of the C ABI.
+#[repr(i32)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum VideoCodec {
+    /// H.264 through libx264
+    #[default]
+    H264 = 0,
+    /// HEVC through libx265, which FFmpeg only has when built with the `hevc` feature
+    Hevc = 1,
+    /// VP9 through libvpx, which FFmpeg only has when built with the `vp9` feature
+    Vp9 = 2,
+    /// AV1 through SVT-AV1, or libaom when FFmpeg has no SVT-AV1; built with the `av1` feature
+    Av1 = 3,
+    /// Apple ProRes through prores_ks, for MOV
+    ProRes = 4,
+    /// Avid DNxHR through FFmpeg's dnxhd encoder, for MOV and MXF
+    Dnxhr = 5,
+    /// Lossless FFV1 version 3, for Matroska
+    Ffv1 = 6,
+}
+
+impl VideoCodec {
+    pub fn from_c(value: i32) -> Option<Self> {
+        match value {
+            0 => Some(VideoCodec::H264),
+            1 => Some(VideoCodec::Hevc),
+            2 => Some(VideoCodec::Vp9),
+            3 => Some(VideoCodec::Av1),
+            4 => Some(VideoCodec::ProRes),
+            5 => Some(VideoCodec::Dnxhr),
+            6 => Some(VideoCodec::Ffv1),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            VideoCodec::H264 => "H.264",
+            VideoCodec::Hevc => "HEVC",
+            VideoCodec::Vp9 => "VP9",
+            VideoCodec::Av1 => "AV1",
+            VideoCodec::ProRes => "ProRes",
+            VideoCodec::Dnxhr => "DNxHR",
+            VideoCodec::Ffv1 => "FFV1",
+        }
+    }
+
+    /// Encoder library, which also names the two-pass stats files
+    pub fn library(self) -> &'static str {
+        match self {
+            VideoCodec::H264 => "x264",
+            VideoCodec::Hevc => "x265",
+            VideoCodec::Vp9 => "libvpx",
+            VideoCodec::Av1 => "av1",
+            VideoCodec::ProRes => "prores_ks",
+            VideoCodec::Dnxhr => "dnxhd",
+            VideoCodec::Ffv1 => "ffv1",
+        }
+    }
+
+    pub fn id(self) -> codec::Id {
+        match self {
+            VideoCodec::H264 => codec::Id::H264,
+            VideoCodec::Hevc => codec::Id::HEVC,
+            VideoCodec::Vp9 => codec::Id::VP9,
+            VideoCodec::Av1 => codec::Id::AV1,
+            VideoCodec::ProRes => codec::Id::PRORES,
+            VideoCodec::Dnxhr => codec::Id::DNXHD,
+            VideoCodec::Ffv1 => codec::Id::FFV1,
+        }
+    }
+
+    /// Find the encoder for this codec
+    ///
+    /// HEVC and VP9 options are x265 and libvpx options, so other encoders of those codecs are not used.
+    /// AV1 is set up for either SVT-AV1 or libaom, and never for FFmpeg's hardware AV1 encoders.
+    /// ProRes takes prores_ks, whose profiles are the ones editors expect, over prores_aw.
+    pub fn encoder(self) -> Result<Codec, PluginError> {
+        let codec = match self {
+            VideoCodec::H264 => encoder::find(codec::Id::H264),
+            VideoCodec::Hevc => encoder::find_by_name("libx265"),
+            VideoCodec::Vp9 => encoder::find_by_name("libvpx-vp9"),
+            VideoCodec::Av1 => encoder::find_by_name("libsvtav1").or_else(|| encoder::find_by_name("libaom-av1")),
+            VideoCodec::ProRes => encoder::find_by_name("prores_ks"),
+            VideoCodec::Dnxhr => encoder::find(codec::Id::DNXHD),
+            VideoCodec::Ffv1 => encoder::find(codec::Id::FFV1),
+        };
+        codec.ok_or_else(|| {
+            PluginError::new(
+                ErrorCode::EncoderUnavailable,
+                format!("No {} encoder is available in this FFmpeg build", self.name()),
+            )
+        })
+    }
+
+    fn default_crf(self) -> f64 {
+        match self {
+            VideoCodec::H264 => X264_DEFAULT_CRF,
+            VideoCodec::Hevc => X265_DEFAULT_CRF,
+            VideoCodec::Vp9 => VP9_DEFAULT_CRF,
+            VideoCodec::Av1 => AV1_DEFAULT_CRF,
+            // Rejected by VideoSettings::from_c: these codecs have no rate control
+            VideoCodec::ProRes | VideoCodec::Dnxhr | VideoCodec::Ffv1 => 0.0,
+        }
+    }
+
+    fn max_crf(self) -> f64 {
+        match self {
+            VideoCodec::H264 | VideoCodec::Hevc => MAX_CRF,
+            VideoCodec::Vp9 => VP9_MAX_CRF,
+            VideoCodec::Av1 => AV1_MAX_CRF,
+            VideoCodec::ProRes | VideoCodec::Dnxhr | VideoCodec::Ffv1 => 0.0,
+        }
+    }
+
+    /// Whether this is an intra-only editing or archival codec, whose bitrate follows from
+    /// the profile (or from lossless coding) rather than from rate control
+    pub fn is_intermediate(self) -> bool {
+        matches!(self, VideoCodec::ProRes | VideoCodec::Dnxhr | VideoCodec::Ffv1)
+    }
+
+    /// Whether the encoder can run a two-pass encode through [`Self::set_pass`]
+    pub fn supports_two_pass(self) -> bool {
+        matches!(self, VideoCodec::H264 | VideoCodec::Hevc)
+    }
+
+    /// Valid levels as level_idc, i.e. ten times the level number; empty if the encoder cannot target one
+    fn levels(self) -> &'static [u32] {
+        match self {
+            VideoCodec::H264 => &H264_LEVELS,
+            VideoCodec::Hevc => &HEVC_LEVELS,
+            VideoCodec::Vp9 | VideoCodec::Av1 | VideoCodec::ProRes | VideoCodec::Dnxhr | VideoCodec::Ffv1 => &[],
+        }
+    }
+
+    /// Whether a stream in `format` can be kept as is when it already meets the request
+    pub fn keeps_format(self, format: Pixel) -> bool {
+        match self {
+            VideoCodec::H264 => H264Profile::High.supports(format),
+            VideoCodec::Hevc | VideoCodec::Vp9 | VideoCodec::Av1 => matches!(
+                format,
+                Pixel::YUV420P | Pixel::YUVJ420P | Pixel::NV12 | Pixel::YUV420P10LE | Pixel::P010LE
+            ),
+            VideoCodec::ProRes => matches!(format, Pixel::YUV422P10LE | Pixel::YUV444P10LE | Pixel::YUVA444P10LE),
+            VideoCodec::Dnxhr => {
+                matches!(format, Pixel::YUV422P | Pixel::YUV422P10LE | Pixel::YUV444P10LE | Pixel::GBRP10LE)
+            }
+            // Whatever FFV1 decodes to is what it was encoded from
+            VideoCodec::Ffv1 => true,
+        }
+    }
+
+    /// Codec tag the stream needs in the output format, or None for the muxer's default
+    pub fn codec_tag(self, muxer: &str) -> Option<u32> {
+        match (self, muxer) {
+            (VideoCodec::Hevc, "mp4" | "mov") => Some(HVC1_TAG),
+            _ => None,
+        }
+    }
+
+    /// Configure pass `pass` (1 or 2) of a two-pass encode that keeps its stats in `stats_file`
+    pub fn set_pass(self, flags: &mut codec::Flags, options: &mut Dictionary, pass: u32, stats_file: &str) {
+        match self {
+            VideoCodec::H264 => {
+                *flags |= if pass == 1 { codec::Flags::PASS1 } else { codec::Flags::PASS2 };
+                options.set("stats", stats_file);
+            }
+            VideoCodec::Hevc => {
+                // libx265 has no AVOption for either, and ignores the pass flags
+                set_encoder_param(options, "x265-params", "pass", &pass.to_string());
+                set_encoder_param(options, "x265-params", "stats", stats_file);
+            }
+            // Rejected by VideoSettings::from_c: libvpx and libaom hand their stats over in memory,
+            // not in a file, SVT-AV1 only does two passes in its own CLI, and the intermediate
+            // codecs have no rate control
+            VideoCodec::Vp9 | VideoCodec::Av1 | VideoCodec::ProRes | VideoCodec::Dnxhr | VideoCodec::Ffv1 => {}
+        }
+    }
+}
+
+/// Append `key=value` to an option such as `x265-params` or `svtav1-params`, which carry the
+/// encoder settings that have no AVOption of their own
+fn set_encoder_param(options: &mut Dictionary, params_option: &str, key: &str, value: &str) {
+    let params = match options.get(params_option) {
+        Some(params) => format!("{}:{}={}", params, key, value),
+        None => format!("{}={}", key, value),
+    };
+    options.set(params_option, &params);
+}
+
+/// Whether `encoder` is SVT-AV1 rather than libaom, whose options differ
+fn is_svt_av1(encoder: Codec) -> bool {
+    encoder.name() == "libsvtav1"
+}
+
+/// How `reencode_video` fits the picture into the requested size
+///
+/// The values are part of the C ABI.
+#[repr(i32)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ResizeMode {
+    /// Scale to exactly the requested size; the sample aspect ratio is set so that
+    /// players still display the source shape
+    #[default]
+    Exact = 0,
+    /// Scale to fit inside the requested size and pad the rest with the pad colour
+    Fit = 1,
+    /// Scale to cover the requested size and crop the overflow, keeping the centre
+    Fill = 2,
+    /// Keep the aspect ratio and shrink until the longer edge is at most the larger of
+    /// the requested width and height; never upscales
+    LongEdge = 3,
+}
+
+impl ResizeMode {
+    pub fn from_c(value: i32) -> Option<Self> {
+        match value {
+            0 => Some(ResizeMode::Exact),
+            1 => Some(ResizeMode::Fit),
+            2 => Some(ResizeMode::Fill),
+            3 => Some(ResizeMode::LongEdge),
+            _ => None,
+        }
+    }
+}
+
+/// How the encoder spends bits
+///
+/// The values are part of the C ABI.
+#[repr(i32)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RateControl {
+    /// Average bitrate: hit the requested bitrate over the whole file
+    #[default]
+    Abr = 0,
+    /// Constant quality: spend what each scene needs for the requested CRF
+    Crf = 1,
+    /// Constant bitrate, for streaming targets with a fixed channel
+    Cbr = 2,
+    /// Average bitrate derived from a file size budget
+    TargetSize = 3,
+}
+
+impl RateControl {
+    pub fn from_c(value: i32) -> Option<Self> {
+        match value {
+            0 => Some(RateControl::Abr),
+            1 => Some(RateControl::Crf),
+            2 => Some(RateControl::Cbr),
+            3 => Some(RateControl::TargetSize),
+            _ => None,
+        }
+    }
+}
+
+/// Speed/compression trade-off, fastest first, named after the x264 and x265 presets
+///
+/// VP9 and AV1 map each preset onto their own speed settings. The values are part of
+/// the C ABI; 0 keeps `medium`, or `veryfast` for AV1.
+#[repr(i32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EncoderPreset {
+    Ultrafast = 1,
+    Superfast = 2,
+    Veryfast = 3,
+    Faster = 4,
+    Fast = 5,
+    Medium = 6,
+    Slow = 7,
+    Slower = 8,
+    Veryslow = 9,
+}
+
+impl EncoderPreset {
+    pub fn from_c(value: i32) -> Option<Self> {
+        match value {
+            1 => Some(EncoderPreset::Ultrafast),
+            2 => Some(EncoderPreset::Superfast),
+            3 => Some(EncoderPreset::Veryfast),
+            4 => Some(EncoderPreset::Faster),
+            5 => Some(EncoderPreset::Fast),
+            6 => Some(EncoderPreset::Medium),
+            7 => Some(EncoderPreset::Slow),
+            8 => Some(EncoderPreset::Slower),
+            9 => Some(EncoderPreset::Veryslow),
+            _ => None,
+        }
+    }
+
+    /// libvpx `deadline` and `cpu-used` closest to this preset
+    fn vp9_speed(self) -> (&'static str, i32) {
+        match self {
+            EncoderPreset::Ultrafast => ("realtime", 8),
+            EncoderPreset::Superfast => ("realtime", 7),
+            EncoderPreset::Veryfast => ("good", 5),
+            EncoderPreset::Faster => ("good", 4),
+            EncoderPreset::Fast => ("good", 3),
+            EncoderPreset::Medium => ("good", 2),
+            EncoderPreset::Slow => ("good", 1),
+            EncoderPreset::Slower => ("good", 0),
+            EncoderPreset::Veryslow => ("best", 0),
+        }
+    }
+
+    /// SVT-AV1 `preset` (0 slowest, 13 fastest) closest to this preset
+    fn svt_av1_preset(self) -> i32 {
+        match self {
+            EncoderPreset::Ultrafast => 12,
+            EncoderPreset::Superfast => 11,
+            EncoderPreset::Veryfast => 10,
+            EncoderPreset::Faster => 9,
+            EncoderPreset::Fast => 8,
+            EncoderPreset::Medium => 6,
+            EncoderPreset::Slow => 4,
+            EncoderPreset::Slower => 2,
+            EncoderPreset::Veryslow => 0,
+        }
+    }
+
+    /// libaom `usage` and `cpu-used` closest to this preset
+    fn aom_speed(self) -> (&'static str, i32) {
+        match self {
+            EncoderPreset::Ultrafast => ("realtime", 8),
+            EncoderPreset::Superfast => ("realtime", 7),
+            EncoderPreset::Veryfast => ("good", 6),
+            EncoderPreset::Faster => ("good", 5),
+            EncoderPreset::Fast => ("good", 4),
+            EncoderPreset::Medium => ("good", 3),
+            EncoderPreset::Slow => ("good", 2),
+            EncoderPreset::Slower => ("good", 1),
+            EncoderPreset::Veryslow => ("good", 0),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            EncoderPreset::Ultrafast => "ultrafast",
+            EncoderPreset::Superfast => "superfast",
+            EncoderPreset::Veryfast => "veryfast",
+            EncoderPreset::Faster => "faster",
+            EncoderPreset::Fast => "fast",
+            EncoderPreset::Medium => "medium",
+            EncoderPreset::Slow => "slow",
+            EncoderPreset::Slower => "slower",
+            EncoderPreset::Veryslow => "veryslow",
+        }
+    }
+}
+
+/// Tuning for the kind of content, named after the x264 tunes; x265 only has `Animation` and
+/// `ZeroLatency`, libvpx only `Film` and `ZeroLatency`, and AV1 output none
+///
+/// The values are part of the C ABI; 0 means no tuning.
+#[repr(i32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EncoderTune {
+    Film = 1,
+    Animation = 2,
+    StillImage = 3,
+    /// No lookahead or B-frames, for live streaming; costs compression
+    ZeroLatency = 4,
+}
+
+impl EncoderTune {
+    pub fn from_c(value: i32) -> Option<Self> {
+        match value {
+            1 => Some(EncoderTune::Film),
+            2 => Some(EncoderTune::Animation),
+            3 => Some(EncoderTune::StillImage),
+            4 => Some(EncoderTune::ZeroLatency),
+            _ => None,
+        }
+    }
+
+    /// Encoder option and value for this tune in `codec`'s encoder, or None if it has no such tune
+    fn option(self, codec: VideoCodec) -> Option<(&'static str, &'static str)> {
+        match (self, codec) {
+            (EncoderTune::Film, VideoCodec::H264) => Some(("tune", "film")),
+            (EncoderTune::Film, VideoCodec::Vp9) => Some(("tune-content", "film")),
+            (EncoderTune::Animation, VideoCodec::H264 | VideoCodec::Hevc) => Some(("tune", "animation")),
+            (EncoderTune::StillImage, VideoCodec::H264) => Some(("tune", "stillimage")),
+            (EncoderTune::ZeroLatency, VideoCodec::H264 | VideoCodec::Hevc) => Some(("tune", "zerolatency")),
+            // No lookahead, which is what holds frames back in libvpx
+            (EncoderTune::ZeroLatency, VideoCodec::Vp9) => Some(("lag-in-frames", "0")),
+            _ => None,
+        }
+    }
+}
+
+/// H.264 profile the output is restricted to
+///
+/// The values are part of the C ABI; 0 lets x264 pick the lowest profile the settings need.
+#[repr(i32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum H264Profile {
+    /// No B-frames or CABAC; for the oldest Android devices and video calls
+    Baseline = 1,
+    Main = 2,
+    High = 3,
+}
+
+impl H264Profile {
+    pub fn from_c(value: i32) -> Option<Self> {
+        match value {
+            1 => Some(H264Profile::Baseline),
+            2 => Some(H264Profile::Main),
+            3 => Some(H264Profile::High),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            H264Profile::Baseline => "baseline",
+            H264Profile::Main => "main",
+            H264Profile::High => "high",
+        }
+    }
+
+    /// Whether a stream whose `profile` field is `profile` (FF_PROFILE_H264_*) plays where this profile does
+    pub fn accepts(self, profile: i32) -> bool {
+        // FF_PROFILE_H264_CONSTRAINED_BASELINE, decodable by every profile
+        const CONSTRAINED_BASELINE: i32 = 66 | (1 << 9);
+        match self {
+            H264Profile::Baseline => matches!(profile, 66 | CONSTRAINED_BASELINE),
+            H264Profile::Main => matches!(profile, CONSTRAINED_BASELINE | 77),
+            H264Profile::High => matches!(profile, CONSTRAINED_BASELINE | 77 | 100),
+        }
+    }
+
+    /// Whether frames in `format` can be encoded in this profile
+    ///
+    /// All three profiles only carry 8-bit 4:2:0.
+    pub fn supports(self, format: Pixel) -> bool {
+        matches!(format, Pixel::YUV420P | Pixel::YUVJ420P | Pixel::NV12)
+    }
+}
+
+/// ProRes profile, from the smallest files to the largest
+///
+/// The values are part of the C ABI; 0 keeps `Hq`, the usual editing default.
+#[repr(i32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProResProfile {
+    Proxy = 1,
+    Lt = 2,
+    Standard = 3,
+    Hq = 4,
+    /// 4:4:4, keeping the alpha channel of sources that have one
+    P4444 = 5,
+    P4444Xq = 6,
+}
+
+impl ProResProfile {
+    pub fn from_c(value: i32) -> Option<Self> {
+        match value {
+            1 => Some(ProResProfile::Proxy),
+            2 => Some(ProResProfile::Lt),
+            3 => Some(ProResProfile::Standard),
+            4 => Some(ProResProfile::Hq),
+            5 => Some(ProResProfile::P4444),
+            6 => Some(ProResProfile::P4444Xq),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            ProResProfile::Proxy => "proxy",
+            ProResProfile::Lt => "lt",
+            ProResProfile::Standard => "standard",
+            ProResProfile::Hq => "hq",
+            ProResProfile::P4444 => "4444",
+            ProResProfile::P4444Xq => "4444xq",
+        }
+    }
+
+    /// FF_PROFILE_PRORES_* value of streams in this profile, which counts from 0
+    fn av_profile(self) -> i32 {
+        self as i32 - 1
+    }
+
+    fn is_444(self) -> bool {
+        matches!(self, ProResProfile::P4444 | ProResProfile::P4444Xq)
+    }
+}
+
+/// DNxHR profile, from the smallest files to the largest
+///
+/// The values are part of the C ABI and match FF_PROFILE_DNXHR_*; 0 keeps `Hq`.
+#[repr(i32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DnxhrProfile {
+    /// Low bandwidth, 8-bit 4:2:2, for offline editing
+    Lb = 1,
+    /// Standard quality, 8-bit 4:2:2
+    Sq = 2,
+    /// High quality, 8-bit 4:2:2
+    Hq = 3,
+    /// High quality, 10-bit 4:2:2
+    Hqx = 4,
+    /// 10-bit 4:4:4, YUV or RGB
+    P444 = 5,
+}
+
+impl DnxhrProfile {
+    pub fn from_c(value: i32) -> Option<Self> {
+        match value {
+            1 => Some(DnxhrProfile::Lb),
+            2 => Some(DnxhrProfile::Sq),
+            3 => Some(DnxhrProfile::Hq),
+            4 => Some(DnxhrProfile::Hqx),
+            5 => Some(DnxhrProfile::P444),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            DnxhrProfile::Lb => "dnxhr_lb",
+            DnxhrProfile::Sq => "dnxhr_sq",
+            DnxhrProfile::Hq => "dnxhr_hq",
+            DnxhrProfile::Hqx => "dnxhr_hqx",
+            DnxhrProfile::P444 => "dnxhr_444",
+        }
+    }
+}
+
+const MAX_B_FRAMES: i32 = 16;
+
+// H.264 levels (Annex A) as level_idc, i.e. ten times the level number
+const H264_LEVELS: [u32; 19] = [10, 11, 12, 13, 20, 21, 22, 30, 31, 32, 40, 41, 42, 50, 51, 52, 60, 61, 62];
+// HEVC levels (Annex A), in the same form; the bitstream's general_level_idc is three times this
+const HEVC_LEVELS: [u32; 13] = [10, 20, 21, 30, 31, 40, 41, 50, 51, 52, 60, 61, 62];
+
+/// C-compatible video encoding options
+///
+/// Zero or negative numeric fields keep the default.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct CVideoOptions {
+    pub resize_mode: i32,  // ResizeMode
+    pub pad_color: u32,    // 0xRRGGBB, used by ResizeMode::Fit
+    pub rate_control: i32, // RateControl
+    pub crf: f64,          // 0-51 (0-63 for VP9 and AV1), used by RateControl::Crf (default 23 for H.264, 28 for HEVC, 31 for VP9, 35 for AV1)
+    pub max_bitrate: i32,  // bits per second, VBV cap for ABR and CRF; constrained quality for VP9 and AV1 CRF
+    pub buffer_size: i32,  // bits, VBV buffer (default: 2 seconds at the cap, 1 second for CBR)
+    pub target_size: i64,  // bytes, required by RateControl::TargetSize
+    pub passes: i32,       // 2 for a two-pass encode (ABR and target size only); 0 is two passes for a target size where supported
+    pub preset: i32,       // EncoderPreset, or 0 for medium (veryfast for AV1)
+    pub tune: i32,         // EncoderTune, or 0 for none
+    pub profile: i32,      // H264Profile (H.264 only), or 0 for automatic
+    pub level: i32,        // level_idc such as 31 for level 3.1, or 0 for automatic
+    pub gop_frames: i32,   // maximum keyframe interval in frames
+    pub gop_seconds: f64,  // maximum keyframe interval in seconds; exclusive with gop_frames
+    pub closed_gop: bool,
+    pub disable_scene_cut: bool, // with a GOP length, gives keyframes at a fixed interval
+    pub b_frames: i32,     // 1-16, -1 for none, 0 for the preset's default
+    pub keyframe_times: *const f64, // seconds from the start of the output where a keyframe is forced
+    pub keyframe_count: i32,
+    pub skip_if_compliant: bool, // remux or copy inputs that already meet the request
+    pub codec: i32,        // VideoCodec
+    pub tile_columns: i32, // VP9 tile columns, a power of two up to 64, or 0 for libvpx's default
+    pub tile_rows: i32,    // VP9 tile rows, a power of two up to 4, or 0 for libvpx's default
+    pub disable_row_mt: bool, // VP9 row-based multithreading is on unless this is set
+    pub prores_profile: i32, // ProResProfile (ProRes only), or 0 for HQ
+    pub dnxhr_profile: i32,  // DnxhrProfile (DNxHR only), or 0 for HQ
+    pub ffv1_slices: i32,    // FFV1 slices per frame (4, 6, 9, 12, 16, 24 or 30), or 0 for ffv1's default
+}
+
+/// How video streams are re-encoded
+#[derive(Clone, Debug, Default)]
+pub struct VideoSettings {
+    pub codec: VideoCodec,
+    pub resize_mode: ResizeMode,
+    pub pad_color: u32, // 0xRRGGBB
+    pub rate_control: RateControl,
+    pub crf: Option<f64>,
+    pub max_bit_rate: Option<u64>, // checked against the bitrate limits by the plugin
+    pub buffer_size: Option<usize>,
+    pub target_size: Option<u64>, // bytes; Some exactly when rate_control is TargetSize
+    pub two_pass: bool,
+    pub preset: Option<EncoderPreset>,
+    pub tune: Option<EncoderTune>,
+    pub profile: Option<H264Profile>,
+    pub level: Option<u32>, // level_idc
+    pub gop_frames: Option<u32>,
+    pub gop_seconds: Option<f64>,
+    pub closed_gop: bool,
+    pub disable_scene_cut: bool,
+    pub b_frames: Option<u32>,
+    pub keyframe_times: Vec<f64>, // sorted, in seconds
+    pub skip_if_compliant: bool,
+    pub tile_columns_log2: Option<u32>,
+    pub tile_rows_log2: Option<u32>,
+    pub disable_row_mt: bool,
+    pub prores_profile: Option<ProResProfile>,
+    pub dnxhr_profile: Option<DnxhrProfile>,
+    pub ffv1_slices: Option<u32>,
+}
+
+impl VideoSettings {
+    /// # Safety
+    ///
+    /// `options.keyframe_times` must be null or point to `options.keyframe_count` values.
+    pub unsafe fn from_c(options: &CVideoOptions) -> Result<Self, PluginError> {
+        let codec = VideoCodec::from_c(options.codec).ok_or_else(|| {
+            PluginError::new(ErrorCode::InvalidArgument, format!("Unknown video codec {}", options.codec))
+        })?;
+        let resize_mode = ResizeMode::from_c(options.resize_mode).ok_or_else(|| {
+            PluginError::new(ErrorCode::InvalidArgument, format!("Unknown resize mode {}", options.resize_mode))
+        })?;
+        if options.pad_color > 0xFF_FF_FF {
+            return Err(PluginError::new(
+                ErrorCode::InvalidArgument,
+                format!("Pad colour {:#x} is not a 0xRRGGBB value", options.pad_color),
+            ));
+        }
+        let rate_control = RateControl::from_c(options.rate_control).ok_or_else(|| {
+            PluginError::new(ErrorCode::InvalidArgument, format!("Unknown rate control mode {}", options.rate_control))
+        })?;
+        let rate_control_set = rate_control != RateControl::Abr
+            || options.crf > 0.0
+            || options.max_bitrate > 0
+            || options.buffer_size > 0
+            || options.passes > 1;
+        if codec.is_intermediate() && rate_control_set {
+            return Err(PluginError::new(
+                ErrorCode::InvalidArgument,
+                format!("{} output takes no rate control options; its bitrate follows from the profile", codec.name()),
+            ));
+        }
+        if !options.crf.is_finite() || options.crf > codec.max_crf() {
+            return Err(PluginError::new(
+                ErrorCode::InvalidArgument,
+                format!("CRF {} must be between 0 and {} for {}", options.crf, codec.max_crf(), codec.name()),
+            ));
+        }
+        let target_size = match rate_control {
+            RateControl::TargetSize if options.target_size <= 0 => {
+                return Err(PluginError::new(
+                    ErrorCode::InvalidArgument,
+                    format!("Target size {} must be a positive number of bytes", options.target_size),
+                ));
+            }
+            RateControl::TargetSize => Some(options.target_size as u64),
+            _ => None,
+        };
+        let two_pass = match options.passes {
+            // A target size is hit more closely once the analysis pass has seen the whole input
+            0 => rate_control == RateControl::TargetSize && codec.supports_two_pass(),
+            passes if passes <= 1 => false,
+            2 if matches!(rate_control, RateControl::Abr | RateControl::TargetSize) => true,
+            2 => {
+                return Err(PluginError::new(
+                    ErrorCode::InvalidArgument,
+                    format!("Two-pass encoding needs ABR or target size rate control, not {:?}", rate_control),
+                ));
+            }
+            passes => {
+                return Err(PluginError::new(ErrorCode::InvalidArgument, format!("Unsupported number of passes {}", passes)));
+            }
+        };
+        if two_pass && !codec.supports_two_pass() {
+            return Err(PluginError::new(
+                ErrorCode::InvalidArgument,
+                format!("Two-pass encoding is not supported for {}", codec.name()),
+            ));
+        }
+        let preset = optional_enum(options.preset, EncoderPreset::from_c, "preset")?;
+        if preset.is_some() && codec.is_intermediate() {
+            return Err(PluginError::new(ErrorCode::InvalidArgument, format!("{} output takes no preset", codec.name())));
+        }
+        let tune = optional_enum(options.tune, EncoderTune::from_c, "tune")?;
+        if let Some(tune) = tune.filter(|tune| tune.option(codec).is_none()) {
+            return Err(PluginError::new(
+                ErrorCode::InvalidArgument,
+                format!("{} output has no {:?} tune", codec.name(), tune),
+            ));
+        }
+        let profile = optional_enum(options.profile, H264Profile::from_c, "H.264 profile")?;
+        if profile.is_some() && codec != VideoCodec::H264 {
+            return Err(PluginError::new(
+                ErrorCode::InvalidArgument,
+                format!("An H.264 profile cannot be used for {} output", codec.name()),
+            ));
+        }
+        let prores_profile = optional_enum(options.prores_profile, ProResProfile::from_c, "ProRes profile")?;
+        if prores_profile.is_some() && codec != VideoCodec::ProRes {
+            return Err(PluginError::new(
+                ErrorCode::InvalidArgument,
+                format!("A ProRes profile cannot be used for {} output", codec.name()),
+            ));
+        }
+        let dnxhr_profile = optional_enum(options.dnxhr_profile, DnxhrProfile::from_c, "DNxHR profile")?;
+        if dnxhr_profile.is_some() && codec != VideoCodec::Dnxhr {
+            return Err(PluginError::new(
+                ErrorCode::InvalidArgument,
+                format!("A DNxHR profile cannot be used for {} output", codec.name()),
+            ));
+        }
+        let ffv1_slices = match options.ffv1_slices {
+            0 => None,
+            _ if codec != VideoCodec::Ffv1 => {
+                return Err(PluginError::new(ErrorCode::InvalidArgument, "Slices are only set for FFV1 output"));
+            }
+            slices if slices > 0 && FFV1_SLICES.contains(&(slices as u32)) => Some(slices as u32),
+            slices => {
+                return Err(PluginError::new(
+                    ErrorCode::InvalidArgument,
+                    format!("FFV1 slice count {} must be one of {:?}", slices, FFV1_SLICES),
+                ));
+            }
+        };
+        let level = match options.level {
+            0 => None,
+            _ if codec.levels().is_empty() => {
+                return Err(PluginError::new(
+                    ErrorCode::InvalidArgument,
+                    format!("{} output cannot target a level", codec.name()),
+                ));
+            }
+            level if level > 0 && codec.levels().contains(&(level as u32)) => Some(level as u32),
+            level => {
+                return Err(PluginError::new(
+                    ErrorCode::InvalidArgument,
+                    format!("Unknown {} level {}; pass level_idc, such as 31 for 3.1", codec.name(), level),
+                ));
+            }
+        };
+        if options.gop_frames < 0 || !options.gop_seconds.is_finite() || options.gop_seconds < 0.0 {
+            return Err(PluginError::new(ErrorCode::InvalidArgument, "GOP length cannot be negative"));
+        }
+        if options.gop_frames > 0 && options.gop_seconds > 0.0 {
+            return Err(PluginError::new(ErrorCode::InvalidArgument, "Pass the GOP length in frames or in seconds, not both"));
+        }
+        let b_frames = match options.b_frames {
+            0 => None,
+            -1 => Some(0),
+            b_frames if (1..=MAX_B_FRAMES).contains(&b_frames) => Some(b_frames as u32),
+            b_frames => {
+                return Err(PluginError::new(
+                    ErrorCode::InvalidArgument,
+                    format!("B-frame count {} must be between 1 and {}, or -1 for none", b_frames, MAX_B_FRAMES),
+                ));
+            }
+        };
+        if matches!(codec, VideoCodec::Vp9 | VideoCodec::Av1) && b_frames.is_some_and(|b_frames| b_frames > 0) {
+            return Err(PluginError::new(ErrorCode::InvalidArgument, format!("{} has no B-frames", codec.name())));
+        }
+        let has_gop_options = options.gop_frames > 0
+            || options.gop_seconds > 0.0
+            || options.closed_gop
+            || options.disable_scene_cut
+            || b_frames.is_some()
+            || options.keyframe_count > 0;
+        if codec.is_intermediate() && has_gop_options {
+            return Err(PluginError::new(
+                ErrorCode::InvalidArgument,
+                format!("Every {} frame is a keyframe, so it takes no GOP, B-frame or keyframe options", codec.name()),
+            ));
+        }
+        let tiles_log2 = |count: i32, max: i32, what: &str| -> Result<Option<u32>, PluginError> {
+            match count {
+                0 => Ok(None),
+                _ if codec != VideoCodec::Vp9 => Err(PluginError::new(
+                    ErrorCode::InvalidArgument,
+                    format!("Tile {} are only used for VP9 output", what),
+                )),
+                count if count > 0 && count <= max && (count as u32).is_power_of_two() => Ok(Some(count.trailing_zeros())),
+                count => Err(PluginError::new(
+                    ErrorCode::InvalidArgument,
+                    format!("{} tile {} must be a power of two up to {}", count, what, max),
+                )),
+            }
+        };
+        let tile_columns_log2 = tiles_log2(options.tile_columns, VP9_MAX_TILE_COLUMNS, "columns")?;
+        let tile_rows_log2 = tiles_log2(options.tile_rows, VP9_MAX_TILE_ROWS, "rows")?;
+        let mut keyframe_times = match (options.keyframe_times.is_null(), options.keyframe_count) {
+            (_, count) if count <= 0 => Vec::new(),
+            (true, _) => {
+                return Err(PluginError::new(ErrorCode::InvalidArgument, "Keyframe times are null but their count is not 0"));
+            }
+            (false, count) => std::slice::from_raw_parts(options.keyframe_times, count as usize).to_vec(),
+        };
+        if keyframe_times.iter().any(|time| !time.is_finite() || *time < 0.0) {
+            return Err(PluginError::new(ErrorCode::InvalidArgument, "Keyframe times must be non-negative seconds"));
+        }
+        keyframe_times.sort_by(f64::total_cmp);
+        keyframe_times.dedup();
+        Ok(Self {
+            codec,
+            resize_mode,
+            pad_color: options.pad_color,
+            rate_control,
+            crf: if options.crf > 0.0 { Some(options.crf) } else { None },
+            max_bit_rate: if options.max_bitrate > 0 { Some(options.max_bitrate as u64) } else { None },
+            buffer_size: if options.buffer_size > 0 { Some(options.buffer_size as usize) } else { None },
+            target_size,
+            two_pass,
+            preset,
+            tune,
+            profile,
+            level,
+            gop_frames: if options.gop_frames > 0 { Some(options.gop_frames as u32) } else { None },
+            gop_seconds: if options.gop_seconds > 0.0 { Some(options.gop_seconds) } else { None },
+            closed_gop: options.closed_gop,
+            disable_scene_cut: options.disable_scene_cut,
+            b_frames,
+            keyframe_times,
+            skip_if_compliant: options.skip_if_compliant,
+            tile_columns_log2,
+            tile_rows_log2,
+            disable_row_mt: options.disable_row_mt,
+            prores_profile,
+            dnxhr_profile,
+            ffv1_slices,
+        })
+    }
+
+    /// Output pixel formats to try, best first, for frames decoded in `source_format`
+    ///
+    /// The delivery codecs take 4:2:0, with HEVC, VP9 and AV1 keeping 10-bit sources at 10
+    /// bits; encoders that cannot take NV12 skip it. ProRes and DNxHR formats follow from the
+    /// profile, and FFV1 keeps the source's depth, chroma and alpha so that it stays lossless.
+    pub fn output_formats(&self, source_format: Pixel) -> Vec<Pixel> {
+        let deep = bit_depth(source_format) > 8;
+        let mut formats = Vec::new();
+        match self.codec {
+            VideoCodec::H264 | VideoCodec::Hevc | VideoCodec::Vp9 | VideoCodec::Av1 => {
+                if self.codec != VideoCodec::H264 && deep {
+                    formats.push(Pixel::YUV420P10LE);
+                }
+                formats.extend([Pixel::YUV420P, Pixel::NV12, source_format]);
+            }
+            VideoCodec::ProRes => {
+                let profile = self.prores_profile.unwrap_or(ProResProfile::Hq);
+                if !profile.is_444() {
+                    formats.push(Pixel::YUV422P10LE);
+                } else if has_alpha(source_format) {
+                    formats.extend([Pixel::YUVA444P10LE, Pixel::YUV444P10LE]);
+                } else {
+                    formats.push(Pixel::YUV444P10LE);
+                }
+            }
+            VideoCodec::Dnxhr => match self.dnxhr_profile.unwrap_or(DnxhrProfile::Hq) {
+                DnxhrProfile::Lb | DnxhrProfile::Sq | DnxhrProfile::Hq => formats.push(Pixel::YUV422P),
+                DnxhrProfile::Hqx => formats.push(Pixel::YUV422P10LE),
+                DnxhrProfile::P444 if is_rgb(source_format) => formats.extend([Pixel::GBRP10LE, Pixel::YUV444P10LE]),
+                DnxhrProfile::P444 => formats.extend([Pixel::YUV444P10LE, Pixel::GBRP10LE]),
+            },
+            VideoCodec::Ffv1 => {
+                formats.push(source_format);
+                formats.extend(planar_equivalent(source_format));
+                formats.push(if deep { Pixel::YUV444P16LE } else { Pixel::YUV444P });
+            }
+        }
+        formats
+    }
+
+    /// FF_PROFILE_* value a stream must have to meet the requested ProRes or DNxHR profile
+    pub fn av_profile(&self) -> Option<i32> {
+        match self.codec {
+            VideoCodec::ProRes => Some(self.prores_profile.unwrap_or(ProResProfile::Hq).av_profile()),
+            VideoCodec::Dnxhr => Some(self.dnxhr_profile.unwrap_or(DnxhrProfile::Hq) as i32),
+            _ => None,
+        }
+    }
+
+    /// Whether any keyframe or B-frame option is set, which only a re-encode can guarantee
+    pub fn has_gop_options(&self) -> bool {
+        self.gop_frames.is_some()
+            || self.gop_seconds.is_some()
+            || self.closed_gop
+            || self.disable_scene_cut
+            || self.b_frames.is_some()
+            || !self.keyframe_times.is_empty()
+    }
+
+    /// Configure keyframe placement and B-frames before the encoder is opened
+    ///
+    /// The closed GOP flag is left to the caller, which owns the encoder flags.
+    ///
+    /// # Arguments
+    ///
+    /// * `frame_rate` - Frame rate of the output, needed for a GOP length in seconds
+    pub fn apply_gop(
+        &self,
+        encoder: &mut encoder::video::Video,
+        options: &mut Dictionary,
+        frame_rate: Option<Rational>,
+    ) -> Result<(), PluginError> {
+        if self.codec.is_intermediate() {
+            // Intra-only anyway; for FFV1 this also resets the coder contexts on every frame,
+            // so a damaged frame in an archive does not take the rest of the GOP with it
+            encoder.set_gop(1);
+            return Ok(());
+        }
+        let gop_frames = match (self.gop_frames, self.gop_seconds) {
+            (Some(frames), _) => Some(frames),
+            (None, Some(seconds)) => {
+                let frame_rate = frame_rate
+                    .filter(|rate| rate.numerator() > 0 && rate.denominator() > 0)
+                    .ok_or_else(|| {
+                        PluginError::new(
+                            ErrorCode::UnsupportedFormat,
+                            "The input frame rate is unknown, so the GOP length must be given in frames",
+                        )
+                    })?;
+                Some(((seconds * f64::from(frame_rate)).round() as u32).max(1))
+            }
+            (None, None) => None,
+        };
+        if let Some(gop_frames) = gop_frames {
+            encoder.set_gop(gop_frames);
+        }
+        if self.disable_scene_cut {
+            match self.codec {
+                VideoCodec::H264 => options.set("sc_threshold", "0"),
+                VideoCodec::Hevc => set_encoder_param(options, "x265-params", "scenecut", "0"),
+                VideoCodec::Av1 if encoder.codec().is_some_and(is_svt_av1) => {
+                    set_encoder_param(options, "svtav1-params", "scd", "0")
+                }
+                // libvpx and libaom only place keyframes at a fixed interval when the minimum
+                // equals the maximum
+                VideoCodec::Vp9 | VideoCodec::Av1 => {
+                    if let Some(gop_frames) = gop_frames {
+                        unsafe {
+                            (*encoder.as_mut_ptr()).keyint_min = gop_frames as c_int;
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        if let Some(b_frames) = self.b_frames {
+            encoder.set_max_b_frames(b_frames as usize);
+        }
+        if !self.keyframe_times.is_empty() && matches!(self.codec, VideoCodec::H264 | VideoCodec::Hevc) {
+            // Forced keyframes are IDR frames, so that every one of them starts a segment;
+            // every VP9 and AV1 keyframe already does
+            options.set("forced-idr", "1");
+        }
+        Ok(())
+    }
+
+    /// Options `encoder` is opened with: preset, tune, profile and level, threading for VP9
+    /// and libaom, and the profile or slicing of the intermediate codecs
+    pub fn encoder_options(&self, encoder: Codec) -> Dictionary<'static> {
+        let mut options = Dictionary::new();
+        let default_preset = if self.codec == VideoCodec::Av1 { AV1_DEFAULT_PRESET } else { EncoderPreset::Medium };
+        let preset = self.preset.unwrap_or(default_preset);
+        match self.codec {
+            VideoCodec::H264 | VideoCodec::Hevc => options.set("preset", preset.name()),
+            VideoCodec::Vp9 => {
+                let (deadline, cpu_used) = preset.vp9_speed();
+                options.set("deadline", deadline);
+                options.set("cpu-used", &cpu_used.to_string());
+                options.set("row-mt", if self.disable_row_mt { "0" } else { "1" });
+                if let Some(tile_columns) = self.tile_columns_log2 {
+                    options.set("tile-columns", &tile_columns.to_string());
+                }
+                if let Some(tile_rows) = self.tile_rows_log2 {
+                    options.set("tile-rows", &tile_rows.to_string());
+                }
+            }
+            VideoCodec::Av1 if is_svt_av1(encoder) => options.set("preset", &preset.svt_av1_preset().to_string()),
+            VideoCodec::Av1 => {
+                let (usage, cpu_used) = preset.aom_speed();
+                options.set("usage", usage);
+                options.set("cpu-used", &cpu_used.to_string());
+                options.set("row-mt", "1");
+            }
+            VideoCodec::ProRes => {
+                options.set("profile", self.prores_profile.unwrap_or(ProResProfile::Hq).name());
+                // Apple's vendor ID, which Final Cut Pro and QuickTime expect in the stream
+                options.set("vendor", "apl0");
+            }
+            VideoCodec::Dnxhr => options.set("profile", self.dnxhr_profile.unwrap_or(DnxhrProfile::Hq).name()),
+            VideoCodec::Ffv1 => {
+                // Version 3 adds slices and per-slice CRCs, which archives rely on to find damage
+                options.set("level", "3");
+                options.set("slicecrc", "1");
+                if let Some(slices) = self.ffv1_slices {
+                    options.set("slices", &slices.to_string());
+                }
+            }
+        }
+        if let Some((key, value)) = self.tune.and_then(|tune| tune.option(self.codec)) {
+            options.set(key, value);
+        }
+        if let Some(profile) = self.profile {
+            options.set("profile", profile.name());
+        }
+        if let Some(level) = self.level {
+            let level = format!("{}.{}", level / 10, level % 10);
+            match self.codec {
+                VideoCodec::H264 => options.set("level", &level),
+                VideoCodec::Hevc => set_encoder_param(&mut options, "x265-params", "level-idc", &level),
+                _ => {}
+            }
+        }
+        options
+    }
+
+    /// Configure the encoder for the rate control mode before it is opened
+    ///
+    /// # Arguments
+    ///
+    /// * `encoder` - Unopened encoder
+    /// * `options` - Options the encoder will be opened with
+    /// * `bit_rate` - Validated target bitrate, used by ABR, CBR and target size
+    /// * `max_bit_rate` - Validated VBV cap, used by all modes but CBR
+    /// * `muxer` - Name of the output format; strict CBR is only signalled where filler data is allowed
+    pub fn apply_rate_control(
+        &self,
+        encoder: &mut encoder::video::Video,
+        options: &mut Dictionary,
+        bit_rate: usize,
+        max_bit_rate: Option<usize>,
+        muxer: &str,
+    ) {
+        if self.codec.is_intermediate() {
+            // The profile, or lossless coding, fixes the bitrate
+            return;
+        }
+        let set_buffer_size = |encoder: &mut encoder::video::Video, buffer_size: usize| unsafe {
+            (*encoder.as_mut_ptr()).rc_buffer_size = buffer_size.min(i32::MAX as usize) as c_int;
+        };
+        match self.rate_control {
+            RateControl::Abr | RateControl::Crf | RateControl::TargetSize => {
+                if self.rate_control == RateControl::Crf {
+                    options.set("crf", &format!("{}", self.crf.unwrap_or(self.codec.default_crf())));
+                    match self.codec {
+                        // SVT-AV1 drops the CRF for VBR when given a bitrate; the cap below
+                        // still applies as capped CRF
+                        VideoCodec::Av1 if encoder.codec().is_some_and(is_svt_av1) => encoder.set_bit_rate(0),
+                        // libvpx and libaom treat a bitrate next to the CRF as a cap (constrained
+                        // quality); without one it has to be 0 for constant quality
+                        VideoCodec::Vp9 | VideoCodec::Av1 => encoder.set_bit_rate(max_bit_rate.unwrap_or(0)),
+                        _ => {}
+                    }
+                } else {
+                    encoder.set_bit_rate(bit_rate);
+                }
+                if let Some(max_bit_rate) = max_bit_rate {
+                    encoder.set_max_bit_rate(max_bit_rate);
+                    set_buffer_size(encoder, self.buffer_size.unwrap_or(max_bit_rate * 2));
+                }
+            }
+            RateControl::Cbr => {
+                encoder.set_bit_rate(bit_rate);
+                encoder.set_max_bit_rate(bit_rate);
+                unsafe {
+                    (*encoder.as_mut_ptr()).rc_min_rate = bit_rate as i64;
+                }
+                set_buffer_size(encoder, self.buffer_size.unwrap_or(bit_rate));
+                // MP4 and MOV get the same rate bounds, signalled as VBR without filler
+                let filler = !ISO_BMFF_MUXERS.contains(&muxer);
+                match self.codec {
+                    VideoCodec::H264 => options.set("nal-hrd", if filler { "cbr" } else { "vbr" }),
+                    VideoCodec::Hevc => {
+                        set_encoder_param(options, "x265-params", "hrd", "1");
+                        if filler {
+                            set_encoder_param(options, "x265-params", "strict-cbr", "1");
+                        }
+                    }
+                    // libvpx and the AV1 encoders switch to CBR when the minimum and maximum
+                    // rates match the bitrate
+                    VideoCodec::Vp9 | VideoCodec::Av1 => {}
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+/// Rectangle in pixels
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Where the source picture ends up in the encoded frame
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ResizeLayout {
+    /// Part of the source to keep, or None for all of it
+    pub crop: Option<Rect>,
+    /// Where the scaled picture sits in the encoded frame; smaller than the frame when padded
+    pub picture: Rect,
+    /// Size of the encoded frame, always even so that 4:2:0 encoders accept it
+    pub width: u32,
+    pub height: u32,
+    pub sample_aspect_ratio: Rational,
+}
+
+impl ResizeLayout {
+    /// # Arguments
+    ///
+    /// * `source_width`, `source_height` - Decoded frame size
+    /// * `source_aspect_ratio` - Sample aspect ratio of the source; 0 or invalid means square pixels
+    /// * `source_format` - Decoded pixel format, which the crop offsets are aligned to
+    /// * `target_width`, `target_height` - Requested size; a 0 dimension is derived from the
+    ///   other one using the display aspect ratio, and both 0 keep the display size
+    /// * `mode` - How the source is fitted into the requested size
+    pub fn new(
+        source_width: u32,
+        source_height: u32,
+        source_aspect_ratio: Rational,
+        source_format: Pixel,
+        target_width: u32,
+        target_height: u32,
+        mode: ResizeMode,
+    ) -> Self {
+        let source_aspect_ratio = if source_aspect_ratio.numerator() > 0 && source_aspect_ratio.denominator() > 0 {
+            source_aspect_ratio
+        } else {
+            Rational(1, 1)
+        };
+        // Source size in square pixels, which is how players show it
+        let display_width = source_width as f64 * f64::from(source_aspect_ratio);
+        let display_height = source_height as f64;
+        let (box_width, box_height) = match (target_width, target_height) {
+            (0, 0) => (display_width, display_height),
+            (width, 0) => (width as f64, width as f64 * display_height / display_width),
+            (0, height) => (height as f64 * display_width / display_height, height as f64),
+            (width, height) => (width as f64, height as f64),
+        };
+
+        match mode {
+            ResizeMode::Exact => {
+                let (width, height) = (even_floor(box_width), even_floor(box_height));
+                // Stretch the pixels back: display aspect ratio of the source over the frame's
+                let sample_aspect_ratio = reduced(
+                    source_width as i64 * source_aspect_ratio.numerator() as i64 * height as i64,
+                    source_height as i64 * source_aspect_ratio.denominator() as i64 * width as i64,
+                );
+                Self::unpadded(width, height, None, sample_aspect_ratio)
+            }
+            ResizeMode::Fit => {
+                let (width, height) = (even_floor(box_width), even_floor(box_height));
+                let scale = (width as f64 / display_width).min(height as f64 / display_height);
+                let picture_width = even_round(display_width * scale).min(width);
+                let picture_height = even_round(display_height * scale).min(height);
+                Self {
+                    crop: None,
+                    picture: Rect {
+                        // Even offsets keep the chroma planes aligned with the luma
+                        x: (width - picture_width) / 4 * 2,
+                        y: (height - picture_height) / 4 * 2,
+                        width: picture_width,
+                        height: picture_height,
+                    },
+                    width,
+                    height,
+                    sample_aspect_ratio: Rational(1, 1),
+                }
+            }
+            ResizeMode::Fill => {
+                let (width, height) = (even_floor(box_width), even_floor(box_height));
+                let scale = (width as f64 / display_width).max(height as f64 / display_height);
+                let crop_width = ((width as f64 / scale / f64::from(source_aspect_ratio)).round() as u32).clamp(1, source_width);
+                let crop_height = ((height as f64 / scale).round() as u32).clamp(1, source_height);
+                let crop = if crop_width == source_width && crop_height == source_height {
+                    None
+                } else {
+                    let (align_x, align_y) = source_format
+                        .descriptor()
+                        .map(|descriptor| (1 << descriptor.log2_chroma_w(), 1 << descriptor.log2_chroma_h()))
+                        .unwrap_or((2, 2));
+                    Some(Rect {
+                        x: (source_width - crop_width) / 2 / align_x * align_x,
+                        y: (source_height - crop_height) / 2 / align_y * align_y,
+                        width: crop_width,
+                        height: crop_height,
+                    })
+                };
+                Self::unpadded(width, height, crop, Rational(1, 1))
+            }
+            ResizeMode::LongEdge => {
+                let limit = target_width.max(target_height);
+                let scale = if limit == 0 {
+                    1.0
+                } else {
+                    (limit as f64 / display_width.max(display_height)).min(1.0)
+                };
+                Self::unpadded(even_floor(display_width * scale), even_floor(display_height * scale), None, Rational(1, 1))
+            }
+        }
+    }
+
+    fn unpadded(width: u32, height: u32, crop: Option<Rect>, sample_aspect_ratio: Rational) -> Self {
+        Self {
+            crop,
+            picture: Rect { x: 0, y: 0, width, height },
+            width,
+            height,
+            sample_aspect_ratio,
+        }
+    }
+
+    pub fn is_padded(&self) -> bool {
+        self.picture.width != self.width || self.picture.height != self.height
+    }
+
+    /// Size the scaler reads from the source
+    pub fn source_size(&self, source_width: u32, source_height: u32) -> (u32, u32) {
+        match self.crop {
+            Some(crop) => (crop.width, crop.height),
+            None => (source_width, source_height),
+        }
+    }
+}
+
+// C enum fields where 0 means "not set"
+fn optional_enum<T>(value: i32, from_c: fn(i32) -> Option<T>, what: &str) -> Result<Option<T>, PluginError> {
+    if value == 0 {
+        return Ok(None);
+    }
+    from_c(value)
+        .map(Some)
+        .ok_or_else(|| PluginError::new(ErrorCode::InvalidArgument, format!("Unknown {} {}", what, value)))
+}
+
+// Nearest even value that is not larger, and at least 2
+fn even_floor(value: f64) -> u32 {
+    ((value / 2.0).floor() as u32 * 2).max(2)
+}
+
+fn even_round(value: f64) -> u32 {
+    ((value / 2.0).round() as u32 * 2).max(2)
+}
+
+fn reduced(numerator: i64, denominator: i64) -> Rational {
+    let (mut num, mut den) = (0, 0);
+    unsafe {
+        ffi::av_reduce(&mut num, &mut den, numerator, denominator, i32::MAX as i64);
+    }
+    Rational(num, den)
+}
+
+/// Size of a frame once it is turned upright
+///
+/// # Arguments
+///
+/// * `rotation` - Clockwise rotation in degrees, as returned by [`crate::probe::rotation`]
+pub fn display_size(width: u32, height: u32, rotation: i32) -> (u32, u32) {
+    if rotation % 180 == 90 { (height, width) } else { (width, height) }
+}
+
+/// Turns decoded frames upright, following the stream's display matrix
+///
+/// Uses libavfilter's transpose, hflip and vflip, the same filters the ffmpeg
+/// command line inserts for autorotation. The output carries no rotation
+/// metadata, so the pixels themselves have to be upright.
+pub struct FrameRotator {
+    graph: filter::Graph,
+}
+
+impl FrameRotator {
+    /// # Arguments
+    ///
+    /// * `format`, `width`, `height`, `time_base`, `aspect_ratio` - Describe the decoded frames
+    /// * `rotation` - Clockwise rotation in degrees, as returned by [`crate::probe::rotation`]
+    ///
+    /// # Returns
+    ///
+    /// None when the frames are already upright
+    pub fn new(
+        format: Pixel,
+        width: u32,
+        height: u32,
+        time_base: Rational,
+        aspect_ratio: Rational,
+        rotation: i32,
+    ) -> Result<Option<Self>, ffmpeg_next::Error> {
+        let filters = match rotation {
+            90 => "transpose=clock",
+            180 => "hflip,vflip",
+            270 => "transpose=cclock",
+            _ => return Ok(None),
+        };
+        let aspect_ratio = if aspect_ratio.numerator() > 0 && aspect_ratio.denominator() > 0 {
+            aspect_ratio
+        } else {
+            Rational(1, 1)
+        };
+
+        let mut graph = filter::Graph::new();
+        let buffer = filter::find("buffer").ok_or(ffmpeg_next::Error::FilterNotFound)?;
+        let buffersink = filter::find("buffersink").ok_or(ffmpeg_next::Error::FilterNotFound)?;
+        let args = format!(
+            "video_size={}x{}:pix_fmt={}:time_base={}/{}:pixel_aspect={}/{}",
+            width,
+            height,
+            ffi::AVPixelFormat::from(format) as i32,
+            time_base.numerator(),
+            time_base.denominator(),
+            aspect_ratio.numerator(),
+            aspect_ratio.denominator(),
+        );
+        graph.add(&buffer, "in", &args)?;
+        graph.add(&buffersink, "out", "")?;
+        graph.output("in", 0)?.input("out", 0)?.parse(filters)?;
+        graph.validate()?;
+        Ok(Some(Self { graph }))
+    }
+
+    pub fn rotate(&mut self, frame: &frame::Video) -> Result<frame::Video, ffmpeg_next::Error> {
+        self.graph
+            .get("in")
+            .ok_or(ffmpeg_next::Error::FilterNotFound)?
+            .source()
+            .add(frame)?;
+        let mut rotated = frame::Video::empty();
+        self.graph
+            .get("out")
+            .ok_or(ffmpeg_next::Error::FilterNotFound)?
+            .sink()
+            .frame(&mut rotated)?;
+        Ok(rotated)
+    }
+}
+
+/// Reference `region` of `frame` without copying its data
+///
+/// Writing through the returned frame writes into `frame`, which is how the scaler
+/// draws into the middle of a padded picture.
+pub fn crop_frame(frame: &frame::Video, region: Rect) -> Result<frame::Video, ffmpeg_next::Error> {
+    let mut view = frame::Video::empty();
+    unsafe {
+        let result = ffi::av_frame_ref(view.as_mut_ptr(), frame.as_ptr());
+        if result < 0 {
+            return Err(ffmpeg_next::Error::from(result));
+        }
+        let raw = &mut *view.as_mut_ptr();
+        raw.crop_left = region.x as usize;
+        raw.crop_top = region.y as usize;
+        raw.crop_right = (frame.width() - region.x - region.width) as usize;
+        raw.crop_bottom = (frame.height() - region.y - region.height) as usize;
+        let result = ffi::av_frame_apply_cropping(view.as_mut_ptr(), AV_FRAME_CROP_UNALIGNED);
+        if result < 0 {
+            return Err(ffmpeg_next::Error::from(result));
+        }
+    }
+    Ok(view)
+}
+
+/// Bits per component of `format`, or 8 if it is unknown
+pub fn bit_depth(format: Pixel) -> u32 {
+    match format.descriptor() {
+        Some(descriptor) => unsafe { (*descriptor.as_ptr()).comp[0].depth as u32 },
+        None => 8,
+    }
+}
+
+fn format_flags(format: Pixel) -> u64 {
+    match format.descriptor() {
+        Some(descriptor) => unsafe { (*descriptor.as_ptr()).flags },
+        None => 0,
+    }
+}
+
+fn has_alpha(format: Pixel) -> bool {
+    format_flags(format) & AV_PIX_FMT_FLAG_ALPHA != 0
+}
+
+fn is_rgb(format: Pixel) -> bool {
+    format_flags(format) & AV_PIX_FMT_FLAG_RGB != 0
+}
+
+/// Planar format with the depth, chroma subsampling, alpha and colour model of `format`
+///
+/// Semi-planar and packed formats such as NV12, P010 and RGBA map onto their planar
+/// counterparts, which are what the intermediate encoders take. None if FFmpeg has no such format.
+fn planar_equivalent(format: Pixel) -> Option<Pixel> {
+    let descriptor = format.descriptor()?;
+    let raw = unsafe { &*descriptor.as_ptr() };
+    let depth = raw.comp[0].depth;
+    let alpha = if raw.flags & AV_PIX_FMT_FLAG_ALPHA != 0 { "a" } else { "" };
+    let name = if raw.flags & AV_PIX_FMT_FLAG_RGB != 0 {
+        format!("gbr{}p", alpha)
+    } else if raw.nb_components - u8::from(!alpha.is_empty()) == 1 {
+        // Grey, whose formats with alpha are named ya8 and ya16
+        return match (alpha, depth) {
+            ("", 8) => Some(Pixel::GRAY8),
+            ("", depth) => format!("gray{}le", depth).parse().ok(),
+            (_, 8) => Some(Pixel::YA8),
+            (_, 16) => Some(Pixel::YA16LE),
+            _ => None,
+        };
+    } else {
+        let subsampling = match (raw.log2_chroma_w, raw.log2_chroma_h) {
+            (0, 0) => "444",
+            (0, 1) => "440",
+            (1, 0) => "422",
+            (1, 1) => "420",
+            (2, 0) => "411",
+            (2, 2) => "410",
+            _ => return None,
+        };
+        format!("yuv{}{}p", alpha, subsampling)
+    };
+    let name = if depth == 8 { name } else { format!("{}{}le", name, depth) };
+    name.parse().ok()
+}
+
+/// Pixel component values of an 0xRRGGBB colour in `format`, in the format's component order
+///
+/// YUV values use the limited range (full for the JPEG formats) and the BT.709 matrix when
+/// `color_space` says so, BT.601 otherwise.
+pub fn color_components(rgb: u32, format: Pixel, color_space: color::Space) -> [u32; 4] {
+    let channel = |shift: u32| ((rgb >> shift) & 0xFF) as f64 / 255.0;
+    let (red, green, blue) = (channel(16), channel(8), channel(0));
+
+    let descriptor = match format.descriptor() {
+        Some(descriptor) => descriptor,
+        None => return [0; 4],
+    };
+    let raw = unsafe { &*descriptor.as_ptr() };
+    let depth = |component: usize| raw.comp[component].depth as u32;
+    let scale = |value: f64, component: usize| -> u32 {
+        let max = ((1u32 << depth(component)) - 1) as f64;
+        (value * max).round().clamp(0.0, max) as u32
+    };
+    let alpha = (1u32 << depth(3).max(1)) - 1;
+
+    if raw.flags & AV_PIX_FMT_FLAG_RGB != 0 {
+        return [scale(red, 0), scale(green, 1), scale(blue, 2), alpha];
+    }
+
+    let (kr, kb) = match color_space {
+        color::Space::BT709 => (0.2126, 0.0722),
+        _ => (0.299, 0.114),
+    };
+    let luma = kr * red + (1.0 - kr - kb) * green + kb * blue;
+    let cb = (blue - luma) / (2.0 * (1.0 - kb));
+    let cr = (red - luma) / (2.0 * (1.0 - kr));
+    let full_range = matches!(format, Pixel::YUVJ420P | Pixel::YUVJ422P | Pixel::YUVJ444P | Pixel::YUVJ440P);
+    // Values at 8 bits, shifted up for deeper formats
+    let (y, u, v) = if full_range {
+        (luma * 255.0, 128.0 + cb * 255.0, 128.0 + cr * 255.0)
+    } else {
+        (16.0 + luma * 219.0, 128.0 + cb * 224.0, 128.0 + cr * 224.0)
+    };
+    let at_depth = |value: f64, component: usize| -> u32 {
+        (value.round().clamp(0.0, 255.0) as u32) << depth(component).saturating_sub(8)
+    };
+    [at_depth(y, 0), at_depth(u, 1), at_depth(v, 2), alpha]
+}
+
+/// Allocate a frame filled with one colour
+///
+/// # Arguments
+///
+/// * `components` - Colour returned by [`color_components`] for `format`
+pub fn solid_frame(format: Pixel, width: u32, height: u32, components: [u32; 4]) -> Result<frame::Video, ffmpeg_next::Error> {
+    let mut frame = frame::Video::new(format, width, height);
+    unsafe {
+        let raw = &mut *frame.as_mut_ptr();
+        let mut data = [std::ptr::null_mut(); 4];
+        let mut linesizes = [0isize; 4];
+        for plane in 0..4 {
+            data[plane] = raw.data[plane];
+            linesizes[plane] = raw.linesize[plane] as isize;
+        }
+        let result = ffi::av_image_fill_color(
+            data.as_ptr(),
+            linesizes.as_ptr(),
+            format.into(),
+            components.as_ptr(),
+            width as c_int,
+            height as c_int,
+            0,
+        );
+        if result < 0 {
+            return Err(ffmpeg_next::Error::from(result));
+        }
+    }
+    Ok(frame)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SQUARE: Rational = Rational(1, 1);
+
+    fn rect(x: u32, y: u32, width: u32, height: u32) -> Rect {
+        Rect { x, y, width, height }
+    }
+
+    fn new_layout(source: (u32, u32), source_aspect_ratio: Rational, target: (u32, u32), mode: ResizeMode) -> ResizeLayout {
+        ResizeLayout::new(source.0, source.1, source_aspect_ratio, Pixel::YUV420P, target.0, target.1, mode)
+    }
+
+    #[test]
+    fn resize_layout() {
+        struct Case {
+            name: &'static str,
+            source: (u32, u32),
+            source_aspect_ratio: Rational,
+            target: (u32, u32),
+            mode: ResizeMode,
+            size: (u32, u32),
+            picture: Rect,
+            crop: Option<Rect>,
+            sample_aspect_ratio: Rational,
+        }
+
+        let cases = [
+            Case {
+                name: "exact landscape",
+                source: (1920, 1080),
+                source_aspect_ratio: SQUARE,
+                target: (1280, 720),
+                mode: ResizeMode::Exact,
+                size: (1280, 720),
+                picture: rect(0, 0, 1280, 720),
+                crop: None,
+                sample_aspect_ratio: SQUARE,
+            },
+            Case {
+                name: "exact into another shape stretches the pixels back",
+                source: (1920, 1080),
+                source_aspect_ratio: SQUARE,
+                target: (1280, 1280),
+                mode: ResizeMode::Exact,
+                size: (1280, 1280),
+                picture: rect(0, 0, 1280, 1280),
+                crop: None,
+                sample_aspect_ratio: Rational(16, 9),
+            },
+            Case {
+                name: "exact rounds an odd width down to even",
+                source: (1920, 1080),
+                source_aspect_ratio: SQUARE,
+                target: (853, 480),
+                mode: ResizeMode::Exact,
+                size: (852, 480),
+                picture: rect(0, 0, 852, 480),
+                crop: None,
+                sample_aspect_ratio: Rational(640, 639),
+            },
+            Case {
+                name: "exact derives the height from the width",
+                source: (1920, 1080),
+                source_aspect_ratio: SQUARE,
+                target: (640, 0),
+                mode: ResizeMode::Exact,
+                size: (640, 360),
+                picture: rect(0, 0, 640, 360),
+                crop: None,
+                sample_aspect_ratio: SQUARE,
+            },
+            Case {
+                name: "exact derives the width from the height of a portrait source",
+                source: (1080, 1920),
+                source_aspect_ratio: SQUARE,
+                target: (0, 640),
+                mode: ResizeMode::Exact,
+                size: (360, 640),
+                picture: rect(0, 0, 360, 640),
+                crop: None,
+                sample_aspect_ratio: SQUARE,
+            },
+            Case {
+                name: "zero target keeps the display size of an anamorphic source",
+                source: (960, 1080),
+                source_aspect_ratio: Rational(2, 1),
+                target: (0, 0),
+                mode: ResizeMode::Exact,
+                size: (1920, 1080),
+                picture: rect(0, 0, 1920, 1080),
+                crop: None,
+                sample_aspect_ratio: SQUARE,
+            },
+            Case {
+                name: "zero target rounds an odd source down to even",
+                source: (1921, 1081),
+                source_aspect_ratio: SQUARE,
+                target: (0, 0),
+                mode: ResizeMode::Exact,
+                size: (1920, 1080),
+                picture: rect(0, 0, 1920, 1080),
+                crop: None,
+                sample_aspect_ratio: Rational(17289, 17296),
+            },
+            Case {
+                name: "invalid source aspect ratio means square pixels",
+                source: (1920, 1080),
+                source_aspect_ratio: Rational(0, 1),
+                target: (0, 0),
+                mode: ResizeMode::Exact,
+                size: (1920, 1080),
+                picture: rect(0, 0, 1920, 1080),
+                crop: None,
+                sample_aspect_ratio: SQUARE,
+            },
+            Case {
+                name: "fit landscape into a square letterboxes",
+                source: (1920, 1080),
+                source_aspect_ratio: SQUARE,
+                target: (1080, 1080),
+                mode: ResizeMode::Fit,
+                size: (1080, 1080),
+                picture: rect(0, 236, 1080, 608),
+                crop: None,
+                sample_aspect_ratio: SQUARE,
+            },
+            Case {
+                name: "fit portrait into landscape pillarboxes",
+                source: (1080, 1920),
+                source_aspect_ratio: SQUARE,
+                target: (1280, 720),
+                mode: ResizeMode::Fit,
+                size: (1280, 720),
+                picture: rect(436, 0, 406, 720),
+                crop: None,
+                sample_aspect_ratio: SQUARE,
+            },
+            Case {
+                name: "fit with the same shape needs no padding",
+                source: (1920, 1080),
+                source_aspect_ratio: SQUARE,
+                target: (960, 540),
+                mode: ResizeMode::Fit,
+                size: (960, 540),
+                picture: rect(0, 0, 960, 540),
+                crop: None,
+                sample_aspect_ratio: SQUARE,
+            },
+            Case {
+                name: "fill landscape into a square crops the sides",
+                source: (1920, 1080),
+                source_aspect_ratio: SQUARE,
+                target: (1080, 1080),
+                mode: ResizeMode::Fill,
+                size: (1080, 1080),
+                picture: rect(0, 0, 1080, 1080),
+                crop: Some(rect(420, 0, 1080, 1080)),
+                sample_aspect_ratio: SQUARE,
+            },
+            Case {
+                name: "fill portrait into landscape crops the top and bottom on chroma boundaries",
+                source: (720, 1280),
+                source_aspect_ratio: SQUARE,
+                target: (1280, 720),
+                mode: ResizeMode::Fill,
+                size: (1280, 720),
+                picture: rect(0, 0, 1280, 720),
+                crop: Some(rect(0, 436, 720, 405)),
+                sample_aspect_ratio: SQUARE,
+            },
+            Case {
+                name: "fill with the same shape does not crop",
+                source: (1920, 1080),
+                source_aspect_ratio: SQUARE,
+                target: (960, 540),
+                mode: ResizeMode::Fill,
+                size: (960, 540),
+                picture: rect(0, 0, 960, 540),
+                crop: None,
+                sample_aspect_ratio: SQUARE,
+            },
+            Case {
+                name: "long edge shrinks a landscape source",
+                source: (1920, 1080),
+                source_aspect_ratio: SQUARE,
+                target: (960, 0),
+                mode: ResizeMode::LongEdge,
+                size: (960, 540),
+                picture: rect(0, 0, 960, 540),
+                crop: None,
+                sample_aspect_ratio: SQUARE,
+            },
+            Case {
+                name: "long edge uses the larger target dimension for a portrait source",
+                source: (1080, 1920),
+                source_aspect_ratio: SQUARE,
+                target: (540, 960),
+                mode: ResizeMode::LongEdge,
+                size: (540, 960),
+                picture: rect(0, 0, 540, 960),
+                crop: None,
+                sample_aspect_ratio: SQUARE,
+            },
+            Case {
+                name: "long edge bigger than the source does not upscale",
+                source: (1280, 720),
+                source_aspect_ratio: SQUARE,
+                target: (4000, 0),
+                mode: ResizeMode::LongEdge,
+                size: (1280, 720),
+                picture: rect(0, 0, 1280, 720),
+                crop: None,
+                sample_aspect_ratio: SQUARE,
+            },
+            Case {
+                name: "long edge with a zero target keeps an odd source, rounded to even",
+                source: (1281, 721),
+                source_aspect_ratio: SQUARE,
+                target: (0, 0),
+                mode: ResizeMode::LongEdge,
+                size: (1280, 720),
+                picture: rect(0, 0, 1280, 720),
+                crop: None,
+                sample_aspect_ratio: SQUARE,
+            },
+        ];
+
+        for case in cases {
+            let layout = new_layout(case.source, case.source_aspect_ratio, case.target, case.mode);
+            assert_eq!((layout.width, layout.height), case.size, "{}: size", case.name);
+            assert_eq!(layout.picture, case.picture, "{}: picture", case.name);
+            assert_eq!(layout.crop, case.crop, "{}: crop", case.name);
+            assert_eq!(layout.sample_aspect_ratio, case.sample_aspect_ratio, "{}: sample aspect ratio", case.name);
+            assert_eq!(layout.is_padded(), case.picture != rect(0, 0, case.size.0, case.size.1), "{}: padding", case.name);
+            assert!(layout.width % 2 == 0 && layout.height % 2 == 0, "{}: odd output size", case.name);
+        }
+    }
+
+    #[test]
+    fn resize_layout_never_goes_below_two_pixels() {
+        let layout = new_layout((1920, 1080), SQUARE, (1, 1), ResizeMode::Exact);
+        assert_eq!((layout.width, layout.height), (2, 2));
+    }
+
+    #[test]
+    fn source_size_follows_the_crop() {
+        let cropped = new_layout((1920, 1080), SQUARE, (1080, 1080), ResizeMode::Fill);
+        assert_eq!(cropped.source_size(1920, 1080), (1080, 1080));
+        let scaled = new_layout((1920, 1080), SQUARE, (1280, 720), ResizeMode::Exact);
+        assert_eq!(scaled.source_size(1920, 1080), (1920, 1080));
+    }
+
+    #[test]
+    fn display_size_swaps_quarter_turns() {
+        assert_eq!(display_size(1920, 1080, 0), (1920, 1080));
+        assert_eq!(display_size(1920, 1080, 90), (1080, 1920));
+        assert_eq!(display_size(1920, 1080, 180), (1920, 1080));
+        assert_eq!(display_size(1920, 1080, 270), (1080, 1920));
+    }
+
+    #[test]
+    fn resize_layout_of_a_rotated_phone_video() {
+        // Portrait video stored landscape with a 90 degree display matrix
+        let (width, height) = display_size(1920, 1080, 90);
+        let layout = new_layout((width, height), SQUARE, (1280, 720), ResizeMode::Fit);
+        assert_eq!((layout.width, layout.height), (1280, 720));
+        assert_eq!(layout.picture, rect(436, 0, 406, 720));
+
+        let layout = new_layout((width, height), SQUARE, (0, 960), ResizeMode::LongEdge);
+        assert_eq!((layout.width, layout.height), (540, 960));
+    }
+
+    fn options(codec: VideoCodec) -> CVideoOptions {
+        CVideoOptions {
+            resize_mode: 0,
+            pad_color: 0,
+            rate_control: 0,
+            crf: 0.0,
+            max_bitrate: 0,
+            buffer_size: 0,
+            target_size: 0,
+            passes: 0,
+            preset: 0,
+            tune: 0,
+            profile: 0,
+            level: 0,
+            gop_frames: 0,
+            gop_seconds: 0.0,
+            closed_gop: false,
+            disable_scene_cut: false,
+            b_frames: 0,
+            keyframe_times: std::ptr::null(),
+            keyframe_count: 0,
+            skip_if_compliant: false,
+            codec: codec as i32,
+            tile_columns: 0,
+            tile_rows: 0,
+            disable_row_mt: false,
+            prores_profile: 0,
+            dnxhr_profile: 0,
+            ffv1_slices: 0,
+        }
+    }
+
+    fn settings(options: &CVideoOptions) -> VideoSettings {
+        match unsafe { VideoSettings::from_c(options) } {
+            Ok(settings) => settings,
+            Err(e) => panic!("{:?} rejected: {}", options, e),
+        }
+    }
+
+    fn assert_rejected(options: CVideoOptions, message: &str) {
+        match unsafe { VideoSettings::from_c(&options) } {
+            Ok(_) => panic!("{:?} accepted, expected \"{}\"", options, message),
+            Err(e) => {
+                assert_eq!(e.code(), ErrorCode::InvalidArgument, "{}", e);
+                assert!(e.to_string().contains(message), "\"{}\" does not mention \"{}\"", e, message);
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_enum_values() {
+        assert_rejected(CVideoOptions { codec: 7, ..options(VideoCodec::H264) }, "Unknown video codec 7");
+        assert_rejected(CVideoOptions { resize_mode: 4, ..options(VideoCodec::H264) }, "Unknown resize mode 4");
+        assert_rejected(CVideoOptions { rate_control: 4, ..options(VideoCodec::H264) }, "Unknown rate control mode 4");
+        assert_rejected(CVideoOptions { preset: 10, ..options(VideoCodec::H264) }, "Unknown preset 10");
+        assert_rejected(CVideoOptions { tune: 5, ..options(VideoCodec::H264) }, "Unknown tune 5");
+        assert_rejected(CVideoOptions { profile: 4, ..options(VideoCodec::H264) }, "Unknown H.264 profile 4");
+        assert_rejected(CVideoOptions { prores_profile: 7, ..options(VideoCodec::ProRes) }, "Unknown ProRes profile 7");
+        assert_rejected(CVideoOptions { dnxhr_profile: 6, ..options(VideoCodec::Dnxhr) }, "Unknown DNxHR profile 6");
+    }
+
+    #[test]
+    fn rejects_a_pad_colour_with_alpha() {
+        assert_rejected(CVideoOptions { pad_color: 0x1_00_00_00, ..options(VideoCodec::H264) }, "Pad colour");
+    }
+
+    #[test]
+    fn rejects_crf_outside_the_codec_scale() {
+        assert_rejected(CVideoOptions { crf: 52.0, ..options(VideoCodec::H264) }, "between 0 and 51 for H.264");
+        assert_rejected(CVideoOptions { crf: 52.0, ..options(VideoCodec::Hevc) }, "between 0 and 51 for HEVC");
+        assert_rejected(CVideoOptions { crf: 64.0, ..options(VideoCodec::Vp9) }, "between 0 and 63 for VP9");
+        assert_rejected(CVideoOptions { crf: 64.0, ..options(VideoCodec::Av1) }, "between 0 and 63 for AV1");
+        assert_rejected(CVideoOptions { crf: f64::NAN, ..options(VideoCodec::H264) }, "CRF");
+        assert_rejected(CVideoOptions { crf: f64::INFINITY, ..options(VideoCodec::H264) }, "CRF");
+    }
+
+    #[test]
+    fn rejects_target_size_without_a_size() {
+        let target_size = RateControl::TargetSize as i32;
+        assert_rejected(CVideoOptions { rate_control: target_size, ..options(VideoCodec::H264) }, "Target size 0");
+        assert_rejected(
+            CVideoOptions { rate_control: target_size, target_size: -1, ..options(VideoCodec::H264) },
+            "Target size -1",
+        );
+    }
+
+    #[test]
+    fn rejects_unsupported_passes() {
+        assert_rejected(
+            CVideoOptions { rate_control: RateControl::Crf as i32, passes: 2, ..options(VideoCodec::H264) },
+            "Two-pass encoding needs ABR or target size",
+        );
+        assert_rejected(
+            CVideoOptions { rate_control: RateControl::Cbr as i32, passes: 2, ..options(VideoCodec::H264) },
+            "Two-pass encoding needs ABR or target size",
+        );
+        assert_rejected(CVideoOptions { passes: 3, ..options(VideoCodec::H264) }, "Unsupported number of passes 3");
+        assert_rejected(CVideoOptions { passes: 2, ..options(VideoCodec::Vp9) }, "not supported for VP9");
+        assert_rejected(CVideoOptions { passes: 2, ..options(VideoCodec::Av1) }, "not supported for AV1");
+    }
+
+    #[test]
+    fn rejects_tunes_the_encoder_lacks() {
+        let still_image = EncoderTune::StillImage as i32;
+        assert_rejected(CVideoOptions { tune: still_image, ..options(VideoCodec::Hevc) }, "HEVC output has no StillImage tune");
+        assert_rejected(CVideoOptions { tune: EncoderTune::Animation as i32, ..options(VideoCodec::Vp9) }, "VP9 output has no Animation tune");
+        assert_rejected(CVideoOptions { tune: EncoderTune::Film as i32, ..options(VideoCodec::Av1) }, "AV1 output has no Film tune");
+        assert_rejected(CVideoOptions { tune: EncoderTune::Film as i32, ..options(VideoCodec::Ffv1) }, "FFV1 output has no Film tune");
+    }
+
+    #[test]
+    fn rejects_profiles_of_another_codec() {
+        let main = H264Profile::Main as i32;
+        assert_rejected(CVideoOptions { profile: main, ..options(VideoCodec::Hevc) }, "An H.264 profile cannot be used for HEVC");
+        assert_rejected(CVideoOptions { profile: main, ..options(VideoCodec::ProRes) }, "An H.264 profile cannot be used for ProRes");
+        let hq = ProResProfile::Hq as i32;
+        assert_rejected(CVideoOptions { prores_profile: hq, ..options(VideoCodec::H264) }, "A ProRes profile cannot be used for H.264");
+        assert_rejected(CVideoOptions { prores_profile: hq, ..options(VideoCodec::Dnxhr) }, "A ProRes profile cannot be used for DNxHR");
+        let hqx = DnxhrProfile::Hqx as i32;
+        assert_rejected(CVideoOptions { dnxhr_profile: hqx, ..options(VideoCodec::ProRes) }, "A DNxHR profile cannot be used for ProRes");
+    }
+
+    #[test]
+    fn rejects_levels_the_codec_does_not_have() {
+        assert_rejected(CVideoOptions { level: 33, ..options(VideoCodec::H264) }, "Unknown H.264 level 33");
+        assert_rejected(CVideoOptions { level: -31, ..options(VideoCodec::H264) }, "Unknown H.264 level -31");
+        // 1b and 1.1 exist in H.264 but not in HEVC
+        assert_rejected(CVideoOptions { level: 11, ..options(VideoCodec::Hevc) }, "Unknown HEVC level 11");
+        assert_rejected(CVideoOptions { level: 31, ..options(VideoCodec::Vp9) }, "VP9 output cannot target a level");
+        assert_rejected(CVideoOptions { level: 31, ..options(VideoCodec::Av1) }, "AV1 output cannot target a level");
+        assert_rejected(CVideoOptions { level: 31, ..options(VideoCodec::Dnxhr) }, "DNxHR output cannot target a level");
+    }
+
+    #[test]
+    fn rejects_invalid_gop_options() {
+        assert_rejected(CVideoOptions { gop_frames: -1, ..options(VideoCodec::H264) }, "GOP length cannot be negative");
+        assert_rejected(CVideoOptions { gop_seconds: -2.0, ..options(VideoCodec::H264) }, "GOP length cannot be negative");
+        assert_rejected(CVideoOptions { gop_seconds: f64::NAN, ..options(VideoCodec::H264) }, "GOP length cannot be negative");
+        assert_rejected(
+            CVideoOptions { gop_frames: 60, gop_seconds: 2.0, ..options(VideoCodec::H264) },
+            "in frames or in seconds, not both",
+        );
+        assert_rejected(CVideoOptions { b_frames: 17, ..options(VideoCodec::H264) }, "B-frame count 17");
+        assert_rejected(CVideoOptions { b_frames: -2, ..options(VideoCodec::H264) }, "B-frame count -2");
+        assert_rejected(CVideoOptions { b_frames: 2, ..options(VideoCodec::Vp9) }, "VP9 has no B-frames");
+        assert_rejected(CVideoOptions { b_frames: 2, ..options(VideoCodec::Av1) }, "AV1 has no B-frames");
+    }
+
+    #[test]
+    fn rejects_invalid_keyframe_times() {
+        assert_rejected(CVideoOptions { keyframe_count: 2, ..options(VideoCodec::H264) }, "Keyframe times are null");
+        let times = [1.0, -1.0];
+        assert_rejected(
+            CVideoOptions { keyframe_times: times.as_ptr(), keyframe_count: 2, ..options(VideoCodec::H264) },
+            "Keyframe times must be non-negative",
+        );
+        let times = [f64::NAN];
+        assert_rejected(
+            CVideoOptions { keyframe_times: times.as_ptr(), keyframe_count: 1, ..options(VideoCodec::H264) },
+            "Keyframe times must be non-negative",
+        );
+    }
+
+    #[test]
+    fn rejects_tiles_outside_vp9() {
+        assert_rejected(CVideoOptions { tile_columns: 2, ..options(VideoCodec::H264) }, "Tile columns are only used for VP9");
+        assert_rejected(CVideoOptions { tile_rows: 2, ..options(VideoCodec::Av1) }, "Tile rows are only used for VP9");
+        assert_rejected(CVideoOptions { tile_columns: 3, ..options(VideoCodec::Vp9) }, "3 tile columns must be a power of two up to 64");
+        assert_rejected(CVideoOptions { tile_columns: 128, ..options(VideoCodec::Vp9) }, "128 tile columns");
+        assert_rejected(CVideoOptions { tile_rows: 8, ..options(VideoCodec::Vp9) }, "8 tile rows must be a power of two up to 4");
+        assert_rejected(CVideoOptions { tile_rows: -1, ..options(VideoCodec::Vp9) }, "-1 tile rows");
+    }
+
+    #[test]
+    fn rejects_delivery_options_for_intermediate_codecs() {
+        for codec in [VideoCodec::ProRes, VideoCodec::Dnxhr, VideoCodec::Ffv1] {
+            let no_rate_control = "takes no rate control options";
+            assert_rejected(CVideoOptions { rate_control: RateControl::Crf as i32, ..options(codec) }, no_rate_control);
+            assert_rejected(CVideoOptions { crf: 10.0, ..options(codec) }, no_rate_control);
+            assert_rejected(CVideoOptions { max_bitrate: 50_000_000, ..options(codec) }, no_rate_control);
+            assert_rejected(CVideoOptions { buffer_size: 50_000_000, ..options(codec) }, no_rate_control);
+            assert_rejected(CVideoOptions { passes: 2, ..options(codec) }, no_rate_control);
+            assert_rejected(CVideoOptions { preset: EncoderPreset::Slow as i32, ..options(codec) }, "takes no preset");
+
+            let no_gop = "so it takes no GOP, B-frame or keyframe options";
+            assert_rejected(CVideoOptions { gop_frames: 30, ..options(codec) }, no_gop);
+            assert_rejected(CVideoOptions { gop_seconds: 1.0, ..options(codec) }, no_gop);
+            assert_rejected(CVideoOptions { closed_gop: true, ..options(codec) }, no_gop);
+            assert_rejected(CVideoOptions { disable_scene_cut: true, ..options(codec) }, no_gop);
+            assert_rejected(CVideoOptions { b_frames: -1, ..options(codec) }, no_gop);
+            let times = [1.0];
+            assert_rejected(CVideoOptions { keyframe_times: times.as_ptr(), keyframe_count: 1, ..options(codec) }, no_gop);
+        }
+    }
+
+    #[test]
+    fn rejects_ffv1_slices_elsewhere_or_off_the_grid() {
+        assert_rejected(CVideoOptions { ffv1_slices: 4, ..options(VideoCodec::ProRes) }, "Slices are only set for FFV1");
+        assert_rejected(CVideoOptions { ffv1_slices: 5, ..options(VideoCodec::Ffv1) }, "FFV1 slice count 5");
+        assert_rejected(CVideoOptions { ffv1_slices: -4, ..options(VideoCodec::Ffv1) }, "FFV1 slice count -4");
+    }
+
+    #[test]
+    fn default_options_keep_the_defaults() {
+        let settings = settings(&options(VideoCodec::H264));
+        assert_eq!(settings.codec, VideoCodec::H264);
+        assert_eq!(settings.resize_mode, ResizeMode::Exact);
+        assert_eq!(settings.rate_control, RateControl::Abr);
+        assert_eq!(settings.crf, None);
+        assert_eq!(settings.max_bit_rate, None);
+        assert_eq!(settings.buffer_size, None);
+        assert_eq!(settings.target_size, None);
+        assert!(!settings.two_pass);
+        assert_eq!(settings.preset, None);
+        assert_eq!(settings.tune, None);
+        assert_eq!(settings.profile, None);
+        assert_eq!(settings.level, None);
+        assert_eq!(settings.b_frames, None);
+        assert!(!settings.has_gop_options());
+        assert_eq!(settings.av_profile(), None);
+    }
+
+    #[test]
+    fn accepts_h264() {
+        let times = [4.0, 2.0, 2.0];
+        let settings = settings(&CVideoOptions {
+            resize_mode: ResizeMode::Fit as i32,
+            pad_color: 0xFF_FF_FF,
+            rate_control: RateControl::Crf as i32,
+            crf: 18.0,
+            max_bitrate: 4_000_000,
+            buffer_size: 8_000_000,
+            preset: EncoderPreset::Slow as i32,
+            tune: EncoderTune::Film as i32,
+            profile: H264Profile::Main as i32,
+            level: 31,
+            gop_seconds: 2.0,
+            closed_gop: true,
+            b_frames: -1,
+            keyframe_times: times.as_ptr(),
+            keyframe_count: times.len() as i32,
+            ..options(VideoCodec::H264)
+        });
+        assert_eq!(settings.resize_mode, ResizeMode::Fit);
+        assert_eq!(settings.pad_color, 0xFF_FF_FF);
+        assert_eq!(settings.rate_control, RateControl::Crf);
+        assert_eq!(settings.crf, Some(18.0));
+        assert_eq!(settings.max_bit_rate, Some(4_000_000));
+        assert_eq!(settings.buffer_size, Some(8_000_000));
+        assert_eq!(settings.preset, Some(EncoderPreset::Slow));
+        assert_eq!(settings.tune, Some(EncoderTune::Film));
+        assert_eq!(settings.profile, Some(H264Profile::Main));
+        assert_eq!(settings.level, Some(31));
+        assert_eq!(settings.gop_frames, None);
+        assert_eq!(settings.gop_seconds, Some(2.0));
+        assert!(settings.closed_gop);
+        assert_eq!(settings.b_frames, Some(0));
+        // Sorted and without duplicates
+        assert_eq!(settings.keyframe_times, vec![2.0, 4.0]);
+        assert!(settings.has_gop_options());
+    }
+
+    #[test]
+    fn accepts_two_pass_target_size() {
+        let settings = settings(&CVideoOptions {
+            rate_control: RateControl::TargetSize as i32,
+            target_size: 10_000_000,
+            passes: 2,
+            ..options(VideoCodec::H264)
+        });
+        assert_eq!(settings.target_size, Some(10_000_000));
+        assert!(settings.two_pass);
+    }
+
+    #[test]
+    fn target_size_defaults_to_two_passes_where_supported() {
+        let target_size = |codec, passes| {
+            settings(&CVideoOptions {
+                rate_control: RateControl::TargetSize as i32,
+                target_size: 10_000_000,
+                passes,
+                ..options(codec)
+            })
+            .two_pass
+        };
+        assert!(target_size(VideoCodec::H264, 0));
+        assert!(target_size(VideoCodec::Hevc, 0));
+        assert!(!target_size(VideoCodec::Vp9, 0));
+        assert!(!target_size(VideoCodec::Av1, 0));
+        assert!(!target_size(VideoCodec::H264, 1));
+    }
+
+    #[test]
+    fn accepts_hevc() {
+        let settings = settings(&CVideoOptions {
+            passes: 2,
+            tune: EncoderTune::Animation as i32,
+            level: 51,
+            b_frames: 4,
+            ..options(VideoCodec::Hevc)
+        });
+        assert_eq!(settings.codec, VideoCodec::Hevc);
+        assert!(settings.two_pass);
+        assert_eq!(settings.tune, Some(EncoderTune::Animation));
+        assert_eq!(settings.level, Some(51));
+        assert_eq!(settings.b_frames, Some(4));
+    }
+
+    #[test]
+    fn accepts_vp9() {
+        let settings = settings(&CVideoOptions {
+            rate_control: RateControl::Crf as i32,
+            crf: 63.0,
+            max_bitrate: 2_000_000,
+            tune: EncoderTune::ZeroLatency as i32,
+            b_frames: -1,
+            tile_columns: 4,
+            tile_rows: 2,
+            disable_row_mt: true,
+            ..options(VideoCodec::Vp9)
+        });
+        assert_eq!(settings.codec, VideoCodec::Vp9);
+        assert_eq!(settings.crf, Some(63.0));
+        assert_eq!(settings.max_bit_rate, Some(2_000_000));
+        assert_eq!(settings.b_frames, Some(0));
+        assert_eq!(settings.tile_columns_log2, Some(2));
+        assert_eq!(settings.tile_rows_log2, Some(1));
+        assert!(settings.disable_row_mt);
+    }
+
+    #[test]
+    fn accepts_av1() {
+        let settings = settings(&CVideoOptions {
+            rate_control: RateControl::Crf as i32,
+            crf: 40.0,
+            preset: EncoderPreset::Veryslow as i32,
+            gop_frames: 240,
+            disable_scene_cut: true,
+            ..options(VideoCodec::Av1)
+        });
+        assert_eq!(settings.codec, VideoCodec::Av1);
+        assert_eq!(settings.crf, Some(40.0));
+        assert_eq!(settings.preset, Some(EncoderPreset::Veryslow));
+        assert_eq!(settings.gop_frames, Some(240));
+        assert!(settings.disable_scene_cut);
+    }
+
+    #[test]
+    fn accepts_intermediate_codecs() {
+        let prores = settings(&CVideoOptions { prores_profile: ProResProfile::P4444 as i32, ..options(VideoCodec::ProRes) });
+        assert_eq!(prores.prores_profile, Some(ProResProfile::P4444));
+        assert_eq!(prores.av_profile(), Some(4));
+        // Without a profile, ProRes is HQ
+        assert_eq!(settings(&options(VideoCodec::ProRes)).av_profile(), Some(3));
+
+        let dnxhr = settings(&CVideoOptions { dnxhr_profile: DnxhrProfile::Hqx as i32, ..options(VideoCodec::Dnxhr) });
+        assert_eq!(dnxhr.dnxhr_profile, Some(DnxhrProfile::Hqx));
+        assert_eq!(dnxhr.av_profile(), Some(DnxhrProfile::Hqx as i32));
+
+        let ffv1 = settings(&CVideoOptions { ffv1_slices: 16, ..options(VideoCodec::Ffv1) });
+        assert_eq!(ffv1.ffv1_slices, Some(16));
+        assert_eq!(ffv1.av_profile(), None);
+    }
+}